@@ -1,4 +1,5 @@
 
+use std::convert::TryFrom;
 use std::vec::Vec;
 
 extern crate rand;
@@ -13,10 +14,9 @@ use criterion::Criterion;
 extern crate aeonflux;
 
 use aeonflux::amacs::Attribute;
-use aeonflux::amacs::SecretKey;
+use aeonflux::credential::AnonymousCredential;
 use aeonflux::issuer::Issuer;
 use aeonflux::nizk::ProofOfValidCredential;
-use aeonflux::parameters::IssuerParameters;
 use aeonflux::parameters::SystemParameters;
 use aeonflux::symmetric::Plaintext;
 use aeonflux::symmetric::Keypair as SymmetricKeypair;
@@ -32,16 +32,17 @@ mod proof_of_credential_benches {
     fn creation_1(c: &mut Criterion) {
         let mut rng = thread_rng();
         let system_parameters = SystemParameters::generate(&mut rng, 1).unwrap();
-        let amacs_key = SecretKey::generate(&mut rng, &system_parameters);
-        let issuer_parameters = IssuerParameters::generate(&system_parameters, &amacs_key);
-        let issuer = Issuer::new(&system_parameters, &issuer_parameters, &amacs_key);
-        let plaintext: Plaintext = b"This is a tsunami alert test..".into();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let issuer_parameters = issuer.issuer_parameters.clone();
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
 
         let mut attributes = Vec::new();
 
         attributes.push(Attribute::SecretPoint(plaintext));
 
-        let credential = issuer.issue(attributes, &mut rng).unwrap();
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer_parameters,
+                                                                     &response, attributes).unwrap();
         let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
 
         c.bench_function("Proof-of-Valid-Credential with 1 attribute Creation", |b| {
@@ -52,31 +53,31 @@ mod proof_of_credential_benches {
     fn verification_1(c: &mut Criterion) {
         let mut rng = thread_rng();
         let system_parameters = SystemParameters::generate(&mut rng, 1).unwrap();
-        let amacs_key = SecretKey::generate(&mut rng, &system_parameters);
-        let issuer_parameters = IssuerParameters::generate(&system_parameters, &amacs_key);
-        let issuer = Issuer::new(&system_parameters, &issuer_parameters, &amacs_key);
-        let plaintext: Plaintext = b"This is a tsunami alert test..".into();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let issuer_parameters = issuer.issuer_parameters.clone();
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
 
         let mut attributes = Vec::new();
 
         attributes.push(Attribute::SecretPoint(plaintext));
 
-        let credential = issuer.issue(attributes, &mut rng).unwrap();
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer_parameters,
+                                                                     &response, attributes).unwrap();
         let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
         let proof = ProofOfValidCredential::prove(&system_parameters, &issuer_parameters, &credential, Some(&keypair), &mut rng).unwrap();
 
         c.bench_function("Proof-of-Valid-Credential with 1 attribute Verification", |b| {
-            b.iter(|| proof.verify(&issuer, &credential));
+            b.iter(|| proof.verify(&issuer));
         });
     }
 
     fn creation_8(c: &mut Criterion) {
         let mut rng = thread_rng();
         let system_parameters = SystemParameters::generate(&mut rng, 8).unwrap();
-        let amacs_key = SecretKey::generate(&mut rng, &system_parameters);
-        let issuer_parameters = IssuerParameters::generate(&system_parameters, &amacs_key);
-        let issuer = Issuer::new(&system_parameters, &issuer_parameters, &amacs_key);
-        let plaintext: Plaintext = b"This is a tsunami alert test..".into();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let issuer_parameters = issuer.issuer_parameters.clone();
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
 
         let mut attributes = Vec::new();
 
@@ -89,7 +90,9 @@ mod proof_of_credential_benches {
         attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
         attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
 
-        let credential = issuer.issue(attributes, &mut rng).unwrap();
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer_parameters,
+                                                                     &response, attributes).unwrap();
         let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
 
         c.bench_function("Proof-of-Valid-Credential with 8 attributes Creation", |b| {
@@ -100,10 +103,9 @@ mod proof_of_credential_benches {
     fn verification_8(c: &mut Criterion) {
         let mut rng = thread_rng();
         let system_parameters = SystemParameters::generate(&mut rng, 8).unwrap();
-        let amacs_key = SecretKey::generate(&mut rng, &system_parameters);
-        let issuer_parameters = IssuerParameters::generate(&system_parameters, &amacs_key);
-        let issuer = Issuer::new(&system_parameters, &issuer_parameters, &amacs_key);
-        let plaintext: Plaintext = b"This is a tsunami alert test..".into();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let issuer_parameters = issuer.issuer_parameters.clone();
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
 
         let mut attributes = Vec::new();
 
@@ -116,12 +118,14 @@ mod proof_of_credential_benches {
         attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
         attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
 
-        let credential = issuer.issue(attributes, &mut rng).unwrap();
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer_parameters,
+                                                                     &response, attributes).unwrap();
         let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
         let proof = ProofOfValidCredential::prove(&system_parameters, &issuer_parameters, &credential, Some(&keypair), &mut rng).unwrap();
 
         c.bench_function("Proof-of-Valid-Credential with 8 attributes Verification", |b| {
-            b.iter(|| proof.verify(&issuer, &credential));
+            b.iter(|| proof.verify(&issuer));
         });
     }
 
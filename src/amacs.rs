@@ -22,6 +22,9 @@ use alloc::vec::Vec;
 #[cfg(all(not(feature = "alloc"), feature = "std"))]
 use std::vec::Vec;
 
+use core::convert::TryFrom;
+
+use curve25519_dalek::ristretto::CompressedRistretto;
 use curve25519_dalek::ristretto::RistrettoPoint;
 use curve25519_dalek::scalar::Scalar;
 use curve25519_dalek::traits::Identity;
@@ -35,13 +38,21 @@ use serde::de::Visitor;
 
 use zeroize::Zeroize;
 
+use crate::elgamal::Ciphertext as ElGamalCiphertext;
+use crate::elgamal::Keypair as ElGamalKeypair;
+use crate::elgamal::PublicKey as ElGamalPublicKey;
+use crate::encoding::chunk_bytes;
+use crate::encoding::decode_from_group;
+use crate::encoding::encode_to_group;
+use crate::encoding::unchunk_bytes;
+use crate::errors::CredentialError;
 use crate::errors::MacError;
 use crate::parameters::SystemParameters;
 use crate::symmetric::Plaintext;
 
 /// Determine the size of a [`SecretKey`], in bytes.
 pub(crate) fn sizeof_secret_key(number_of_attributes: u8) -> usize {
-    32 * (6 + number_of_attributes) as usize
+    1 + 32 * (5 + number_of_attributes as usize)
 }
 
 /// An AMAC secret key is \(( (w, w', x_0, x_1, \vec{y_{n}}, W ) \in \mathbb{Z}_q \))
@@ -105,14 +116,79 @@ impl SecretKey {
         SecretKey { w, w_prime, x_0, x_1, y, W }
     }
 
-    /// DOCDOC
+    /// Deserialise a [`SecretKey`] from bytes.
+    ///
+    /// The format is the number of attributes `n`, followed by the scalars
+    /// \(( (w, w', x_0, x_1, y_0, \ldots, y_n) \)), followed by the compressed
+    /// point \(( W \)).  All scalars must be canonically encoded.
+    ///
+    /// Any intermediate copies of the key material are zeroed before returning.
     pub(crate) fn from_bytes(bytes: &[u8]) -> Result<SecretKey, MacError> {
-        unimplemented!()
+        if bytes.len() < 1 || bytes.len() != sizeof_secret_key(bytes[0]) {
+            return Err(MacError::KeypairDeserialisation);
+        }
+
+        let number_of_attributes: usize = bytes[0] as usize;
+        let mut scalars: Vec<Scalar> = Vec::with_capacity(4 + number_of_attributes);
+        let mut chunk = [0u8; 32];
+        let mut index: usize = 1;
+
+        for _ in 0..4 + number_of_attributes {
+            chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+
+            match Scalar::from_canonical_bytes(chunk) {
+                Some(x) => scalars.push(x),
+                None    => {
+                    chunk.zeroize();
+                    scalars.zeroize();
+                    return Err(MacError::ScalarFormatError);
+                },
+            }
+        }
+
+        chunk.copy_from_slice(&bytes[index..index+32]);
+
+        let W: RistrettoPoint = match CompressedRistretto(chunk).decompress() {
+            Some(x) => x,
+            None    => {
+                chunk.zeroize();
+                scalars.zeroize();
+                return Err(MacError::PointDecompressionError);
+            },
+        };
+
+        chunk.zeroize();
+
+        let w:       Scalar = scalars[0];
+        let w_prime: Scalar = scalars[1];
+        let x_0:     Scalar = scalars[2];
+        let x_1:     Scalar = scalars[3];
+        let y: Vec<Scalar> = scalars[4..].to_vec();
+
+        scalars.zeroize();
+
+        Ok(SecretKey { w, w_prime, x_0, x_1, y, W })
     }
 
-    /// DOCDOC
+    /// Serialise this [`SecretKey`] to a byte array.
+    ///
+    /// The caller is responsible for zeroing the returned bytes once they are
+    /// no longer needed.
     pub(crate) fn to_bytes(&self) -> Vec<u8> {
-        unimplemented!()
+        let mut v: Vec<u8> = Vec::with_capacity(sizeof_secret_key(self.y.len() as u8));
+
+        v.push(self.y.len() as u8);
+        v.extend(self.w.as_bytes().iter());
+        v.extend(self.w_prime.as_bytes().iter());
+        v.extend(self.x_0.as_bytes().iter());
+        v.extend(self.x_1.as_bytes().iter());
+
+        for y_i in self.y.iter() {
+            v.extend(y_i.as_bytes().iter());
+        }
+
+        v.extend(self.W.compress().to_bytes().iter());
+        v
     }
 }
 
@@ -137,6 +213,71 @@ pub enum Attribute {
     SecretPoint(Plaintext),
 }
 
+impl Attribute {
+    /// Split an arbitrary-length byte string into group element attributes
+    /// which are revealed upon credential presentation.
+    ///
+    /// The returned attributes must occupy consecutive slots in the
+    /// credential, in order.  See [`encoding::chunk_bytes`] for the framing.
+    pub fn from_public_bytes(data: &[u8]) -> Result<Vec<Attribute>, CredentialError> {
+        let mut attributes: Vec<Attribute> = Vec::new();
+
+        for chunk in chunk_bytes(data).iter() {
+            attributes.push(Attribute::PublicPoint(encode_to_group(chunk)?.0));
+        }
+        Ok(attributes)
+    }
+
+    /// Split an arbitrary-length byte string into group element attributes
+    /// which are hidden upon credential presentation.
+    ///
+    /// Each chunk is encrypted and proven separately when the credential is
+    /// shown, so the presentation covers every chunk.  The returned attributes
+    /// must occupy consecutive slots in the credential, in order.
+    pub fn from_secret_bytes(data: &[u8]) -> Result<Vec<Attribute>, CredentialError> {
+        let mut attributes: Vec<Attribute> = Vec::new();
+
+        for chunk in chunk_bytes(data).iter() {
+            attributes.push(Attribute::SecretPoint(Plaintext::try_from(&chunk[..])?));
+        }
+        Ok(attributes)
+    }
+
+    /// Reassemble a byte string from the group element `attributes` created by
+    /// [`Attribute::from_public_bytes`] or [`Attribute::from_secret_bytes`].
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the original byte string, otherwise a
+    /// [`CredentialError::BadAttribute`] if any attribute is a scalar or the
+    /// chunks are malformed.
+    pub fn to_bytes_from_chunks(attributes: &[Attribute]) -> Result<Vec<u8>, CredentialError> {
+        let mut chunks: Vec<[u8; 30]> = Vec::with_capacity(attributes.len());
+        let mut chunk = [0u8; 30];
+
+        for attribute in attributes.iter() {
+            let mut data: Vec<u8> = match attribute {
+                Attribute::PublicPoint(M) => decode_from_group(M)?.0,
+                Attribute::SecretPoint(p) => Vec::<u8>::try_from(p)?,
+                _ => return Err(CredentialError::BadAttribute),
+            };
+
+            if data.len() != chunk.len() {
+                return Err(CredentialError::BadAttribute);
+            }
+            chunk.copy_from_slice(&data);
+            chunks.push(chunk);
+            data.zeroize();
+        }
+        chunk.zeroize();
+
+        let data = unchunk_bytes(&chunks).ok_or(CredentialError::BadAttribute);
+
+        chunks.zeroize();
+        data
+    }
+}
+
 // We can't derive this because generally in elliptic curve cryptography group
 // elements aren't used as secrets, thus curve25519-dalek doesn't impl Zeroize
 // for RistrettoPoint.
@@ -159,6 +300,15 @@ impl Drop for Attribute {
 }
 
 
+/// The type tag for a serialised [`Attribute::PublicScalar`] or [`EncryptedAttribute::PublicScalar`].
+pub(crate) const TAG_PUBLIC_SCALAR: u8 = 0;
+/// The type tag for a serialised [`Attribute::SecretScalar`] or [`EncryptedAttribute::SecretScalar`].
+pub(crate) const TAG_SECRET_SCALAR: u8 = 1;
+/// The type tag for a serialised [`Attribute::PublicPoint`] or [`EncryptedAttribute::PublicPoint`].
+pub(crate) const TAG_PUBLIC_POINT:  u8 = 2;
+/// The type tag for a serialised [`Attribute::SecretPoint`] or [`EncryptedAttribute::SecretPoint`].
+pub(crate) const TAG_SECRET_POINT:  u8 = 3;
+
 /// These are the form of the attributes during credential presentation, when
 /// some may be be hidden either by commiting to them and proving them in
 /// zero-knowledge (as is the case for hidden scalar attributes) or by
@@ -176,6 +326,33 @@ pub enum EncryptedAttribute {
     SecretPoint,
 }
 
+/// These are the form of the attributes during blinded credential issuance,
+/// where hidden scalar attributes are ElGamal encrypted to a key chosen by the
+/// user, such that the issuer never learns them.
+///
+/// Note that group element attributes which will later be hidden upon
+/// presentation are still revealed to the issuer, as in unblinded issuance,
+/// unless they are requested as [`BlindedAttribute::EncryptedPoint`]s by a
+/// protocol which separately proves what they encrypt, such as
+/// [`crate::profile::ProfileKeyCredentialRequest`].
+#[derive(Clone)]
+pub enum BlindedAttribute {
+    /// A scalar attribute which is revealed upon credential issuance.
+    PublicScalar(Scalar),
+    /// A scalar attribute \( m_i \) which is hidden upon credential issuance,
+    /// as an ElGamal encryption of \( G_m_i * m_i \).
+    SecretScalar(ElGamalCiphertext),
+    /// A group element attribute which is revealed upon credential issuance.
+    PublicPoint(RistrettoPoint),
+    /// A group element attribute which will be hidden upon credential
+    /// presentation, but which is revealed upon credential issuance.
+    SecretPoint(Plaintext),
+    /// A group element attribute which will be hidden upon credential
+    /// presentation, and which is hidden upon credential issuance as an
+    /// ElGamal encryption of its encoding, \( M_1 \).
+    EncryptedPoint(ElGamalCiphertext),
+}
+
 /// Messages are computed from `Attribute`s by scalar multiplying the scalar
 /// portions by their respective generator in `SystemParameters.G_m`.
 pub struct Messages(pub(crate) Vec<RistrettoPoint>);
@@ -199,32 +376,71 @@ impl Messages {
         }
         Messages(messages)
     }
+
+    /// Compute the messages for a set of `BlindedAttribute`s.
+    ///
+    /// The messages for attributes hidden from the issuer are set to the
+    /// identity element, such that they contribute nothing to an AMAC computed
+    /// over them.
+    pub(crate) fn from_blinded_attributes(
+        attributes: &Vec<BlindedAttribute>,
+        system_parameters: &SystemParameters
+    ) -> Messages
+    {
+        let mut messages: Vec<RistrettoPoint> = Vec::with_capacity(attributes.len());
+
+        for (i, attribute) in attributes.iter().enumerate() {
+            let M_i: RistrettoPoint = match attribute {
+                BlindedAttribute::PublicScalar(m) => m * system_parameters.G_m[i],
+                BlindedAttribute::SecretScalar(_) => RistrettoPoint::identity(),
+                BlindedAttribute::PublicPoint(M)  => *M,
+                BlindedAttribute::SecretPoint(p)  => p.M1,
+                BlindedAttribute::EncryptedPoint(_) => RistrettoPoint::identity(),
+            };
+            messages.push(M_i);
+        }
+        Messages(messages)
+    }
 }
 
 /// An algebraic message authentication code, \(( (t,U,V) \in \mathbb{Z}_q \times \mathbb{G} \times \mathbb{G} \)).
+#[derive(Clone)]
 pub(crate) struct Amac {
     pub(crate) t: Scalar,
     pub(crate) U: RistrettoPoint,
     pub(crate) V: RistrettoPoint,
 }
 
+// We can't derive this because generally in elliptic curve cryptography group
+// elements aren't used as secrets, thus curve25519-dalek doesn't impl Zeroize
+// for RistrettoPoint.
+impl Zeroize for Amac {
+    fn zeroize(&mut self) {
+        self.t.zeroize();
+
+        self.U = RistrettoPoint::identity();
+        self.V = RistrettoPoint::identity();
+    }
+}
+
 impl Amac {
     /// Compute \(( V = W + (U (x_0 + x_1 t)) + \sigma{i=1}{n} M_i y_i \)).
+    ///
+    /// Note that \( x_1 \) is multiplied by \( t \), as in MAC_GGM and in the
+    /// statements of [`crate::nizk::ProofOfIssuance`] and
+    /// [`crate::nizk::ProofOfValidCredential`], rather than added to it.
     fn compute_V(
-        system_parameters: &SystemParameters,
         secret_key: &SecretKey,
-        attributes: &Vec<Attribute>,
+        messages: &Messages,
         t: &Scalar,
         U: &RistrettoPoint,
     ) -> RistrettoPoint
     {
-        let messages: Messages = Messages::from_attributes(attributes, system_parameters);
-
-        // V = W + U * x_0 + U * x_1 + U * t
-        let mut V: RistrettoPoint = secret_key.W + (U * secret_key.x_0) + (U * secret_key.x_1) + (U * t);
+        // V = W + U * x_0 + U * x_1 * t
+        let mut V: RistrettoPoint = secret_key.W + (U * (secret_key.x_0 + (secret_key.x_1 * t)));
 
-        // V = W + U * x_0 + U * x_1 + U * t + \sigma{i=1}{n} M_i y_i
-        V += RistrettoPoint::multiscalar_mul(&secret_key.y[..], &messages.0[..]);
+        // V = W + U * x_0 + U * x_1 * t + \sigma{i=1}{n} M_i y_i
+        V += RistrettoPoint::multiscalar_mul(&secret_key.y[..messages.0.len()], &messages.0[..]);
         V
     }
 
@@ -246,7 +462,8 @@ impl Amac {
         let t: Scalar = Scalar::random(csprng);
         // XXX QUESTION are we okay with using the ristretto flavour or the elligator2 mapping here?
         let U: RistrettoPoint = RistrettoPoint::random(csprng);
-        let V: RistrettoPoint = Amac::compute_V(system_parameters, secret_key, messages, &t, &U);
+        let M: Messages = Messages::from_attributes(messages, system_parameters);
+        let V: RistrettoPoint = Amac::compute_V(secret_key, &M, &t, &U);
 
         Ok(Amac { t, U, V })
     }
@@ -258,18 +475,106 @@ impl Amac {
         secret_key: &SecretKey,
         messages: &Vec<Attribute>,
     ) -> bool {
-        let V_prime = Amac::compute_V(system_parameters, secret_key, messages, &self.t, &self.U);
+        let M: Messages = Messages::from_attributes(messages, system_parameters);
+        let V_prime = Amac::compute_V(secret_key, &M, &self.t, &self.U);
 
         self.V == V_prime
     }
 }
 
+/// An algebraic message authentication code computed over some attributes
+/// which are hidden from the issuer, \(( (t,U,E_V) \)), where \(( E_V \)) is
+/// an ElGamal encryption of \(( V \)) to the user's public key.
+pub(crate) struct BlindedAmac {
+    pub(crate) t: Scalar,
+    pub(crate) U: RistrettoPoint,
+    pub(crate) V: ElGamalCiphertext,
+}
+
+impl BlindedAmac {
+    /// Compute a blinded algebraic message authentication code with a secret
+    /// key for a vector of partially encrypted messages.
+    ///
+    /// Since the ElGamal encryption is additively homomorphic, we compute
+    ///
+    /// \(( E_V = Enc(W + (U (x_0 + x_1 t)) + \sigma_{i \notin \mathcal{H}}{M_i y_i}) +
+    ///           \sigma_{i \in \mathcal{H}}{E_i y_i} \))
+    ///
+    /// where \(( \mathcal{H} \)) is the set of hidden attributes, and the
+    /// outer encryption is made with a fresh nonce in order to rerandomise the
+    /// resulting ciphertext.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the `BlindedAmac` and the nonce used for
+    /// its encryption (which is needed to prove its correctness), otherwise a
+    /// [`MacError`].
+    pub(crate) fn tag<R>(
+        csprng: &mut R,
+        system_parameters: &SystemParameters,
+        secret_key: &SecretKey,
+        public_key: &ElGamalPublicKey,
+        messages: &Vec<BlindedAttribute>,
+    ) -> Result<(BlindedAmac, Scalar), MacError>
+    where
+        R: RngCore + CryptoRng,
+    {
+        if messages.len() > system_parameters.NUMBER_OF_ATTRIBUTES as usize {
+            return Err(MacError::MessageLengthError{length: system_parameters.NUMBER_OF_ATTRIBUTES as usize});
+        }
+
+        let t: Scalar = Scalar::random(csprng);
+        let U: RistrettoPoint = RistrettoPoint::random(csprng);
+        let nonce: Scalar = Scalar::random(csprng);
+
+        // Compute the portion of V which is over the revealed attributes and encrypt it.
+        let M: Messages = Messages::from_blinded_attributes(messages, system_parameters);
+        let V_revealed: RistrettoPoint = Amac::compute_V(secret_key, &M, &t, &U);
+        let mut V: ElGamalCiphertext = public_key.encrypt_with_nonce(system_parameters, &V_revealed, &nonce);
+
+        // Homomorphically add in the encrypted attributes.
+        for (i, attribute) in messages.iter().enumerate() {
+            match attribute {
+                BlindedAttribute::SecretScalar(E_i) | BlindedAttribute::EncryptedPoint(E_i) => {
+                    V.C1 += E_i.C1 * secret_key.y[i];
+                    V.C2 += E_i.C2 * secret_key.y[i];
+                },
+                _ => continue,
+            }
+        }
+
+        Ok((BlindedAmac { t, U, V }, nonce))
+    }
+
+    /// Decrypt this `BlindedAmac` to obtain an [`Amac`].
+    pub(crate) fn unblind(&self, keypair: &ElGamalKeypair) -> Amac {
+        Amac { t: self.t, U: self.U, V: keypair.decrypt(&self.V) }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     use rand::thread_rng;
 
+    #[test]
+    fn secret_key_serialize_deserialize() {
+        let mut rng = thread_rng();
+        let params = SystemParameters::generate(&mut rng, 3).unwrap();
+        let sk = SecretKey::generate(&mut rng, &params);
+        let mut bytes = sk.to_bytes();
+        let deserialized = SecretKey::from_bytes(&bytes).unwrap();
+
+        assert_eq!(deserialized.to_bytes(), bytes);
+        assert!(SecretKey::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        // Set the high bit of w so that it is no longer canonically encoded.
+        bytes[32] |= 0x80;
+
+        assert!(SecretKey::from_bytes(&bytes).is_err());
+    }
+
     #[test]
     fn secret_key_generate() {
         let mut rng = thread_rng();
@@ -286,9 +591,9 @@ mod test {
         let sk = SecretKey::generate(&mut rng, &params);
         let mut messages = Vec::new();
 
-        let P1: Plaintext = (&[0u8; 30]).into();
-        let P2: Plaintext = (&[1u8; 30]).into();
-        let P3: Plaintext = (&[2u8; 30]).into();
+        let P1 = Plaintext::try_from(&[0u8; 30][..]).unwrap();
+        let P2 = Plaintext::try_from(&[1u8; 30][..]).unwrap();
+        let P3 = Plaintext::try_from(&[2u8; 30][..]).unwrap();
 
         messages.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
         messages.push(Attribute::SecretPoint(P1));
@@ -303,4 +608,35 @@ mod test {
 
         assert!(amac.verify(&params, &sk, &messages));
     }
+
+    #[test]
+    fn blinded_amac_unblind_verification() {
+        let mut rng = thread_rng();
+        let params = SystemParameters::generate(&mut rng, 4).unwrap();
+        let sk = SecretKey::generate(&mut rng, &params);
+        let keypair = ElGamalKeypair::generate(&params, &mut rng);
+        let m1 = Scalar::random(&mut rng);
+        let m3 = Scalar::random(&mut rng);
+        let M2 = RistrettoPoint::random(&mut rng);
+        let m4 = Scalar::random(&mut rng);
+
+        let mut messages = Vec::new();
+
+        messages.push(Attribute::SecretScalar(m1));
+        messages.push(Attribute::PublicPoint(M2));
+        messages.push(Attribute::SecretScalar(m3));
+        messages.push(Attribute::PublicScalar(m4));
+
+        let mut blinded = Vec::new();
+
+        blinded.push(BlindedAttribute::SecretScalar(keypair.public.encrypt(&params, &(params.G_m[0] * m1), &mut rng)));
+        blinded.push(BlindedAttribute::PublicPoint(M2));
+        blinded.push(BlindedAttribute::SecretScalar(keypair.public.encrypt(&params, &(params.G_m[2] * m3), &mut rng)));
+        blinded.push(BlindedAttribute::PublicScalar(m4));
+
+        let (blinded_amac, _) = BlindedAmac::tag(&mut rng, &params, &sk, &keypair.public, &blinded).unwrap();
+        let amac = blinded_amac.unblind(&keypair);
+
+        assert!(amac.verify(&params, &sk, &messages));
+    }
 }
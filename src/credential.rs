@@ -14,16 +14,45 @@ use alloc::vec::Vec;
 #[cfg(all(not(feature = "alloc"), feature = "std"))]
 use std::vec::Vec;
 
+use curve25519_dalek::ristretto::CompressedRistretto;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+
 use rand_core::CryptoRng;
 use rand_core::RngCore;
 
+use serde::{self, Serialize, Deserialize, Serializer, Deserializer};
+use serde::de::Visitor;
+
+use zeroize::Zeroize;
+
+use crate::amacs::TAG_PUBLIC_POINT;
+use crate::amacs::TAG_PUBLIC_SCALAR;
+use crate::amacs::TAG_SECRET_POINT;
+use crate::amacs::TAG_SECRET_SCALAR;
 use crate::amacs::Amac;
 use crate::amacs::Attribute;
+use crate::amacs::BlindedAttribute;
+use crate::elgamal::Keypair as ElGamalKeypair;
+use crate::elgamal::PublicKey as ElGamalPublicKey;
 use crate::errors::CredentialError;
+use crate::issuer::BlindedIssuanceResponse;
+use crate::issuer::IssuanceResponse;
+use crate::issuer::Issuer;
 use crate::parameters::IssuerParameters;
 use crate::parameters::SystemParameters;
+use crate::nizk::PresentationOptions;
+use crate::nizk::ProofOfCredentialRequest;
+use crate::nizk::ProofOfRefresh;
 use crate::nizk::ProofOfValidCredential;
+use crate::nizk::SerialNumber;
 use crate::symmetric::Keypair as SymmetricKeypair;
+use crate::symmetric::Plaintext;
+use crate::symmetric::SIZEOF_PLAINTEXT;
+
+/// The version of the serialisation format of an [`AnonymousCredential`],
+/// which is always the first byte of a serialised credential.
+pub(crate) const CREDENTIAL_FORMAT_VERSION: u8 = 1;
 
 /// An anonymous credential.
 pub struct AnonymousCredential {
@@ -31,6 +60,115 @@ pub struct AnonymousCredential {
     pub(crate) attributes: Vec<Attribute>,
 }
 
+impl Zeroize for AnonymousCredential {
+    fn zeroize(&mut self) {
+        self.amac.zeroize();
+        self.attributes.zeroize();
+    }
+}
+
+/// Overwrite the credential's tag and secret attributes with zeroes (and the
+/// identity element) when it drops out of scope.
+impl Drop for AnonymousCredential {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl AnonymousCredential {
+    /// Deserialise an [`AnonymousCredential`] from bytes.
+    ///
+    /// The format is a version byte, the number of attributes `n`, the aMAC
+    /// \( (t, U, V) \), and then the `n` attributes, each a type tag followed
+    /// by its scalar, compressed point, or [`Plaintext`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<AnonymousCredential, CredentialError> {
+        if bytes.len() < 2 + 96 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+        if bytes[0] != CREDENTIAL_FORMAT_VERSION {
+            return Err(CredentialError::UnsupportedVersion);
+        }
+
+        let NUMBER_OF_ATTRIBUTES: usize = bytes[1] as usize;
+        let mut index: usize = 2;
+        let mut chunk = [0u8; 32];
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let t: Scalar = try_deserialise_scalar!("t", chunk);
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let U: RistrettoPoint = try_deserialise!("U", chunk);
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let V: RistrettoPoint = try_deserialise!("V", chunk);
+
+        let amac = Amac { t, U, V };
+        let mut attributes: Vec<Attribute> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+
+        for i in 0..NUMBER_OF_ATTRIBUTES {
+            if bytes.len() < index + 1 {
+                return Err(CredentialError::WrongNumberOfBytes);
+            }
+            let tag = bytes[index]; index += 1;
+
+            if tag == TAG_SECRET_POINT {
+                if bytes.len() < index + SIZEOF_PLAINTEXT {
+                    return Err(CredentialError::WrongNumberOfBytes);
+                }
+                attributes.push(Attribute::SecretPoint(Plaintext::from_bytes(&bytes[index..index+SIZEOF_PLAINTEXT])?));
+                index += SIZEOF_PLAINTEXT;
+                continue;
+            }
+
+            if bytes.len() < index + 32 {
+                return Err(CredentialError::WrongNumberOfBytes);
+            }
+            chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+
+            match tag {
+                TAG_PUBLIC_SCALAR => attributes.push(Attribute::PublicScalar(try_deserialise_scalar!(format!("m_{}", i), chunk))),
+                TAG_SECRET_SCALAR => attributes.push(Attribute::SecretScalar(try_deserialise_scalar!(format!("m_{}", i), chunk))),
+                TAG_PUBLIC_POINT  => attributes.push(Attribute::PublicPoint(try_deserialise!(format!("M_{}", i), chunk))),
+                _                 => return Err(CredentialError::BadAttribute),
+            }
+        }
+
+        chunk.zeroize();
+
+        if index != bytes.len() {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        Ok(AnonymousCredential { amac, attributes })
+    }
+
+    /// Serialise this [`AnonymousCredential`] to a byte array.
+    ///
+    /// The caller is responsible for zeroing the returned bytes once they are
+    /// no longer needed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::with_capacity(2 + 96 + (1 + SIZEOF_PLAINTEXT) * self.attributes.len());
+
+        v.push(CREDENTIAL_FORMAT_VERSION);
+        v.push(self.attributes.len() as u8);
+        v.extend(self.amac.t.as_bytes().iter());
+        v.extend(self.amac.U.compress().to_bytes().iter());
+        v.extend(self.amac.V.compress().to_bytes().iter());
+
+        for attribute in self.attributes.iter() {
+            match attribute {
+                Attribute::PublicScalar(m) => { v.push(TAG_PUBLIC_SCALAR); v.extend(m.as_bytes().iter()); },
+                Attribute::SecretScalar(m) => { v.push(TAG_SECRET_SCALAR); v.extend(m.as_bytes().iter()); },
+                Attribute::PublicPoint(M)  => { v.push(TAG_PUBLIC_POINT);  v.extend(M.compress().to_bytes().iter()); },
+                Attribute::SecretPoint(p)  => { v.push(TAG_SECRET_POINT);  v.extend(p.to_bytes()); },
+            }
+        }
+        v
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(AnonymousCredential, "A valid byte sequence representing an AnonymousCredential");
+
 impl AnonymousCredential {
     /// Present this credential to an issuer.
     pub fn show<C>(
@@ -45,4 +183,548 @@ impl AnonymousCredential {
     {
         ProofOfValidCredential::prove(&system_parameters, &issuer_parameters, &self, keypair, csprng)
     }
+
+    /// Present this credential to an issuer, additionally proving the
+    /// statements in the `options`, such as predicates over its hidden scalar
+    /// attributes or a pseudonym within some scope.
+    pub fn show_with_options<C>(
+        &self,
+        system_parameters: &SystemParameters,
+        issuer_parameters: &IssuerParameters,
+        keypair: Option<&SymmetricKeypair>,
+        options: &PresentationOptions,
+        csprng: &mut C,
+    ) -> Result<ProofOfValidCredential, CredentialError>
+    where
+        C: CryptoRng + RngCore,
+    {
+        ProofOfValidCredential::prove_with_options(&system_parameters, &issuer_parameters, &self,
+                                                   keypair, options, csprng)
+    }
+}
+
+impl AnonymousCredential {
+    /// Obtain an anonymous credential from an issuer's response to an
+    /// unblinded issuance request.
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] and published [`IssuerParameters`],
+    /// * The issuer's `response`, and
+    /// * The `attributes` which were sent to the issuer.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is an [`AnonymousCredential`], if the
+    /// issuer's [`ProofOfIssuance`] verifies, otherwise a [`CredentialError`].
+    /// The proof ensures that the credential was issued under the published
+    /// [`IssuerParameters`], rather than some key which could be used to link
+    /// this user later.
+    pub fn from_issuance_response(
+        system_parameters: &SystemParameters,
+        issuer_parameters: &IssuerParameters,
+        response: &IssuanceResponse,
+        attributes: Vec<Attribute>,
+    ) -> Result<AnonymousCredential, CredentialError>
+    {
+        let credential = AnonymousCredential { amac: response.amac.clone(), attributes };
+
+        response.proof.verify(system_parameters, issuer_parameters, &credential)?;
+
+        Ok(credential)
+    }
+
+    /// Obtain an anonymous credential from an issuer's response to a blinded
+    /// [`CredentialRequest`].
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] and published [`IssuerParameters`],
+    /// * The `request` which was sent to the issuer,
+    /// * The issuer's `response`,
+    /// * The ElGamal `keypair` used to create the `request`, and
+    /// * The plaintext `attributes` used to create the `request`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is an [`AnonymousCredential`], if the
+    /// issuer's proof of correct (blinded) issuance verifies, otherwise a
+    /// [`CredentialError`], which is [`CredentialError::BadAttribute`] if the
+    /// `attributes` are not those of the `request`.
+    pub fn from_blinded_issuance_response(
+        system_parameters: &SystemParameters,
+        issuer_parameters: &IssuerParameters,
+        request: &CredentialRequest,
+        response: &BlindedIssuanceResponse,
+        keypair: &ElGamalKeypair,
+        attributes: Vec<Attribute>,
+    ) -> Result<AnonymousCredential, CredentialError>
+    {
+        if attributes.len() != request.attributes.len() {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+
+        // The MAC is over the request's attributes, so it would never verify
+        // on any others.
+        for (i, (attribute, blinded)) in attributes.iter().zip(request.attributes.iter()).enumerate() {
+            let matches = match (attribute, blinded) {
+                (Attribute::PublicScalar(m), BlindedAttribute::PublicScalar(n)) => m == n,
+                (Attribute::PublicPoint(M), BlindedAttribute::PublicPoint(N)) => M == N,
+                (Attribute::SecretPoint(p), BlindedAttribute::SecretPoint(q)) => p == q,
+                (Attribute::SecretPoint(p), BlindedAttribute::EncryptedPoint(E)) => keypair.decrypt(E) == p.M1,
+                (Attribute::SecretScalar(m), BlindedAttribute::SecretScalar(E)) => {
+                    keypair.decrypt(E) == system_parameters.G_m[i] * m
+                },
+                _ => false,
+            };
+
+            if !matches {
+                return Err(CredentialError::BadAttribute);
+            }
+        }
+
+        response.proof.verify(system_parameters, issuer_parameters, &request.public_key,
+                              &request.attributes, &response.amac)?;
+
+        Ok(AnonymousCredential { amac: response.amac.unblind(keypair), attributes })
+    }
+}
+
+/// A request for a new anonymous credential, whose hidden scalar attributes
+/// are blinded from the issuer.
+pub struct CredentialRequest {
+    pub(crate) public_key: ElGamalPublicKey,
+    pub(crate) attributes: Vec<BlindedAttribute>,
+    pub(crate) proof: ProofOfCredentialRequest,
+}
+
+impl CredentialRequest {
+    /// Create a request for a new anonymous credential on a set of
+    /// `attributes` in a blinded manner.
+    ///
+    /// By "blinded" we mean that all [`Attribute::SecretScalar`]s are ElGamal
+    /// encrypted to the `keypair`, such that the issuer is unable to learn
+    /// them, while all other attributes are revealed.
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    /// * The set of `attributes` to include on the credential,
+    /// * An ElGamal `keypair`, which must be kept to unblind the response, and
+    /// * A `csprng`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`CredentialRequest`], otherwise a
+    /// [`CredentialError`].
+    pub fn new<C>(
+        system_parameters: &SystemParameters,
+        attributes: &Vec<Attribute>,
+        keypair: &ElGamalKeypair,
+        csprng: &mut C,
+    ) -> Result<CredentialRequest, CredentialError>
+    where
+        C: CryptoRng + RngCore,
+    {
+        let (request, _) = CredentialRequest::new_with_hidden_scalars(system_parameters, attributes, keypair, csprng)?;
+
+        Ok(request)
+    }
+
+    /// As [`CredentialRequest::new`], but additionally returning the hidden
+    /// scalar attributes, as `(index, m_i, r_i)` tuples, where `r_i` is the
+    /// nonce used for the encryption of the `i`th attribute.
+    pub(crate) fn new_with_hidden_scalars<C>(
+        system_parameters: &SystemParameters,
+        attributes: &Vec<Attribute>,
+        keypair: &ElGamalKeypair,
+        csprng: &mut C,
+    ) -> Result<(CredentialRequest, Vec<(usize, Scalar, Scalar)>), CredentialError>
+    where
+        C: CryptoRng + RngCore,
+    {
+        if attributes.len() > system_parameters.NUMBER_OF_ATTRIBUTES as usize {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+
+        let mut blinded_attributes: Vec<BlindedAttribute> = Vec::with_capacity(attributes.len());
+        let mut hidden_scalars: Vec<(usize, Scalar, Scalar)> = Vec::new();
+
+        for (i, attribute) in attributes.iter().enumerate() {
+            match attribute {
+                Attribute::PublicScalar(m) => blinded_attributes.push(BlindedAttribute::PublicScalar(*m)),
+                Attribute::PublicPoint(M)  => blinded_attributes.push(BlindedAttribute::PublicPoint(*M)),
+                Attribute::SecretPoint(p)  => blinded_attributes.push(BlindedAttribute::SecretPoint(p.clone())),
+                Attribute::SecretScalar(m) => {
+                    let r: Scalar = Scalar::random(csprng);
+                    let E = keypair.public.encrypt_with_nonce(system_parameters, &(system_parameters.G_m[i] * m), &r);
+
+                    blinded_attributes.push(BlindedAttribute::SecretScalar(E));
+                    hidden_scalars.push((i, *m, r));
+                },
+            }
+        }
+
+        let proof = ProofOfCredentialRequest::prove(system_parameters, keypair, &blinded_attributes, &hidden_scalars);
+
+        Ok((CredentialRequest {
+            public_key: keypair.public,
+            attributes: blinded_attributes,
+            proof: proof,
+        }, hidden_scalars))
+    }
+}
+
+/// A request to trade an old credential for a new one, whose hidden scalar
+/// attributes are provably those of the old credential, each plus some public
+/// update, without revealing either to the issuer.
+pub struct RefreshRequest {
+    pub(crate) presentation: ProofOfValidCredential,
+    pub(crate) request: CredentialRequest,
+    pub(crate) proof: ProofOfRefresh,
+}
+
+impl RefreshRequest {
+    /// Create a request to refresh an old `credential`.
+    ///
+    /// The new credential has the given `attributes`, whose hidden scalar
+    /// attributes must be in the same positions as those of the old
+    /// `credential` and equal to them, save for those with an update
+    /// \\( \delta_i \\) in `updates`, which must equal
+    /// \\( m_i + \delta_i \\).  The issuer decides which updates it requires,
+    /// e.g. incrementing a counter by one, as well as whether to accept the
+    /// revealed attributes of the new credential.
+    ///
+    /// The issuer only refreshes each old credential once, and so requires
+    /// that the `options` present a [`crate::nizk::SerialNumber`] derived
+    /// from the hidden scalar attribute it designates.  That attribute is not
+    /// linked to the new credential, which should have a fresh random scalar
+    /// in its place, or else it could never be refreshed again.
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] and the [`IssuerParameters`] of the old credential,
+    /// * The old `credential`,
+    /// * A symmetric `symmetric_keypair`, if the old credential has hidden group
+    ///   element attributes,
+    /// * The `options` for the presentation of the old credential,
+    /// * The `attributes` of the new credential,
+    /// * The `updates`, as `(index, delta_i)` tuples,
+    /// * An ElGamal `keypair`, which must be kept to unblind the response, and
+    /// * A `csprng`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`RefreshRequest`], otherwise a
+    /// [`CredentialError`], which is [`CredentialError::BadAttribute`] if the
+    /// hidden scalar attributes of the new credential are not those of the old
+    /// credential with the `updates` applied.
+    pub fn new<C>(
+        system_parameters: &SystemParameters,
+        issuer_parameters: &IssuerParameters,
+        credential: &AnonymousCredential,
+        symmetric_keypair: Option<&SymmetricKeypair>,
+        options: &PresentationOptions,
+        attributes: &Vec<Attribute>,
+        updates: &[(usize, Scalar)],
+        keypair: &ElGamalKeypair,
+        csprng: &mut C,
+    ) -> Result<RefreshRequest, CredentialError>
+    where
+        C: CryptoRng + RngCore,
+    {
+        let (presentation, z) = ProofOfValidCredential::prove_with_nonce(system_parameters, issuer_parameters,
+                                                                         credential, symmetric_keypair,
+                                                                         options, csprng)?;
+        let (request, hidden_scalars) = CredentialRequest::new_with_hidden_scalars(system_parameters, attributes,
+                                                                                   keypair, csprng)?;
+        let proof = ProofOfRefresh::prove(system_parameters, &presentation, &z, &keypair.public,
+                                          &request.attributes, &hidden_scalars, updates)?;
+
+        Ok(RefreshRequest { presentation, request, proof })
+    }
+
+    /// The presentation of the old credential, against which an issuer may
+    /// check any further statements it requires, such as predicates over the
+    /// old credential's hidden scalar attributes.
+    pub fn presentation(&self) -> &ProofOfValidCredential {
+        &self.presentation
+    }
+
+    /// The blinded request for the new credential.
+    pub fn request(&self) -> &CredentialRequest {
+        &self.request
+    }
+
+    /// Verify this [`RefreshRequest`] against the `issuer` of the old
+    /// credential, with the `updates` which that issuer requires, and that
+    /// the old credential is presented with a serial number derived from the
+    /// attribute at `serial_number_index`.
+    ///
+    /// This does not check the request's [`ProofOfCredentialRequest`], which
+    /// is checked upon blinded issuance.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the [`SerialNumber`] of the old
+    /// credential, otherwise a [`CredentialError`].
+    pub(crate) fn verify(
+        &self,
+        issuer: &Issuer,
+        updates: &[(usize, Scalar)],
+        serial_number_index: u16,
+    ) -> Result<SerialNumber, CredentialError>
+    {
+        let serial_number = self.presentation.verify_with_serial_number(issuer, serial_number_index)?;
+
+        self.proof.verify(&issuer.system_parameters, &self.presentation, &self.request.public_key,
+                          &self.request.attributes, updates)?;
+
+        Ok(serial_number)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use core::convert::TryFrom;
+
+    use curve25519_dalek::traits::MultiscalarMul;
+
+    use std::collections::BTreeSet;
+
+    use rand::thread_rng;
+    use rand::rngs::ThreadRng;
+
+    use crate::amacs::Messages;
+    use crate::verifier::Nullifier;
+
+
+    #[test]
+    fn credential_serialize_deserialize() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 4).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretPoint(plaintext));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let bytes = credential.to_bytes();
+        let deserialized = AnonymousCredential::from_bytes(&bytes).unwrap();
+
+        assert_eq!(deserialized.to_bytes(), bytes);
+        assert!(deserialized.amac.verify(&system_parameters, &issuer.amacs_key, &deserialized.attributes));
+        assert!(AnonymousCredential::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn credential_presentation_requires_multiplicative_t() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 2).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes.clone()).unwrap();
+        let proof = credential.show(&system_parameters, &issuer.issuer_parameters, None, &mut rng).unwrap();
+
+        assert!(proof.verify(&issuer).is_ok());
+
+        // A MAC computed as V = W + U x_0 + U x_1 + U t + \sigma M_i y_i, as
+        // the tag was originally computed, cannot be presented.
+        let sk = &issuer.amacs_key;
+        let (t, U) = (credential.amac.t, credential.amac.U);
+        let messages = Messages::from_attributes(&attributes, &system_parameters);
+        let V = sk.W + (U * sk.x_0) + (U * sk.x_1) + (U * t) +
+            RistrettoPoint::multiscalar_mul(&sk.y[..messages.0.len()], &messages.0[..]);
+        let credential = AnonymousCredential { amac: Amac { t, U, V }, attributes };
+        let proof = credential.show(&system_parameters, &issuer.issuer_parameters, None, &mut rng).unwrap();
+
+        assert!(proof.verify(&issuer).is_err());
+    }
+
+    #[test]
+    fn issuance_response_wrong_issuer_parameters() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 2).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let other_issuer = Issuer::new(&system_parameters, &mut rng);
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+
+        // An issuer tagging with a key other than the published one is detected.
+        let response = other_issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes);
+
+        assert!(credential.is_err());
+    }
+
+    #[test]
+    fn blinded_issuance_response_wrong_attributes() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let keypair = ElGamalKeypair::generate(&system_parameters, &mut rng);
+        let secret = Scalar::random(&mut rng);
+        let public = Scalar::random(&mut rng);
+        let point = RistrettoPoint::random(&mut rng);
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretScalar(secret));
+        attributes.push(Attribute::PublicScalar(public));
+        attributes.push(Attribute::PublicPoint(point));
+
+        let request = CredentialRequest::new(&system_parameters, &attributes, &keypair, &mut rng).unwrap();
+        let response = issuer.issue_blinded(&request, &mut rng).unwrap();
+        let unblind = |attributes: Vec<Attribute>| {
+            AnonymousCredential::from_blinded_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                &request, &response, &keypair, attributes)
+        };
+
+        // Neither a hidden nor a revealed attribute may differ from the request's,
+        assert_eq!(unblind(vec![Attribute::SecretScalar(secret + Scalar::one()),
+                                Attribute::PublicScalar(public),
+                                Attribute::PublicPoint(point)]).err(),
+                   Some(CredentialError::BadAttribute));
+        assert_eq!(unblind(vec![Attribute::SecretScalar(secret),
+                                Attribute::PublicScalar(public + Scalar::one()),
+                                Attribute::PublicPoint(point)]).err(),
+                   Some(CredentialError::BadAttribute));
+
+        // nor be of another kind,
+        assert_eq!(unblind(vec![Attribute::PublicScalar(secret),
+                                Attribute::PublicScalar(public),
+                                Attribute::PublicPoint(point)]).err(),
+                   Some(CredentialError::BadAttribute));
+
+        // and the decryption is under the request's keypair.
+        let other_keypair = ElGamalKeypair::generate(&system_parameters, &mut rng);
+
+        assert_eq!(AnonymousCredential::from_blinded_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                       &request, &response, &other_keypair,
+                                                                       attributes.clone()).err(),
+                   Some(CredentialError::BadAttribute));
+
+        let credential = unblind(attributes).unwrap();
+        let presentation = credential.show(&system_parameters, &issuer.issuer_parameters, None, &mut rng).unwrap();
+
+        assert!(presentation.verify(&issuer).is_ok());
+    }
+
+    #[test]
+    fn refresh_credential() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 4).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let keypair = ElGamalKeypair::generate(&system_parameters, &mut rng);
+        let user_id = Scalar::random(&mut rng);
+        let serial = Scalar::random(&mut rng);
+        let mut spent: BTreeSet<Nullifier> = BTreeSet::new();
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretScalar(user_id));
+        attributes.push(Attribute::SecretScalar(Scalar::from(5u64)));
+        attributes.push(Attribute::PublicScalar(Scalar::from(1u64)));
+        attributes.push(Attribute::SecretScalar(serial));
+
+        let request = CredentialRequest::new(&system_parameters, &attributes, &keypair, &mut rng).unwrap();
+        let response = issuer.issue_blinded(&request, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_blinded_issuance_response(&system_parameters,
+                                                                             &issuer.issuer_parameters,
+                                                                             &request, &response, &keypair,
+                                                                             attributes).unwrap();
+
+        // The issuer requires that the counter be incremented by one, and
+        // that the old credential be presented with its serial number.
+        let updates = vec![(1, Scalar::one())];
+        let options = PresentationOptions { serial_number: Some(3), ..Default::default() };
+        let new_attributes = |counter: u64, mut rng: &mut ThreadRng| {
+            vec![Attribute::SecretScalar(user_id),
+                 Attribute::SecretScalar(Scalar::from(counter)),
+                 Attribute::PublicScalar(Scalar::from(2u64)),
+                 Attribute::SecretScalar(Scalar::random(&mut rng))]
+        };
+
+        let attributes = new_attributes(6, &mut rng);
+        let refresh = RefreshRequest::new(&system_parameters, &issuer.issuer_parameters, &credential, None,
+                                          &options, &attributes, &updates, &keypair, &mut rng).unwrap();
+
+        // An issuer requiring a different update rejects the request.
+        assert!(issuer.refresh(&refresh, &[(1, Scalar::from(2u64))], 3, &mut spent, &mut rng).is_err());
+        assert!(issuer.refresh(&refresh, &[], 3, &mut spent, &mut rng).is_err());
+
+        // As does one deriving serial numbers from another attribute.
+        assert_eq!(issuer.refresh(&refresh, &updates, 0, &mut spent, &mut rng).err(),
+                   Some(CredentialError::VerificationFailure));
+        assert!(spent.is_empty());
+
+        let response = issuer.refresh(&refresh, &updates, 3, &mut spent, &mut rng).unwrap();
+        let refreshed = AnonymousCredential::from_blinded_issuance_response(&system_parameters,
+                                                                            &issuer.issuer_parameters,
+                                                                            refresh.request(), &response,
+                                                                            &keypair, attributes).unwrap();
+        let presentation = refreshed.show(&system_parameters, &issuer.issuer_parameters, None, &mut rng).unwrap();
+
+        assert!(presentation.verify(&issuer).is_ok());
+
+        // The old credential cannot be refreshed a second time.
+        let again = RefreshRequest::new(&system_parameters, &issuer.issuer_parameters, &credential, None,
+                                        &options, &new_attributes(6, &mut rng), &updates,
+                                        &keypair, &mut rng).unwrap();
+
+        assert_eq!(issuer.refresh(&again, &updates, 3, &mut spent, &mut rng).err(),
+                   Some(CredentialError::SpentNullifier));
+
+        // Nor without presenting its serial number.
+        let mut attributes = new_attributes(6, &mut rng);
+
+        attributes[3] = Attribute::SecretScalar(serial);
+
+        let unserialed = RefreshRequest::new(&system_parameters, &issuer.issuer_parameters, &credential, None,
+                                             &PresentationOptions::default(), &attributes,
+                                             &updates, &keypair, &mut rng).unwrap();
+
+        assert_eq!(issuer.refresh(&unserialed, &updates, 3, &mut spent, &mut rng).err(),
+                   Some(CredentialError::MissingData));
+
+        // The refreshed credential has a fresh serial number, and so may be refreshed in turn.
+        let next = RefreshRequest::new(&system_parameters, &issuer.issuer_parameters, &refreshed, None,
+                                       &options, &new_attributes(7, &mut rng), &updates,
+                                       &keypair, &mut rng).unwrap();
+
+        assert!(issuer.refresh(&next, &updates, 3, &mut spent, &mut rng).is_ok());
+        assert_eq!(spent.len(), 2);
+
+        // A user cannot claim any other value for the counter.
+        let mut bad_attributes = Vec::new();
+
+        bad_attributes.push(Attribute::SecretScalar(user_id));
+        bad_attributes.push(Attribute::SecretScalar(Scalar::from(7u64)));
+
+        let bad = RefreshRequest::new(&system_parameters, &issuer.issuer_parameters, &credential, None,
+                                      &PresentationOptions::default(), &bad_attributes, &updates,
+                                      &keypair, &mut rng);
+
+        assert!(bad.err() == Some(CredentialError::BadAttribute));
+    }
 }
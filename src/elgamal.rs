@@ -0,0 +1,193 @@
+// -*- mode: rust; -*-
+//
+// This file is part of aeonflux.
+// Copyright (c) 2020 The Brave Authors
+// See LICENSE for licensing information.
+//
+// Authors:
+// - isis agora lovecruft <isis@patternsinthevoid.net>
+
+//! ElGamal encryption of group elements.
+//!
+//! During blinded credential issuance, the user encrypts their hidden
+//! attributes to an ElGamal public key of their own choosing.  Since ElGamal
+//! is additively homomorphic, the issuer is able to compute an encryption of
+//! the algebraic MAC over the hidden attributes without ever learning them,
+//! which the user then decrypts to obtain their credential.
+
+use curve25519_dalek::ristretto::CompressedRistretto;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+
+use rand_core::CryptoRng;
+use rand_core::RngCore;
+
+use zeroize::Zeroize;
+
+use crate::errors::CredentialError;
+use crate::parameters::SystemParameters;
+
+/// An ElGamal secret key, \\( d \in \mathbb{Z}_q \\).
+#[derive(Clone, Zeroize)]
+pub(crate) struct SecretKey {
+    pub(crate) d: Scalar,
+}
+
+/// Overwrite the secret key material with zeroes when it drops out of scope.
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+/// An ElGamal public key, \\( D = G * d \\).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PublicKey {
+    pub D: RistrettoPoint,
+}
+
+/// An ElGamal keypair.
+#[derive(Clone)]
+pub struct Keypair {
+    /// The secret portion of this keypair.
+    pub(crate) secret: SecretKey,
+    /// The public portion of this keypair.
+    pub public: PublicKey,
+}
+
+/// An ElGamal ciphertext, \\( (C_1, C_2) = (G * r, M + D * r) \\).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Ciphertext {
+    pub C1: RistrettoPoint,
+    pub C2: RistrettoPoint,
+}
+
+impl Keypair {
+    /// Generate a new ElGamal keypair.
+    ///
+    /// # Inputs
+    ///
+    /// * Some [`SystemParameters`], and
+    /// * A cryptographically secure pseudo-random number generator.
+    ///
+    /// # Returns
+    ///
+    /// A newly generated [`Keypair`].
+    pub fn generate<R>(
+        system_parameters: &SystemParameters,
+        csprng: &mut R,
+    ) -> Keypair
+    where
+        R: RngCore + CryptoRng,
+    {
+        let d: Scalar = Scalar::random(csprng);
+        let D: RistrettoPoint = system_parameters.G * d;
+
+        Keypair {
+            secret: SecretKey { d },
+            public: PublicKey { D },
+        }
+    }
+
+    /// Decrypt a [`Ciphertext`] to the group element it encrypts.
+    pub fn decrypt(&self, ciphertext: &Ciphertext) -> RistrettoPoint {
+        ciphertext.C2 - (ciphertext.C1 * self.secret.d)
+    }
+}
+
+impl PublicKey {
+    /// Encrypt a group element `M` to this public key.
+    pub fn encrypt<R>(
+        &self,
+        system_parameters: &SystemParameters,
+        M: &RistrettoPoint,
+        csprng: &mut R,
+    ) -> Ciphertext
+    where
+        R: RngCore + CryptoRng,
+    {
+        self.encrypt_with_nonce(system_parameters, M, &Scalar::random(csprng))
+    }
+
+    /// Encrypt a group element `M` to this public key with a specific `nonce`.
+    ///
+    /// The nonce must be uniformly random and never reused; this is only
+    /// exposed within the crate so that provers may reuse it as a witness.
+    pub(crate) fn encrypt_with_nonce(
+        &self,
+        system_parameters: &SystemParameters,
+        M: &RistrettoPoint,
+        nonce: &Scalar,
+    ) -> Ciphertext
+    {
+        let C1: RistrettoPoint = system_parameters.G * nonce;
+        let C2: RistrettoPoint = M + (self.D * nonce);
+
+        Ciphertext { C1, C2 }
+    }
+
+    /// Deserialise a [`PublicKey`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<PublicKey, CredentialError> {
+        if bytes.len() != 32 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut chunk = [0u8; 32];
+
+        chunk.copy_from_slice(bytes);
+
+        Ok(PublicKey { D: try_deserialise!("D", chunk) })
+    }
+
+    /// Serialise this [`PublicKey`] to a byte array.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.D.compress().to_bytes()
+    }
+}
+
+impl Ciphertext {
+    /// Deserialise a [`Ciphertext`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Ciphertext, CredentialError> {
+        if bytes.len() != 64 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut chunk = [0u8; 32];
+
+        chunk.copy_from_slice(&bytes[..32]);
+        let C1: RistrettoPoint = try_deserialise!("C1", chunk);
+
+        chunk.copy_from_slice(&bytes[32..]);
+        let C2: RistrettoPoint = try_deserialise!("C2", chunk);
+
+        Ok(Ciphertext { C1, C2 })
+    }
+
+    /// Serialise this [`Ciphertext`] to a byte array, e.g. for storage until
+    /// it is handed to the holder of the secret key.
+    pub fn to_bytes(&self) -> [u8; 64] {
+        let mut bytes = [0u8; 64];
+
+        bytes[..32].copy_from_slice(self.C1.compress().as_bytes());
+        bytes[32..].copy_from_slice(self.C2.compress().as_bytes());
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rand::thread_rng;
+
+    #[test]
+    fn encrypt_decrypt_roundtrip() {
+        let mut csprng = thread_rng();
+        let system_parameters = SystemParameters::hash_and_pray(&mut csprng, 2).unwrap();
+        let keypair = Keypair::generate(&system_parameters, &mut csprng);
+        let message = RistrettoPoint::random(&mut csprng);
+        let ciphertext = keypair.public.encrypt(&system_parameters, &message, &mut csprng);
+
+        assert_eq!(keypair.decrypt(&ciphertext), message);
+    }
+}
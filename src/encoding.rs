@@ -10,8 +10,22 @@
 
 //! Encoding/decoding byte sequences to and from the ristretto255 group.
 
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::vec::Vec;
+#[cfg(all(not(feature = "alloc"), feature = "std"))]
+use std::vec::Vec;
+
 use curve25519_dalek::ristretto::CompressedRistretto;
 use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::traits::IsIdentity;
+
+use crate::errors::EncodingError;
+
+/// The maximum number of bytes which may be encoded as a single group element.
+pub const MAX_ENCODED_LENGTH: usize = 30;
+
+/// The mask of the bits of the high byte of an encoding which hold the length.
+const LENGTH_MASK: u8 = 0x1f;
 
 /// Encodes up to 30 bytes as an element of the ristretto255 group.
 ///
@@ -27,9 +41,12 @@ use curve25519_dalek::ristretto::RistrettoPoint;
 /// Instead, we encode 30 bytes at a time using increment-and-test.
 ///
 /// This function computes a sequence of 32-byte candidate encodings, of
-/// the form `i || data || 0`, where `i` is a counter running over even
-/// numbers `[0,2,4,...,254]`. The first candidate which is a valid encoding
-/// is the canonical representative of `data`.
+/// the form `i || data || padding || (j << 5 | length)`, where `i` is a
+/// counter running over even numbers `[0,2,4,...,254]`, `data` is
+/// zero-padded to 30 bytes, and the low five bits of the high byte hold the
+/// `length` of the `data`, so that it may be recovered exactly.  The first
+/// candidate which is a valid encoding of a point other than the identity is
+/// the canonical representative of `data`.
 ///
 /// Each candidate has a 1/4 chance of being a valid encoding, so the
 /// probability of *not* finding a representative after k trials is
@@ -41,44 +58,175 @@ use curve25519_dalek::ristretto::RistrettoPoint;
 /// (1-1/4)/(1/4) = 3.
 ///
 /// In the extremely unlikely event that no candidate is found after 128
-/// trials, we can begin incrementing the high byte from 0 (its initial
-/// value) by 1s up to 64, giving 128*64 trials in total and cutting the
-/// failure probability to 2**(lg(3/4)*128*64) ~= 2**(-3400) ~= 0.
+/// trials, we use the two remaining bits of the high byte, `j`, to count
+/// from 0 (its initial value) up to 3, giving 128*4 trials in total and
+/// cutting the failure probability to 2**(lg(3/4)*128*4) < 2**(-212).
 ///
 /// # Returns
 ///
-/// The encoded group element and the counter for which try succeeded.
+/// A `Result` whose `Ok` value is the encoded group element and the counter
+/// for which try succeeded, otherwise an [`EncodingError`] if the `data` is
+/// longer than [`MAX_ENCODED_LENGTH`] or no valid encoding was found.
 //
-// XXX TODO return a Vec<(RistrettoPoint, usize)>
-// XXX error handling
 // XXX shortcut if counter is known
-// XXX [0u8; 30] encodes to the identity element
-pub fn encode_to_group(data: &[u8]) -> (RistrettoPoint, usize) {
-    assert!(data.len() <= 30);
+pub fn encode_to_group(data: &[u8]) -> Result<(RistrettoPoint, usize), EncodingError> {
+    if data.len() > MAX_ENCODED_LENGTH {
+        return Err(EncodingError::DataTooLong);
+    }
+
     let mut bytes = [0u8; 32];
     bytes[1..1 + data.len()].copy_from_slice(data);
-    for j in 0..64 {
-        bytes[31] = j as u8;
+
+    for j in 0..4 {
+        bytes[31] = (j << 5) as u8 | data.len() as u8;
         for i in 0..128 {
             bytes[0] = 2 * i as u8;
             if let Some(point) = CompressedRistretto(bytes).decompress() {
-                return (point, i + j * 128);
+                // The empty string would otherwise encode to the identity.
+                if point.is_identity() {
+                    continue;
+                }
+                return Ok((point, i + j * 128));
             }
         }
     }
-    panic!("a very unlikely event occurred");
+    Err(EncodingError::ExhaustedCounter)
 }
 
-/// Decode a group element into up to 30 bytes of data.
-//
-// XXX TODO return a Vec<u8>
-pub fn decode_from_group(point: &RistrettoPoint) -> ([u8; 30], usize) {
-    let mut data: [u8; 30] = [0u8; 30];
+/// Decode a group element into the up to 30 bytes of data it encodes.
+///
+/// # Returns
+///
+/// A `Result` whose `Ok` value is the data and the counter for which
+/// encoding succeeded, otherwise an [`EncodingError`] if the `point` is not
+/// an encoding produced by [`encode_to_group`].
+pub fn decode_from_group(point: &RistrettoPoint) -> Result<(Vec<u8>, usize), EncodingError> {
     let compressed = point.compress();
+    let bytes = compressed.as_bytes();
+    let length = (bytes[31] & LENGTH_MASK) as usize;
+
+    if length > MAX_ENCODED_LENGTH || point.is_identity() {
+        return Err(EncodingError::InvalidEncoding);
+    }
+    if bytes[1 + length..31].iter().any(|x| *x != 0) {
+        return Err(EncodingError::InvalidEncoding);
+    }
+
+    let data: Vec<u8> = bytes[1..1 + length].to_vec();
+
+    Ok((data, (bytes[0] / 2) as usize + (bytes[31] >> 5) as usize * 128usize))
+}
+
+/// The number of bytes encoded by [`encode_32_bytes_to_group`].
+pub const SIZEOF_32_BYTES: usize = 32;
 
-    data.copy_from_slice(&compressed.as_bytes()[1..31]);
+/// Encode exactly 32 bytes, such as a key, as a pair of group elements.
+///
+/// Since at most [`MAX_ENCODED_LENGTH`] bytes fit in a single group element,
+/// each half of the `data` is encoded separately with [`encode_to_group`].
+///
+/// # Returns
+///
+/// A `Result` whose `Ok` value is the encodings of the first and second
+/// halves of the `data`, otherwise an [`EncodingError`].
+pub fn encode_32_bytes_to_group(data: &[u8; SIZEOF_32_BYTES]) -> Result<[RistrettoPoint; 2], EncodingError> {
+    let (first, _) = encode_to_group(&data[..SIZEOF_32_BYTES / 2])?;
+    let (second, _) = encode_to_group(&data[SIZEOF_32_BYTES / 2..])?;
 
-    (data, (compressed.as_bytes()[0] / 2) as usize + compressed.as_bytes()[31] as usize * 128usize)
+    Ok([first, second])
+}
+
+/// Decode a pair of group elements created by [`encode_32_bytes_to_group`]
+/// into the 32 bytes they encode.
+///
+/// # Returns
+///
+/// A `Result` whose `Ok` value is the data, otherwise an [`EncodingError`]
+/// if either `point` does not encode exactly half of it.
+pub fn decode_32_bytes_from_group(points: &[RistrettoPoint; 2]) -> Result<[u8; SIZEOF_32_BYTES], EncodingError> {
+    let mut data = [0u8; SIZEOF_32_BYTES];
+
+    for (j, point) in points.iter().enumerate() {
+        let (half, _) = decode_from_group(point)?;
+
+        if half.len() != SIZEOF_32_BYTES / 2 {
+            return Err(EncodingError::InvalidEncoding);
+        }
+        data[j * SIZEOF_32_BYTES / 2..(j + 1) * SIZEOF_32_BYTES / 2].copy_from_slice(&half);
+    }
+    Ok(data)
+}
+
+/// The number of bytes of data carried by each chunk from [`chunk_bytes`].
+pub const CHUNK_DATA_LENGTH: usize = 29;
+
+/// The bit set in a chunk's header byte when it is the final chunk.
+const FINAL_CHUNK: u8 = 0x80;
+
+/// Determine the number of 30-byte chunks required to frame `length` bytes.
+pub fn number_of_chunks(length: usize) -> usize {
+    if length == 0 {
+        return 1;
+    }
+    (length + CHUNK_DATA_LENGTH - 1) / CHUNK_DATA_LENGTH
+}
+
+/// Split an arbitrary-length byte string into 30-byte chunks, each of which
+/// may be encoded as a group element with [`encode_to_group`].
+///
+/// Each chunk is a header byte followed by up to [`CHUNK_DATA_LENGTH`] bytes
+/// of `data`, zero-padded.  The header holds the number of data bytes in the
+/// chunk, with the high bit set on the final chunk, so that truncation is
+/// detected when the chunks are reassembled.
+pub fn chunk_bytes(data: &[u8]) -> Vec<[u8; 30]> {
+    let n = number_of_chunks(data.len());
+    let mut chunks: Vec<[u8; 30]> = Vec::with_capacity(n);
+
+    for i in 0..n {
+        let start = i * CHUNK_DATA_LENGTH;
+        let end = core::cmp::min(start + CHUNK_DATA_LENGTH, data.len());
+        let mut chunk = [0u8; 30];
+
+        chunk[0] = (end - start) as u8;
+        chunk[1..1 + end - start].copy_from_slice(&data[start..end]);
+
+        if i == n - 1 {
+            chunk[0] |= FINAL_CHUNK;
+        }
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// Reassemble a byte string from chunks created by [`chunk_bytes`].
+///
+/// # Returns
+///
+/// `None` if the `chunks` are malformed, truncated, or have trailing chunks
+/// after the final one.
+pub fn unchunk_bytes(chunks: &[[u8; 30]]) -> Option<Vec<u8>> {
+    if chunks.is_empty() {
+        return None;
+    }
+
+    let mut data: Vec<u8> = Vec::with_capacity(chunks.len() * CHUNK_DATA_LENGTH);
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        let is_final = chunk[0] & FINAL_CHUNK == FINAL_CHUNK;
+        let length = (chunk[0] & !FINAL_CHUNK) as usize;
+
+        if is_final != (i == chunks.len() - 1) {
+            return None;
+        }
+        if length > CHUNK_DATA_LENGTH || (!is_final && length != CHUNK_DATA_LENGTH) {
+            return None;
+        }
+        if chunk[1 + length..].iter().any(|x| *x != 0) {
+            return None;
+        }
+        data.extend_from_slice(&chunk[1..1 + length]);
+    }
+    Some(data)
 }
 
 #[cfg(test)]
@@ -91,14 +239,85 @@ mod test {
     #[test]
     fn encoding_decoding_roundtrip() {
         let mut rng = thread_rng();
-        let mut data = [0u8; 30];
+
+        for length in 0..=MAX_ENCODED_LENGTH {
+            let mut data = vec![0u8; length];
+
+            rng.fill_bytes(&mut data);
+
+            let (encoded, counter_a) = encode_to_group(&data[..]).unwrap();
+            let (decoded, counter_b) = decode_from_group(&encoded).unwrap();
+
+            assert_eq!(counter_a, counter_b);
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn encoding_zeroes_is_not_identity() {
+        let (empty, _) = encode_to_group(&[]).unwrap();
+        let (zeroes, _) = encode_to_group(&[0u8; 30]).unwrap();
+
+        assert!(!empty.is_identity());
+        assert!(!zeroes.is_identity());
+        assert_eq!(decode_from_group(&empty).unwrap().0, &[0u8; 0]);
+        assert_eq!(decode_from_group(&zeroes).unwrap().0, &[0u8; 30][..]);
+    }
+
+    #[test]
+    fn encoding_too_long() {
+        assert_eq!(encode_to_group(&[1u8; 31]).unwrap_err(), EncodingError::DataTooLong);
+    }
+
+    #[test]
+    fn encoding_32_bytes_roundtrip() {
+        let mut rng = thread_rng();
+        let mut data = [0u8; SIZEOF_32_BYTES];
 
         rng.fill_bytes(&mut data);
 
-        let (encoded, counter_a) = encode_to_group(&data[..]);
-        let (decoded, counter_b) = decode_from_group(&encoded);
+        let encoded = encode_32_bytes_to_group(&data).unwrap();
+
+        assert_eq!(decode_32_bytes_from_group(&encoded).unwrap(), data);
+
+        // Encodings of data of other lengths are rejected.
+        let (short, _) = encode_to_group(&data[..15]).unwrap();
+
+        assert_eq!(decode_32_bytes_from_group(&[encoded[0], short]).unwrap_err(), EncodingError::InvalidEncoding);
+    }
+
+    #[test]
+    fn chunking_roundtrip() {
+        let mut rng = thread_rng();
+
+        for length in [0usize, 1, 28, 29, 30, 58, 100].iter() {
+            let mut data = vec![0u8; *length];
+
+            rng.fill_bytes(&mut data);
+
+            let chunks = chunk_bytes(&data);
+
+            assert_eq!(chunks.len(), number_of_chunks(*length));
+            assert_eq!(unchunk_bytes(&chunks).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn chunking_preserves_trailing_zeroes() {
+        let data = [0u8; 40];
+        let chunks = chunk_bytes(&data);
+
+        for chunk in chunks.iter() {
+            assert!(!encode_to_group(chunk).unwrap().0.is_identity());
+        }
+        assert_eq!(unchunk_bytes(&chunks).unwrap(), &data[..]);
+    }
+
+    #[test]
+    fn chunking_rejects_truncation() {
+        let chunks = chunk_bytes(&[1u8; 64]);
 
-        assert_eq!(counter_a, counter_b);
-        assert_eq!(decoded, data);
+        assert!(unchunk_bytes(&chunks[..2]).is_none());
+        assert!(unchunk_bytes(&[]).is_none());
     }
 }
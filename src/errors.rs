@@ -72,9 +72,41 @@ impl From<NoneError> for MacError {
 #[cfg(feature = "std")]
 impl Error for MacError { }
 
+/// Errors which may occur when encoding data to, or decoding data from, a
+/// group element.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum EncodingError {
+    /// The data was longer than the maximum which may be encoded.
+    DataTooLong,
+    /// No candidate encoding was a valid group element.
+    ExhaustedCounter,
+    /// The group element was not an encoding of any data.
+    InvalidEncoding,
+}
+
+impl Display for EncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EncodingError::DataTooLong
+                => write!(f, "The data was too long to be encoded as a group element"),
+            EncodingError::ExhaustedCounter
+                => write!(f, "No valid encoding of the data as a group element was found"),
+            EncodingError::InvalidEncoding
+                => write!(f, "The group element did not encode any data"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Error for EncodingError { }
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub enum CredentialError {
     BadAttribute,
+    /// A proof in a batch could not be verified.
+    ///
+    /// The `index` is that of the first proof in the batch which failed.
+    BatchVerificationFailure{ index: usize },
     CredentialIssuance,
     MacCreation,
     MacVerification,
@@ -83,9 +115,23 @@ pub enum CredentialError {
     NoIssuerKey,
     NoIssuerParameters,
     NoSystemParameters,
+    /// A presented hidden attribute was not among a group's members.
+    NotAGroupMember,
+    /// A [`crate::verifier::NullifierStore`] could not be read or written.
+    NullifierStorage,
     PointDecompressionError,
     ScalarFormatError,
+    /// A nullifier, such as a serial number, had already been seen.
+    SpentNullifier,
     UndecryptableAttribute,
+    /// No key with the given identifier was found.
+    UnknownKey,
+    /// A hidden scalar attribute did not satisfy a predicate, or a required
+    /// predicate was not proven.
+    UnsatisfiedPredicate,
+    /// Serialised data began with a format version which this library does
+    /// not understand.
+    UnsupportedVersion,
     VerificationFailure,
     WrongNumberOfAttributes,
     WrongNumberOfBytes,
@@ -96,6 +142,8 @@ impl fmt::Display for CredentialError {
         match *self {
             CredentialError::BadAttribute
                 => write!(f, "An attribute was unacceptable"),
+            CredentialError::BatchVerificationFailure{ index: i }
+                => write!(f, "The proof at index {} in the batch could not be verified", i),
             CredentialError::CredentialIssuance
                 => write!(f, "Failed to get a credential issued"),
             CredentialError::MacCreation
@@ -112,18 +160,30 @@ impl fmt::Display for CredentialError {
                 => write!(f, "The issuer was not initialised properly and has no parameters"),
             CredentialError::NoSystemParameters
                 => write!(f, "The system parameters were not initialised"),
+            CredentialError::NotAGroupMember
+                => write!(f, "The hidden attribute was not a member of the group"),
+            CredentialError::NullifierStorage
+                => write!(f, "The nullifier store could not be read or written"),
             CredentialError::PointDecompressionError
                 => write!(f, "Cannot decompress Ristretto point"),
             CredentialError::ScalarFormatError
                 => write!(f, "Cannot use scalar with high-bit set"),
+            CredentialError::SpentNullifier
+                => write!(f, "The credential had already been shown"),
             CredentialError::UndecryptableAttribute
                 => write!(f, "A hidden group attribute could not be decrypted"),
+            CredentialError::UnknownKey
+                => write!(f, "No issuer key with the given identifier was found"),
+            CredentialError::UnsatisfiedPredicate
+                => write!(f, "A predicate over a hidden scalar attribute was not satisfied"),
+            CredentialError::UnsupportedVersion
+                => write!(f, "The serialised data was of an unsupported version"),
             CredentialError::VerificationFailure
                 => write!(f, "The proof could not be verified"),
             CredentialError::WrongNumberOfAttributes
                 => write!(f, "The credential did not have the correct number of attributes"),
             CredentialError::WrongNumberOfBytes
-                => write!(f, "The credential could not be deserialised because it was not a multiple of 32 bytes"),
+                => write!(f, "The data could not be deserialised because it had the wrong number of bytes"),
         }
     }
 }
@@ -151,6 +211,19 @@ impl From<MacError> for CredentialError {
     }
 }
 
+impl From<EncodingError> for CredentialError {
+    fn from(source: EncodingError) -> CredentialError {
+        match source {
+            EncodingError::DataTooLong
+                => CredentialError::WrongNumberOfBytes,
+            EncodingError::ExhaustedCounter
+                => CredentialError::BadAttribute,
+            EncodingError::InvalidEncoding
+                => CredentialError::BadAttribute,
+        }
+    }
+}
+
 #[cfg(feature = "std")]
 impl Error for CredentialError { }
 
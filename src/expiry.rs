@@ -0,0 +1,196 @@
+// -*- mode: rust; -*-
+//
+// This file is part of aeonflux.
+// Copyright (c) 2020 The Brave Authors
+// See LICENSE for licensing information.
+//
+// Authors:
+// - isis agora lovecruft <isis@patternsinthevoid.net>
+
+//! Credentials which expire, and issuers whose keys are rotated per epoch.
+//!
+//! The last attribute of a credential is reserved for its expiry time, a
+//! hidden scalar attribute holding a timestamp in e.g. seconds since the Unix
+//! epoch.  Upon presentation, the user proves that the expiry time is later
+//! than the verifier's clock with a [`Predicate`] over it, without revealing
+//! the expiry time itself.
+//!
+//! An issuer may additionally rotate its key every [`Epoch`], by issuing with
+//! a fresh [`Issuer`] from [`Issuer::rotate`] and giving credentials an expiry
+//! time no later than a few epochs hence.  Since verification requires the
+//! issuer's secret key, the verifier keeps the [`Issuer`] for each epoch whose
+//! credentials may not yet have expired, and checks presentations against them
+//! all with [`ProofOfValidCredential::verify_unexpired`].
+//!
+//! [`Issuer`]: crate::issuer::Issuer
+//! [`Issuer::rotate`]: crate::issuer::Issuer::rotate
+//! [`ProofOfValidCredential::verify_unexpired`]: crate::nizk::ProofOfValidCredential::verify_unexpired
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::vec::Vec;
+#[cfg(all(not(feature = "alloc"), feature = "std"))]
+use std::vec::Vec;
+
+use curve25519_dalek::scalar::Scalar;
+
+use crate::amacs::Attribute;
+use crate::errors::CredentialError;
+use crate::nizk::Predicate;
+use crate::parameters::SystemParameters;
+
+/// A period of time, such as a day, during which an issuer uses a single key.
+///
+/// Epochs are numbered consecutively from zero, starting at timestamp zero.
+pub type Epoch = u64;
+
+/// Determine the [`Epoch`] containing the `timestamp`, for epochs of
+/// `epoch_length` units of time each.
+///
+/// # Panics
+///
+/// If `epoch_length` is zero.
+pub fn epoch_at(timestamp: u64, epoch_length: u64) -> Epoch {
+    timestamp / epoch_length
+}
+
+/// Determine the first timestamp after the `epoch`, for epochs of
+/// `epoch_length` units of time each.
+///
+/// This is a natural expiry time for credentials issued during the `epoch`.
+pub fn epoch_end(epoch: Epoch, epoch_length: u64) -> u64 {
+    epoch.saturating_add(1).saturating_mul(epoch_length)
+}
+
+/// Get the index of the attribute reserved for a credential's expiry time,
+/// which is the last attribute.
+///
+/// # Returns
+///
+/// A `Result` whose `Ok` value is the index, otherwise
+/// [`CredentialError::WrongNumberOfAttributes`] if the `system_parameters`
+/// have no attributes.
+pub fn expiry_index(system_parameters: &SystemParameters) -> Result<usize, CredentialError> {
+    match system_parameters.NUMBER_OF_ATTRIBUTES {
+        0 => Err(CredentialError::WrongNumberOfAttributes),
+        n => Ok(n as usize - 1),
+    }
+}
+
+/// Add an expiry time to some `attributes`, for issuance.
+///
+/// Any attributes between those given and the reserved attribute are set to
+/// revealed scalars equal to zero, exactly as if they were not present.
+///
+/// # Inputs
+///
+/// * The `system_parameters`,
+/// * Up to one fewer `attributes` than the `system_parameters` allow,
+/// * The time at which the credential `expires_at`.
+///
+/// # Returns
+///
+/// A `Result` whose `Ok` value is the attributes including the expiry time,
+/// otherwise a [`CredentialError`].
+pub fn with_expiry(
+    system_parameters: &SystemParameters,
+    attributes: &[Attribute],
+    expires_at: u64,
+) -> Result<Vec<Attribute>, CredentialError>
+{
+    let index = expiry_index(system_parameters)?;
+
+    if attributes.len() > index {
+        return Err(CredentialError::WrongNumberOfAttributes);
+    }
+
+    let mut attributes: Vec<Attribute> = attributes.to_vec();
+
+    attributes.resize(index, Attribute::PublicScalar(Scalar::zero()));
+    attributes.push(Attribute::SecretScalar(Scalar::from(expires_at)));
+
+    Ok(attributes)
+}
+
+/// The [`Predicate`] that a credential has not expired at the time `now`,
+/// i.e. that its expiry time is strictly later than `now`.
+///
+/// # Returns
+///
+/// A `Result` whose `Ok` value is the [`Predicate`], otherwise a [`CredentialError`].
+pub fn not_expired(
+    system_parameters: &SystemParameters,
+    now: u64,
+) -> Result<Predicate, CredentialError>
+{
+    Ok(Predicate::AtLeast { index: expiry_index(system_parameters)? as u16, bound: now.saturating_add(1) })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rand::thread_rng;
+
+    use crate::credential::AnonymousCredential;
+    use crate::issuer::Issuer;
+    use crate::nizk::PresentationOptions;
+
+    #[test]
+    fn epochs() {
+        assert_eq!(epoch_at(0, 86400), 0);
+        assert_eq!(epoch_at(86399, 86400), 0);
+        assert_eq!(epoch_at(86400, 86400), 1);
+        assert_eq!(epoch_end(0, 86400), 86400);
+        assert_eq!(epoch_end(u64::max_value(), 86400), u64::max_value());
+    }
+
+    #[test]
+    fn expiring_credentials_across_epochs() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let epoch_length: u64 = 86400;
+        let now: u64 = 1600000000;
+        let epoch = epoch_at(now, epoch_length);
+
+        let previous_issuer = Issuer::new(&system_parameters, &mut rng);
+        let issuer = previous_issuer.rotate(&mut rng);
+        let issuers = [(epoch - 1, &previous_issuer), (epoch, &issuer)];
+
+        let attributes = vec![Attribute::SecretScalar(Scalar::random(&mut rng))];
+        let attributes = with_expiry(&system_parameters, &attributes, epoch_end(epoch, epoch_length)).unwrap();
+
+        assert_eq!(attributes.len(), 3);
+        assert!(with_expiry(&system_parameters, &attributes, now).is_err());
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let options = PresentationOptions {
+            predicates: vec![not_expired(&system_parameters, now).unwrap()],
+            ..Default::default()
+        };
+        let proof = credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                                 None, &options, &mut rng).unwrap();
+
+        assert_eq!(proof.verify_unexpired(&issuers, now), Ok(epoch));
+        assert_eq!(proof.verify_unexpired(&issuers, now + epoch_length),
+                   Err(CredentialError::UnsatisfiedPredicate));
+        assert_eq!(proof.verify_unexpired(&issuers[..1], now),
+                   Err(CredentialError::VerificationFailure));
+
+        // The credential cannot be shown as unexpired after its expiry time.
+        let options = PresentationOptions {
+            predicates: vec![not_expired(&system_parameters, epoch_end(epoch, epoch_length)).unwrap()],
+            ..Default::default()
+        };
+
+        assert_eq!(credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                                None, &options, &mut rng).err(),
+                   Some(CredentialError::UnsatisfiedPredicate));
+
+        // Presentations without an expiry proof are not accepted.
+        let proof = credential.show(&system_parameters, &issuer.issuer_parameters, None, &mut rng).unwrap();
+
+        assert_eq!(proof.verify_unexpired(&issuers, now), Err(CredentialError::UnsatisfiedPredicate));
+    }
+}
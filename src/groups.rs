@@ -0,0 +1,342 @@
+// -*- mode: rust; -*-
+//
+// This file is part of aeonflux.
+// Copyright (c) 2020 The Brave Authors
+// See LICENSE for licensing information.
+//
+// Authors:
+// - isis agora lovecruft <isis@patternsinthevoid.net>
+
+//! Groups whose members share a symmetric key.
+//!
+//! Every member of a group holds its [`GroupSecretParams`], which contain a
+//! symmetric [`Keypair`].  A server stores, for each [`Group`], its
+//! [`GroupPublicParams`] and the encryptions of its members' [`Uid`]s under the
+//! group's key, and so never learns who the members are.
+//!
+//! A member holds a credential certifying their UID as a hidden group element
+//! attribute, i.e. an [`Attribute::SecretPoint`](crate::amacs::Attribute).
+//! Presenting it with the group's keypair encrypts their UID under the group's
+//! key, with a proof that the ciphertext encrypts the certified UID, which the
+//! server may then look up in the group's list of members with
+//! [`Group::verify_membership`].  The presentation must be bound to a fresh
+//! [`Challenge`] from the server, since anyone who observed it could otherwise
+//! replay it to pass as a member.  Since the symmetric encryption has unique
+//! ciphertexts, this ciphertext is exactly the one which was stored when the
+//! member was added.
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::vec::Vec;
+#[cfg(all(not(feature = "alloc"), feature = "std"))]
+use std::vec::Vec;
+
+use core::convert::TryFrom;
+
+use curve25519_dalek::ristretto::CompressedRistretto;
+
+use rand_core::CryptoRng;
+use rand_core::RngCore;
+
+use serde::{self, Serialize, Deserialize, Serializer, Deserializer};
+use serde::de::Visitor;
+
+use crate::errors::CredentialError;
+use crate::issuer::Issuer;
+use crate::nizk::Challenge;
+use crate::nizk::ProofOfValidCredential;
+use crate::parameters::SystemParameters;
+use crate::profile::ProfileKey;
+use crate::profile::ProfileKeyCiphertext;
+use crate::symmetric::Ciphertext;
+use crate::symmetric::CiphertextSet;
+use crate::symmetric::Keypair;
+use crate::symmetric::MasterSecret;
+use crate::symmetric::Plaintext;
+use crate::symmetric::PublicKey;
+
+/// The size of a [`Uid`], in bytes.
+pub const SIZEOF_UID: usize = 16;
+
+/// A member's unique identifier, such as a UUID.
+pub type Uid = [u8; SIZEOF_UID];
+
+/// The secret parameters of a group, which are shared by all its members.
+#[derive(Clone)]
+pub struct GroupSecretParams {
+    master_secret: MasterSecret,
+    keypair: Keypair,
+}
+
+impl GroupSecretParams {
+    /// Derive a group's [`GroupSecretParams`] from its `master_secret`.
+    pub fn derive(
+        master_secret: &MasterSecret,
+        system_parameters: &SystemParameters,
+    ) -> GroupSecretParams
+    {
+        GroupSecretParams {
+            master_secret: *master_secret,
+            keypair: Keypair::derive(master_secret, system_parameters),
+        }
+    }
+
+    /// Generate the [`GroupSecretParams`] for a new group.
+    ///
+    /// # Inputs
+    ///
+    /// * Some [`SystemParameters`], and
+    /// * A cryptographically secure pseudo-random number generator.
+    pub fn generate<R>(
+        system_parameters: &SystemParameters,
+        csprng: &mut R,
+    ) -> GroupSecretParams
+    where
+        R: RngCore + CryptoRng,
+    {
+        let (keypair, master_secret) = Keypair::generate(system_parameters, csprng);
+
+        GroupSecretParams { master_secret, keypair }
+    }
+
+    /// The group's [`MasterSecret`], which should be shared with new members.
+    pub fn master_secret(&self) -> &MasterSecret {
+        &self.master_secret
+    }
+
+    /// The group's symmetric [`Keypair`], with which members present their
+    /// credentials to prove their membership.
+    pub fn keypair(&self) -> &Keypair {
+        &self.keypair
+    }
+
+    /// The group's [`GroupPublicParams`], to be stored by the server.
+    pub fn public_params(&self) -> GroupPublicParams {
+        GroupPublicParams { public_key: self.keypair.public }
+    }
+
+    /// Encrypt a member's `uid` under the group's key, e.g. to add them to
+    /// the group.
+    pub fn encrypt_uid(&self, uid: &Uid) -> Result<Ciphertext, CredentialError> {
+        let plaintext = Plaintext::try_from(&uid[..])?;
+
+        Ok(self.keypair.encrypt(&plaintext))
+    }
+
+    /// Decrypt an encrypted member UID, e.g. to list the group's members.
+    pub fn decrypt_uid(&self, ciphertext: &Ciphertext) -> Result<Uid, CredentialError> {
+        let plaintext = self.keypair.decrypt(ciphertext)?;
+        let data = Vec::<u8>::try_from(&plaintext)?;
+
+        if data.len() != SIZEOF_UID {
+            return Err(CredentialError::UndecryptableAttribute);
+        }
+
+        let mut uid: Uid = [0u8; SIZEOF_UID];
+
+        uid.copy_from_slice(&data);
+
+        Ok(uid)
+    }
+
+    /// Encrypt a member's `profile_key` under the group's key, for sharing
+    /// with the other members.
+    pub fn encrypt_profile_key(&self, profile_key: &ProfileKey) -> Result<ProfileKeyCiphertext, CredentialError> {
+        let [first, second] = profile_key.plaintexts()?;
+
+        Ok(ProfileKeyCiphertext([self.keypair.encrypt(&first), self.keypair.encrypt(&second)]))
+    }
+
+    /// Decrypt a member's encrypted profile key.
+    pub fn decrypt_profile_key(&self, ciphertext: &ProfileKeyCiphertext) -> Result<ProfileKey, CredentialError> {
+        let plaintexts = [self.keypair.decrypt(&ciphertext.0[0])?, self.keypair.decrypt(&ciphertext.0[1])?];
+
+        ProfileKey::from_plaintexts(&plaintexts)
+    }
+}
+
+/// The public parameters of a group, which identify it to the server.
+#[derive(Clone, Copy)]
+pub struct GroupPublicParams {
+    /// The public key of the group's symmetric [`Keypair`].
+    pub public_key: PublicKey,
+}
+
+impl GroupPublicParams {
+    /// Deserialise some [`GroupPublicParams`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<GroupPublicParams, CredentialError> {
+        if bytes.len() != 32 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut chunk = [0u8; 32];
+
+        chunk.copy_from_slice(bytes);
+
+        Ok(GroupPublicParams { public_key: PublicKey { pk: try_deserialise!("pk", chunk) } })
+    }
+
+    /// Serialise these [`GroupPublicParams`] to a byte array.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.public_key.pk.compress().to_bytes()
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(GroupPublicParams, "A valid byte sequence representing GroupPublicParams");
+
+/// A group as stored by the server: its [`GroupPublicParams`] and the
+/// encrypted UIDs of its members.
+#[derive(Clone)]
+pub struct Group {
+    public_params: GroupPublicParams,
+    members: CiphertextSet,
+}
+
+impl Group {
+    /// Create a new [`Group`] without any members.
+    pub fn new(public_params: GroupPublicParams) -> Group {
+        Group { public_params, members: CiphertextSet::new() }
+    }
+
+    /// This group's [`GroupPublicParams`].
+    pub fn public_params(&self) -> &GroupPublicParams {
+        &self.public_params
+    }
+
+    /// The encrypted UIDs of this group's members.
+    pub fn members(&self) -> &CiphertextSet {
+        &self.members
+    }
+
+    /// Add the member whose UID is encrypted as `ciphertext`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if they were not already a member, otherwise `false`.
+    pub fn add_member(&mut self, ciphertext: &Ciphertext) -> bool {
+        self.members.insert(ciphertext)
+    }
+
+    /// Remove the member whose UID is encrypted as `ciphertext`.
+    ///
+    /// # Returns
+    ///
+    /// `true` if they were a member, otherwise `false`.
+    pub fn remove_member(&mut self, ciphertext: &Ciphertext) -> bool {
+        self.members.remove(ciphertext)
+    }
+
+    /// Verify a presentation of a credential, made with this group's keypair
+    /// and bound to the server's `challenge`, and that its hidden UID is a
+    /// member of this group.
+    ///
+    /// # Inputs
+    ///
+    /// * The `issuer` of the credential,
+    /// * The `proof` of the credential,
+    /// * The `index` of the hidden group element attribute which is the UID, and
+    /// * The `challenge` which the server sent for this presentation.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`],
+    /// which is [`CredentialError::NotAGroupMember`] if the credential is
+    /// valid but the UID is not a member, and [`CredentialError::MissingData`]
+    /// if the presentation is not bound to any challenge.
+    pub fn verify_membership(
+        &self,
+        issuer: &Issuer,
+        proof: &ProofOfValidCredential,
+        index: u16,
+        challenge: &Challenge,
+    ) -> Result<(), CredentialError>
+    {
+        let (public_key, ciphertext) = proof.encrypted_attribute(index).ok_or(CredentialError::MissingData)?;
+
+        if public_key.pk != self.public_params.public_key.pk {
+            return Err(CredentialError::VerificationFailure);
+        }
+
+        proof.verify_with_challenge(issuer, challenge)?;
+
+        match bool::from(self.members.contains(&ciphertext)) {
+            true  => Ok(()),
+            false => Err(CredentialError::NotAGroupMember),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use curve25519_dalek::scalar::Scalar;
+
+    use rand::thread_rng;
+
+    use crate::amacs::Attribute;
+    use crate::credential::AnonymousCredential;
+    use crate::nizk::PresentationOptions;
+
+    #[test]
+    fn group_membership() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let uid: Uid = [7u8; SIZEOF_UID];
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretPoint(Plaintext::try_from(&uid[..]).unwrap()));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+
+        let group_secret_params = GroupSecretParams::generate(&system_parameters, &mut rng);
+        let other_secret_params = GroupSecretParams::generate(&system_parameters, &mut rng);
+        let public_params = GroupPublicParams::from_bytes(&group_secret_params.public_params().to_bytes()).unwrap();
+        let mut group = Group::new(public_params);
+
+        let member = group_secret_params.encrypt_uid(&uid).unwrap();
+
+        assert!(group.add_member(&group_secret_params.encrypt_uid(&[1u8; SIZEOF_UID]).unwrap()));
+        assert!(group.add_member(&member));
+        assert!(!group.add_member(&member));
+        assert_eq!(group_secret_params.decrypt_uid(&member).unwrap(), uid);
+
+        let challenge = Challenge::generate(b"example.com", &mut rng);
+        let options = PresentationOptions { challenge: Some(challenge.clone()), ..Default::default() };
+        let proof = credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                                 Some(group_secret_params.keypair()), &options, &mut rng).unwrap();
+
+        assert!(group.verify_membership(&issuer, &proof, 0, &challenge).is_ok());
+        assert_eq!(group.verify_membership(&issuer, &proof, 2, &challenge).err(), Some(CredentialError::MissingData));
+
+        // A presentation cannot be replayed in answer to another challenge, nor made without one.
+        let unbound = credential.show(&system_parameters, &issuer.issuer_parameters,
+                                      Some(group_secret_params.keypair()), &mut rng).unwrap();
+
+        assert_eq!(group.verify_membership(&issuer, &proof, 0, &Challenge::generate(b"example.com", &mut rng)).err(),
+                   Some(CredentialError::VerificationFailure));
+        assert_eq!(group.verify_membership(&issuer, &unbound, 0, &challenge).err(), Some(CredentialError::MissingData));
+
+        // A presentation made with another group's keypair proves nothing about this group.
+        let other = credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                                 Some(other_secret_params.keypair()), &options, &mut rng).unwrap();
+
+        assert_eq!(group.verify_membership(&issuer, &other, 0, &challenge).err(), Some(CredentialError::VerificationFailure));
+
+        // Members who have been removed can no longer prove their membership.
+        assert!(group.remove_member(&member));
+        assert_eq!(group.members().len(), 1);
+        assert_eq!(group.verify_membership(&issuer, &proof, 0, &challenge).err(), Some(CredentialError::NotAGroupMember));
+
+        // The same holds for a group which is derived from the shared master secret.
+        let derived = GroupSecretParams::derive(group_secret_params.master_secret(), &system_parameters);
+
+        assert!(group.add_member(&derived.encrypt_uid(&uid).unwrap()));
+        assert!(group.verify_membership(&issuer, &proof, 0, &challenge).is_ok());
+    }
+}
@@ -14,6 +14,8 @@ use alloc::vec::Vec;
 #[cfg(all(not(feature = "alloc"), feature = "std"))]
 use std::vec::Vec;
 
+use curve25519_dalek::scalar::Scalar;
+
 use rand_core::CryptoRng;
 use rand_core::RngCore;
 
@@ -26,12 +28,22 @@ use serde::ser::Serializer;
 use crate::amacs::sizeof_secret_key;
 use crate::amacs::Amac;
 use crate::amacs::Attribute;
+use crate::amacs::BlindedAmac;
+use crate::amacs::BlindedAttribute;
 use crate::amacs::SecretKey;
-use crate::credential::AnonymousCredential;
+use crate::credential::CredentialRequest;
+use crate::credential::RefreshRequest;
 use crate::errors::CredentialError;
+use crate::nizk::ProofOfBlindedIssuance;
+use crate::nizk::ProofOfIssuance;
+use crate::nizk::ProofOfValidCredential;
+use crate::nizk::SerialNumber;
 use crate::parameters::sizeof_system_parameters;
+use crate::parameters::SIZEOF_ISSUER_PARAMETERS;
 use crate::parameters::IssuerParameters;
+use crate::parameters::KeyId;
 use crate::parameters::SystemParameters;
+use crate::verifier::NullifierStore;
 
 /// An anonymous credential issuer/verifier.
 pub struct Issuer {
@@ -68,6 +80,23 @@ impl Issuer {
         }
     }
 
+    /// Rotate to a fresh key under the same [`SystemParameters`], e.g. at the
+    /// start of a new [`Epoch`].
+    ///
+    /// Credentials issued by this [`Issuer`] can only be verified by it, and
+    /// so it should be kept until they have all expired.
+    ///
+    /// [`Epoch`]: crate::expiry::Epoch
+    pub fn rotate<C>(
+        &self,
+        csprng: &mut C,
+    ) -> Issuer
+    where
+        C: CryptoRng + RngCore,
+    {
+        Issuer::new(&self.system_parameters, csprng)
+    }
+
     /// Issue a new anonymous credential on a set of `attributes` in an
     /// unblinded manner.
     ///
@@ -82,41 +111,165 @@ impl Issuer {
     ///
     /// # Returns
     ///
-    /// A `Result` whose `Ok` value is an [`AnonymousCredential`], otherwise a
+    /// A `Result` whose `Ok` value is an [`IssuanceResponse`], from which the
+    /// user may obtain their [`AnonymousCredential`], otherwise a
     /// [`CredentialError`].
     pub fn issue<C>(
         &self,
-        attributes: Vec<Attribute>,
+        attributes: &Vec<Attribute>,
         csprng: &mut C,
-    ) -> Result<AnonymousCredential, CredentialError>
+    ) -> Result<IssuanceResponse, CredentialError>
     where
         C: CryptoRng + RngCore,
     {
-        match Amac::tag(csprng, &self.system_parameters, &self.amacs_key, &attributes) {
-            Ok(amac) => Ok(AnonymousCredential { amac, attributes }),
-            Err(x) => Err(x.into()),
+        let amac = Amac::tag(csprng, &self.system_parameters, &self.amacs_key, attributes)?;
+        let proof = ProofOfIssuance::prove(&self, &amac, attributes);
+
+        Ok(IssuanceResponse { amac, proof })
+    }
+
+    /// Issue a new anonymous credential on a set of attributes in a blinded
+    /// manner.
+    ///
+    /// By "blinded" we mean that the hidden scalar attributes in the `request`
+    /// are encrypted, and the issuer is only able to perform verification on
+    /// the remaining revealed attributes.
+    ///
+    /// # Inputs
+    ///
+    /// * A [`CredentialRequest`] from a user,
+    /// * A `csprng`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`BlindedIssuanceResponse`], which the
+    /// user may unblind to obtain their [`AnonymousCredential`], otherwise a
+    /// [`CredentialError`].
+    pub fn issue_blinded<C>(
+        &self,
+        request: &CredentialRequest,
+        csprng: &mut C,
+    ) -> Result<BlindedIssuanceResponse, CredentialError>
+    where
+        C: CryptoRng + RngCore,
+    {
+        // Nothing in a bare request proves what its encrypted group element
+        // attributes are, so they are only accepted from protocols which do.
+        for attribute in request.attributes.iter() {
+            if let BlindedAttribute::EncryptedPoint(_) = attribute {
+                return Err(CredentialError::BadAttribute);
+            }
         }
+
+        self.tag_blinded(request, csprng)
+    }
+
+    /// Verify a blinded `request` and compute a [`BlindedAmac`] over its
+    /// attributes.
+    pub(crate) fn tag_blinded<C>(
+        &self,
+        request: &CredentialRequest,
+        csprng: &mut C,
+    ) -> Result<BlindedIssuanceResponse, CredentialError>
+    where
+        C: CryptoRng + RngCore,
+    {
+        request.proof.verify(&self.system_parameters, &request.public_key, &request.attributes)?;
+
+        let (amac, nonce) = BlindedAmac::tag(csprng, &self.system_parameters, &self.amacs_key,
+                                             &request.public_key, &request.attributes)?;
+        let proof = ProofOfBlindedIssuance::prove(&self, &request.public_key, &request.attributes, &amac, &nonce);
+
+        Ok(BlindedIssuanceResponse { amac, proof })
+    }
+
+    /// Issue a fresh credential in exchange for an old one issued by this
+    /// issuer, without learning the hidden scalar attributes of either.
+    ///
+    /// # Inputs
+    ///
+    /// * The user's `request`,
+    /// * The `updates` which this issuer requires be made to the hidden scalar
+    ///   attributes, as `(index, delta_i)` tuples, e.g. `(0, Scalar::one())`
+    ///   to increment a counter,
+    /// * The `serial_number_index` of the hidden scalar attribute from which
+    ///   the old credential's [`SerialNumber`] must be derived,
+    /// * The `nullifiers` which record the serial numbers of the credentials
+    ///   already refreshed, and
+    /// * A `csprng`.
+    ///
+    /// Any policy over the revealed attributes of the new credential, or over
+    /// the [`RefreshRequest::presentation`] of the old one, is the caller's to
+    /// check.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`BlindedIssuanceResponse`], which the
+    /// user may unblind to obtain their new [`AnonymousCredential`], otherwise
+    /// a [`CredentialError`], which is [`CredentialError::SpentNullifier`] if
+    /// the old credential has already been refreshed.
+    pub fn refresh<C, S>(
+        &self,
+        request: &RefreshRequest,
+        updates: &[(usize, Scalar)],
+        serial_number_index: u16,
+        nullifiers: &mut S,
+        csprng: &mut C,
+    ) -> Result<BlindedIssuanceResponse, CredentialError>
+    where
+        C: CryptoRng + RngCore,
+        S: NullifierStore,
+    {
+        let serial_number = request.verify(self, updates, serial_number_index)?;
+
+        issue_refreshed(self, request, &serial_number, nullifiers, csprng)
     }
 }
 
+/// An issuer's response to an unblinded issuance request, containing an
+/// algebraic MAC over the requested attributes and a proof that it was created
+/// with the key corresponding to the published [`IssuerParameters`].
+pub struct IssuanceResponse {
+    pub(crate) amac: Amac,
+    pub(crate) proof: ProofOfIssuance,
+}
+
+/// An issuer's response to a [`CredentialRequest`], containing an encrypted
+/// algebraic MAC over the requested attributes and a proof of its correctness.
+pub struct BlindedIssuanceResponse {
+    pub(crate) amac: BlindedAmac,
+    pub(crate) proof: ProofOfBlindedIssuance,
+}
+
+/// Given the `number_of_attributes`, calculate the size of a serialised
+/// [`Issuer`], in bytes.
+fn sizeof_issuer(number_of_attributes: u8) -> usize {
+    sizeof_system_parameters(number_of_attributes) + SIZEOF_ISSUER_PARAMETERS + sizeof_secret_key(number_of_attributes)
+}
+
 impl Issuer {
     /// Create an [`Issuer`] from bytes.
     pub fn from_bytes(bytes: &[u8]) -> Result<Issuer, CredentialError> {
-        let system_parameters = SystemParameters::from_bytes(&bytes)?;
-        let offset = sizeof_system_parameters(system_parameters.NUMBER_OF_ATTRIBUTES);
-        let issuer_parameters = IssuerParameters::from_bytes(&bytes[offset..offset+64])?;
-        let amacs_key = SecretKey::from_bytes(&bytes[offset+64..])?;
+        if bytes.len() < 1 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let offset = sizeof_system_parameters(bytes[0]);
+
+        if bytes.len() != sizeof_issuer(bytes[0]) {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let system_parameters = SystemParameters::from_bytes(&bytes[..offset])?;
+        let issuer_parameters = IssuerParameters::from_bytes(&bytes[offset..offset+SIZEOF_ISSUER_PARAMETERS])?;
+        let amacs_key = SecretKey::from_bytes(&bytes[offset+SIZEOF_ISSUER_PARAMETERS..])?;
 
         Ok(Issuer { system_parameters, issuer_parameters, amacs_key })
     }
 
     /// Serialise this [`Issuer`] to a byte array.
     pub fn to_bytes(&self) -> Vec<u8> {
-        let size = 64 +
-            sizeof_system_parameters(self.system_parameters.NUMBER_OF_ATTRIBUTES) +
-            sizeof_secret_key(self.system_parameters.NUMBER_OF_ATTRIBUTES);
-            
-        let mut bytes: Vec<u8> = Vec::with_capacity(size);
+        let mut bytes: Vec<u8> = Vec::with_capacity(sizeof_issuer(self.system_parameters.NUMBER_OF_ATTRIBUTES));
 
         bytes.extend(self.system_parameters.to_bytes());
         bytes.extend(self.issuer_parameters.to_bytes());
@@ -127,3 +280,294 @@ impl Issuer {
 }
 
 impl_serde_with_to_bytes_and_from_bytes!(Issuer, "A valid byte sequence representing an Issuer");
+
+/// The keys of an issuer over time: the current key, with which new
+/// credentials are issued, and retired keys, which are kept only to verify
+/// credentials issued before the last rotation.
+///
+/// Every key is identified by the [`KeyId`] of its [`IssuerParameters`],
+/// which presentations carry, so that verification is dispatched to the key
+/// the credential was issued under and outstanding credentials keep working
+/// after a rotation.  The keyring also keeps a log of the parameters of every
+/// key it has ever held, including those which have since been removed, which
+/// may be published so that users can check which keys were legitimately
+/// used.
+pub struct IssuerKeyring {
+    current: Issuer,
+    retired: Vec<Issuer>,
+    history: Vec<IssuerParameters>,
+}
+
+impl IssuerKeyring {
+    /// Create a new [`IssuerKeyring`] whose current key is that of the `issuer`.
+    pub fn new(issuer: Issuer) -> IssuerKeyring {
+        let history = vec![issuer.issuer_parameters.clone()];
+
+        IssuerKeyring { current: issuer, retired: Vec::new(), history }
+    }
+
+    /// The [`Issuer`] with the current key, which should be used for issuance.
+    pub fn current(&self) -> &Issuer {
+        &self.current
+    }
+
+    /// Rotate to a fresh current key, retiring the previous one.
+    ///
+    /// # Returns
+    ///
+    /// The [`KeyId`] of the new current key.
+    pub fn rotate<C>(
+        &mut self,
+        csprng: &mut C,
+    ) -> KeyId
+    where
+        C: CryptoRng + RngCore,
+    {
+        let next = self.current.rotate(csprng);
+        let previous = core::mem::replace(&mut self.current, next);
+
+        self.retired.push(previous);
+        self.history.push(self.current.issuer_parameters.clone());
+        self.current.issuer_parameters.key_id()
+    }
+
+    /// Get the [`Issuer`], current or retired, whose key has the given `key_id`.
+    pub fn get(&self, key_id: &KeyId) -> Option<&Issuer> {
+        if self.current.issuer_parameters.key_id() == *key_id {
+            return Some(&self.current);
+        }
+        self.retired.iter().find(|issuer| issuer.issuer_parameters.key_id() == *key_id)
+    }
+
+    /// Remove the retired key with the given `key_id`, e.g. once every
+    /// credential issued under it has expired.  The current key cannot be
+    /// removed, and the removed key's parameters remain in the history.
+    ///
+    /// # Returns
+    ///
+    /// The removed [`Issuer`], if there was a retired key with that `key_id`.
+    pub fn remove(&mut self, key_id: &KeyId) -> Option<Issuer> {
+        let index = self.retired.iter().position(|issuer| issuer.issuer_parameters.key_id() == *key_id)?;
+
+        Some(self.retired.remove(index))
+    }
+
+    /// The [`IssuerParameters`] of every key this keyring has ever held, from
+    /// oldest to newest, the last being the current key.
+    pub fn history(&self) -> &[IssuerParameters] {
+        &self.history
+    }
+
+    /// Verify a [`ProofOfValidCredential`] with the key it claims to have
+    /// been issued under.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the [`KeyId`] of the key which verified
+    /// the `proof`, otherwise a [`CredentialError`], which is
+    /// [`CredentialError::UnknownKey`] if this keyring has no such key.
+    pub fn verify(
+        &self,
+        proof: &ProofOfValidCredential,
+    ) -> Result<KeyId, CredentialError>
+    {
+        let key_id = proof.key_id();
+        let issuer = self.get(&key_id).ok_or(CredentialError::UnknownKey)?;
+
+        proof.verify(issuer)?;
+
+        Ok(key_id)
+    }
+
+    /// Issue a fresh credential under the current key in exchange for an old
+    /// one issued under any key in this keyring, as in [`Issuer::refresh`].
+    ///
+    /// This allows users to migrate their credentials to the current key after
+    /// a rotation, before the retired key is removed.  The `nullifiers` must
+    /// be shared by all of this keyring's keys.
+    pub fn refresh<C, S>(
+        &self,
+        request: &RefreshRequest,
+        updates: &[(usize, Scalar)],
+        serial_number_index: u16,
+        nullifiers: &mut S,
+        csprng: &mut C,
+    ) -> Result<BlindedIssuanceResponse, CredentialError>
+    where
+        C: CryptoRng + RngCore,
+        S: NullifierStore,
+    {
+        let issuer = self.get(&request.presentation.key_id()).ok_or(CredentialError::UnknownKey)?;
+
+        if issuer.system_parameters != self.current.system_parameters {
+            return Err(CredentialError::NoSystemParameters);
+        }
+
+        let serial_number = request.verify(issuer, updates, serial_number_index)?;
+
+        issue_refreshed(&self.current, request, &serial_number, nullifiers, csprng)
+    }
+}
+
+/// Issue the new credential for a verified refresh `request` with the
+/// `issuer`, recording the `serial_number` of the old credential in the
+/// `nullifiers`.
+///
+/// The serial number is only recorded once issuance has succeeded, such that
+/// a malformed request does not use up the old credential.
+fn issue_refreshed<C, S>(
+    issuer: &Issuer,
+    request: &RefreshRequest,
+    serial_number: &SerialNumber,
+    nullifiers: &mut S,
+    csprng: &mut C,
+) -> Result<BlindedIssuanceResponse, CredentialError>
+where
+    C: CryptoRng + RngCore,
+    S: NullifierStore,
+{
+    if nullifiers.contains(&serial_number.to_bytes())? {
+        return Err(CredentialError::SpentNullifier);
+    }
+
+    let response = issuer.issue_blinded(&request.request, csprng)?;
+
+    match nullifiers.insert(&serial_number.to_bytes())? {
+        true  => Ok(response),
+        false => Err(CredentialError::SpentNullifier),
+    }
+}
+
+impl IssuerKeyring {
+    /// Deserialise an [`IssuerKeyring`] from bytes.
+    ///
+    /// The format is the number of retired keys as two little-endian bytes,
+    /// the current [`Issuer`], each retired [`Issuer`], the number of entries
+    /// in the history as four little-endian bytes, and each of their
+    /// [`IssuerParameters`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<IssuerKeyring, CredentialError> {
+        if bytes.len() < 2 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut u16_bytes = [0u8; 2];
+        let mut u32_bytes = [0u8; 4];
+
+        u16_bytes.copy_from_slice(&bytes[..2]);
+
+        let number_of_retired = u16::from_le_bytes(u16_bytes) as usize;
+        let mut index: usize = 2;
+        let mut issuers: Vec<Issuer> = Vec::with_capacity(1 + number_of_retired);
+
+        for _ in 0..1 + number_of_retired {
+            if bytes.len() < index + 1 || bytes.len() < index + sizeof_issuer(bytes[index]) {
+                return Err(CredentialError::WrongNumberOfBytes);
+            }
+
+            let size = sizeof_issuer(bytes[index]);
+
+            issuers.push(Issuer::from_bytes(&bytes[index..index+size])?);
+            index += size;
+        }
+
+        if bytes.len() < index + 4 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        u32_bytes.copy_from_slice(&bytes[index..index+4]); index += 4;
+
+        let number_of_entries = u32::from_le_bytes(u32_bytes) as usize;
+
+        if bytes.len() - index != number_of_entries * SIZEOF_ISSUER_PARAMETERS {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let history = bytes[index..].chunks(SIZEOF_ISSUER_PARAMETERS)
+            .map(IssuerParameters::from_bytes)
+            .collect::<Result<Vec<IssuerParameters>, CredentialError>>()?;
+        let current = issuers.remove(0);
+
+        Ok(IssuerKeyring { current, retired: issuers, history })
+    }
+
+    /// Serialise this [`IssuerKeyring`] to a byte array.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+
+        bytes.extend((self.retired.len() as u16).to_le_bytes().iter());
+        bytes.extend(self.current.to_bytes());
+
+        for issuer in self.retired.iter() {
+            bytes.extend(issuer.to_bytes());
+        }
+
+        bytes.extend((self.history.len() as u32).to_le_bytes().iter());
+
+        for issuer_parameters in self.history.iter() {
+            bytes.extend(issuer_parameters.to_bytes());
+        }
+
+        bytes
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(IssuerKeyring, "A valid byte sequence representing an IssuerKeyring");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rand::thread_rng;
+
+    use crate::credential::AnonymousCredential;
+
+    #[test]
+    fn issuer_serialize_deserialize() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 2).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let serialized = issuer.to_bytes();
+        let deserialized = Issuer::from_bytes(&serialized).unwrap();
+
+        assert_eq!(deserialized.system_parameters, issuer.system_parameters);
+        assert_eq!(deserialized.issuer_parameters, issuer.issuer_parameters);
+        assert_eq!(deserialized.to_bytes(), serialized);
+    }
+
+    #[test]
+    fn keyring_rotation() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 2).unwrap();
+        let mut keyring = IssuerKeyring::new(Issuer::new(&system_parameters, &mut rng));
+        let attributes = vec![Attribute::SecretScalar(Scalar::random(&mut rng))];
+
+        let old_key_id = keyring.current().issuer_parameters.key_id();
+        let old_parameters = keyring.current().issuer_parameters.clone();
+        let response = keyring.current().issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &old_parameters,
+                                                                     &response, attributes).unwrap();
+
+        let new_key_id = keyring.rotate(&mut rng);
+
+        assert_ne!(new_key_id, old_key_id);
+        assert_eq!(keyring.history().len(), 2);
+
+        // Credentials issued before the rotation still verify with the retired key.
+        let proof = credential.show(&system_parameters, &old_parameters, None, &mut rng).unwrap();
+
+        assert_eq!(proof.key_id(), old_key_id);
+        assert_eq!(keyring.verify(&proof), Ok(old_key_id));
+        assert!(proof.verify(keyring.current()).is_err());
+
+        let deserialized = IssuerKeyring::from_bytes(&keyring.to_bytes()).unwrap();
+
+        assert_eq!(deserialized.verify(&proof), Ok(old_key_id));
+        assert_eq!(deserialized.history(), keyring.history());
+
+        // Once removed, the retired key is no longer used, but remains in the history.
+        assert!(keyring.remove(&new_key_id).is_none());
+        assert!(keyring.remove(&old_key_id).is_some());
+        assert_eq!(keyring.verify(&proof), Err(CredentialError::UnknownKey));
+        assert_eq!(keyring.history().len(), 2);
+    }
+}
@@ -50,11 +50,16 @@ mod macros;
 
 pub mod amacs;
 pub mod credential;
+pub mod elgamal;
 pub mod encoding;
 pub mod errors;
+pub mod expiry;
+pub mod groups;
 pub mod issuer;
 pub mod nizk;
 pub mod parameters;
 pub mod prelude;
+pub mod profile;
 pub mod symmetric;
+pub mod verifier;
 //pub mod user;
@@ -49,3 +49,29 @@ macro_rules! impl_serde_with_to_bytes_and_from_bytes {
         }
     }
 }
+
+macro_rules! try_deserialise {
+    ($name:expr, $bytes:expr) => {
+        match CompressedRistretto($bytes).decompress() {
+            Some(x)  => x,
+            None     => {
+                #[cfg(feature = "std")]
+                println!("Could not decode {:?} from bytes: {:?}", $name, $bytes);
+                return Err(CredentialError::PointDecompressionError);
+            },
+        }
+    }
+}
+
+macro_rules! try_deserialise_scalar {
+    ($name:expr, $bytes:expr) => {
+        match Scalar::from_canonical_bytes($bytes) {
+            Some(x)  => x,
+            None     => {
+                #[cfg(feature = "std")]
+                println!("Could not decode {:?} from bytes: {:?}", $name, $bytes);
+                return Err(CredentialError::ScalarFormatError);
+            },
+        }
+    }
+}
@@ -20,15 +20,28 @@ use core::ops::Index;
 use std::ops::Index;
 
 use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::ristretto::CompressedRistretto;
 use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::traits::IsIdentity;
 
 use rand_core::CryptoRng;
 use rand_core::RngCore;
 
+use serde::{self, Serialize, Deserialize, Serializer, Deserializer};
+use serde::de::Visitor;
+
+use sha2::Sha512;
+
+use subtle::Choice;
+use subtle::ConditionallySelectable;
+use subtle::ConstantTimeEq;
+
+use zkp::BatchableProof;
 use zkp::CompactProof;
 use zkp::Transcript;
-// XXX do we want/need batch proof verification?
-// use zkp::toolbox::batch_verifier::BatchVerifier;
+use zkp::toolbox::batch_verifier::BatchVerifier;
+use zkp::toolbox::batch_verifier::PointVar as BatchVerifierPointVar;
+use zkp::toolbox::batch_verifier::ScalarVar as BatchVerifierScalarVar;
 use zkp::toolbox::SchnorrCS;
 use zkp::toolbox::prover::Prover;
 use zkp::toolbox::prover::PointVar as ProverPointVar;
@@ -37,17 +50,117 @@ use zkp::toolbox::verifier::Verifier;
 use zkp::toolbox::verifier::PointVar as VerifierPointVar;
 use zkp::toolbox::verifier::ScalarVar as VerifierScalarVar;
 
+use zeroize::Zeroize;
+
+use crate::amacs::TAG_PUBLIC_POINT;
+use crate::amacs::TAG_PUBLIC_SCALAR;
+use crate::amacs::TAG_SECRET_POINT;
+use crate::amacs::TAG_SECRET_SCALAR;
+use crate::amacs::Amac;
 use crate::amacs::Attribute;
+use crate::amacs::BlindedAmac;
+use crate::amacs::BlindedAttribute;
 use crate::amacs::EncryptedAttribute;
 use crate::amacs::Messages;
 use crate::credential::AnonymousCredential;
+use crate::elgamal::Ciphertext as ElGamalCiphertext;
+use crate::elgamal::Keypair as ElGamalKeypair;
+use crate::elgamal::PublicKey as ElGamalPublicKey;
 use crate::errors::CredentialError;
+use crate::expiry::expiry_index;
+use crate::expiry::Epoch;
 use crate::issuer::Issuer;
 use crate::parameters::{IssuerParameters, SystemParameters};
+use crate::parameters::KeyId;
+use crate::parameters::SIZEOF_KEY_ID;
+use crate::profile::ProfileKeyCommitment;
 use crate::symmetric::Ciphertext;
 use crate::symmetric::Keypair as SymmetricKeypair;
 use crate::symmetric::Plaintext;
 use crate::symmetric::PublicKey as SymmetricPublicKey; // XXX rename this to something more sensical
+use crate::symmetric::SIZEOF_CIPHERTEXT;
+
+/// The version of the serialisation format of the proofs in this module,
+/// which is always the first byte of a serialised proof.
+pub(crate) const PROOF_FORMAT_VERSION: u8 = 10;
+
+/// Serialise a `CompactProof` as its challenge followed by its responses.
+fn compact_proof_to_bytes(proof: &CompactProof, v: &mut Vec<u8>) {
+    v.extend(proof.challenge.as_bytes().iter());
+
+    for response in proof.responses.iter() {
+        v.extend(response.as_bytes().iter());
+    }
+}
+
+/// Deserialise a `CompactProof` from its challenge followed by its responses.
+///
+/// The `bytes` must contain exactly the proof, and every scalar must be
+/// canonically encoded.
+fn compact_proof_from_bytes(bytes: &[u8]) -> Result<CompactProof, CredentialError> {
+    if bytes.len() < 32 || bytes.len() % 32 != 0 {
+        return Err(CredentialError::WrongNumberOfBytes);
+    }
+
+    let mut chunk = [0u8; 32];
+
+    chunk.copy_from_slice(&bytes[..32]);
+    let challenge: Scalar = try_deserialise_scalar!("challenge", chunk);
+
+    let mut responses: Vec<Scalar> = Vec::with_capacity(bytes.len() / 32 - 1);
+
+    for i in 1..bytes.len() / 32 {
+        chunk.copy_from_slice(&bytes[i*32..(i+1)*32]);
+        responses.push(try_deserialise_scalar!(format!("response_{}", i-1), chunk));
+    }
+
+    Ok(CompactProof { challenge, responses })
+}
+
+/// Serialise a `BatchableProof` as its commitments followed by its responses.
+fn batchable_proof_to_bytes(proof: &BatchableProof, v: &mut Vec<u8>) {
+    for commitment in proof.commitments.iter() {
+        v.extend(commitment.as_bytes().iter());
+    }
+
+    for response in proof.responses.iter() {
+        v.extend(response.as_bytes().iter());
+    }
+}
+
+/// Deserialise a `BatchableProof` from `number_of_commitments` commitments
+/// followed by its responses.
+///
+/// The `bytes` must contain exactly the proof, every commitment must be a
+/// valid point, and every scalar must be canonically encoded.
+fn batchable_proof_from_bytes(
+    bytes: &[u8],
+    number_of_commitments: usize,
+) -> Result<BatchableProof, CredentialError>
+{
+    if bytes.len() < 32 * number_of_commitments || bytes.len() % 32 != 0 {
+        return Err(CredentialError::WrongNumberOfBytes);
+    }
+
+    let mut chunk = [0u8; 32];
+    let mut commitments: Vec<CompressedRistretto> = Vec::with_capacity(number_of_commitments);
+    let mut responses: Vec<Scalar> = Vec::with_capacity(bytes.len() / 32 - number_of_commitments);
+
+    for i in 0..number_of_commitments {
+        chunk.copy_from_slice(&bytes[i*32..(i+1)*32]);
+        // Check that the commitment decompresses, but keep it compressed as
+        // the zkp crate expects.
+        let _: RistrettoPoint = try_deserialise!(format!("commitment_{}", i), chunk);
+        commitments.push(CompressedRistretto(chunk));
+    }
+
+    for i in number_of_commitments..bytes.len() / 32 {
+        chunk.copy_from_slice(&bytes[i*32..(i+1)*32]);
+        responses.push(try_deserialise_scalar!(format!("response_{}", i - number_of_commitments), chunk));
+    }
+
+    Ok(BatchableProof { commitments, responses })
+}
 
 pub struct ProofOfIssuance(CompactProof);
 
@@ -55,10 +168,11 @@ pub struct ProofOfIssuance(CompactProof);
 /// issuer's secret key, and that an [`AnonymousCredential`] was computed
 /// correctly w.r.t. the pubilshed system and issuer parameters.
 impl ProofOfIssuance {
-    /// Create a [`ProofOfIssuance`].
-    pub fn prove(
+    /// Create a [`ProofOfIssuance`] for an `amac` over some `attributes`.
+    pub(crate) fn prove(
         issuer: &Issuer,
-        credential: &AnonymousCredential,
+        amac: &Amac,
+        attributes: &Vec<Attribute>,
     ) -> ProofOfIssuance
     {
         use zkp::toolbox::prover::PointVar;
@@ -86,8 +200,6 @@ impl ProofOfIssuance {
         // without multiplying by any scalar.
         let one = prover.allocate_scalar(b"1", Scalar::one());
 
-        let t = prover.allocate_scalar(b"t", credential.amac.t);
-
         // Commit to the values and names of the Camenisch-Stadler publics.
         let (neg_G_V, _)   = prover.allocate_point(b"-G_V",     -issuer.system_parameters.G_V);
         let (G, _)         = prover.allocate_point(b"G",         issuer.system_parameters.G);
@@ -108,19 +220,25 @@ impl ProofOfIssuance {
 
         let (C_W, _) = prover.allocate_point(b"C_W", issuer.issuer_parameters.C_W);
         let (I, _)   = prover.allocate_point(b"I",   issuer.issuer_parameters.I);
-        let (U, _)   = prover.allocate_point(b"U", credential.amac.U);
-        let (V, _)   = prover.allocate_point(b"V", credential.amac.V);
+        let (U, _)   = prover.allocate_point(b"U", amac.U);
+        let (tU, _)  = prover.allocate_point(b"tU", amac.U * amac.t);
+        let (V, _)   = prover.allocate_point(b"V", amac.V);
 
-        let mut M: Vec<PointVar> = Vec::with_capacity(issuer.system_parameters.NUMBER_OF_ATTRIBUTES as usize);
+        let mut M: Vec<(usize, PointVar)> = Vec::with_capacity(issuer.system_parameters.NUMBER_OF_ATTRIBUTES as usize);
 
-        let messages: Messages = Messages::from_attributes(&credential.attributes, &issuer.system_parameters);
+        let messages: Messages = Messages::from_attributes(attributes, &issuer.system_parameters);
 
         for (i, M_i) in messages.0.iter().enumerate() {
+            // Messages which are the identity, e.g. for scalar attributes equal
+            // to zero, contribute nothing to V and are rejected by the verifier.
+            if M_i.is_identity() {
+                continue;
+            }
             // XXX fix the zkp crate to take Strings
             //let (M_x, _) = prover.allocate_point(format!("M_{}", i), M_i);
             let (M_x, _) = prover.allocate_point(b"M", *M_i);
 
-            M.push(M_x);
+            M.push((i, M_x));
         }
 
         // Constraint #1: C_W = G_w * w + G_w' * w'
@@ -136,14 +254,13 @@ impl ProofOfIssuance {
 
         prover.constrain(I, rhs);
 
-        // Constraint #3: V = G_w * w + U * x_0 + U * x_1 + U * t + \sigma{i=1}{n} M_i * y_i
-        let mut rhs: Vec<(ScalarVar, PointVar)> = Vec::with_capacity(4 + issuer.system_parameters.NUMBER_OF_ATTRIBUTES as usize);
+        // Constraint #3: V = G_w * w + U * x_0 + tU * x_1 + \sigma{i=1}{n} M_i * y_i
+        let mut rhs: Vec<(ScalarVar, PointVar)> = Vec::with_capacity(3 + issuer.system_parameters.NUMBER_OF_ATTRIBUTES as usize);
 
         rhs.push((w, G_w));
         rhs.push((x_0, U));
-        rhs.push((x_1, U));
-        rhs.push((t, U));
-        rhs.extend(y.iter().copied().zip(M.iter().copied()));
+        rhs.push((x_1, tU));
+        rhs.extend(M.iter().map(|(i, M_i)| (y[*i], *M_i)));
 
         prover.constrain(V, rhs);
 
@@ -179,7 +296,6 @@ impl ProofOfIssuance {
         }
 
         let one = verifier.allocate_scalar(b"1");
-        let t   = verifier.allocate_scalar(b"t");
 
         // Commit to the values and names of the Camenisch-Stadler publics.
         let neg_G_V   = verifier.allocate_point(b"-G_V",    (-system_parameters.G_V).compress())?;
@@ -200,18 +316,22 @@ impl ProofOfIssuance {
         let C_W = verifier.allocate_point(b"C_W", issuer_parameters.C_W.compress())?;
         let I   = verifier.allocate_point(b"I",   issuer_parameters.I.compress())?;
         let U   = verifier.allocate_point(b"U", credential.amac.U.compress())?;
+        let tU  = verifier.allocate_point(b"tU", (credential.amac.U * credential.amac.t).compress())?;
         let V   = verifier.allocate_point(b"V", credential.amac.V.compress())?;
 
-        let mut M: Vec<PointVar> = Vec::with_capacity(system_parameters.NUMBER_OF_ATTRIBUTES as usize);
+        let mut M: Vec<(usize, PointVar)> = Vec::with_capacity(system_parameters.NUMBER_OF_ATTRIBUTES as usize);
 
         let messages: Messages = Messages::from_attributes(&credential.attributes, system_parameters);
 
         for (i, M_i) in messages.0.iter().enumerate() {
+            if M_i.is_identity() {
+                continue;
+            }
             // XXX fix the zkp crate to take Strings
             //let (M_x, _) = verifier.allocate_point(format!("M_{}", i), M_i);
             let M_x = verifier.allocate_point(b"M", M_i.compress())?;
 
-            M.push(M_x);
+            M.push((i, M_x));
         }
 
         // Constraint #1: C_W = G_w * w + G_w' * w'
@@ -227,14 +347,13 @@ impl ProofOfIssuance {
 
         verifier.constrain(I, rhs);
 
-        // Constraint #3: V = G_w * w + U * x_0 + U * x_1 + U * t + \sigma{i=1}{n} M_i * y_i
-        let mut rhs: Vec<(ScalarVar, PointVar)> = Vec::with_capacity(4 + system_parameters.NUMBER_OF_ATTRIBUTES as usize);
+        // Constraint #3: V = G_w * w + U * x_0 + tU * x_1 + \sigma{i=1}{n} M_i * y_i
+        let mut rhs: Vec<(ScalarVar, PointVar)> = Vec::with_capacity(3 + system_parameters.NUMBER_OF_ATTRIBUTES as usize);
 
         rhs.push((w, G_w));
         rhs.push((x_0, U));
-        rhs.push((x_1, U));
-        rhs.push((t, U));
-        rhs.extend(y.iter().copied().zip(M.iter().copied()));
+        rhs.push((x_1, tU));
+        rhs.extend(M.iter().map(|(i, M_i)| (y[*i], *M_i)));
 
         verifier.constrain(V, rhs);
 
@@ -242,692 +361,5768 @@ impl ProofOfIssuance {
     }
 }
 
-/// A proof-of-knowledge that a ciphertext encrypts a plaintext
-/// committed to in a list of commitments.
-pub struct ProofOfEncryption {
-    proof: CompactProof,
-    public_key: SymmetricPublicKey,
-    ciphertext: Ciphertext,
-    index: u16,
-    C_y_1: RistrettoPoint,
-    C_y_2: RistrettoPoint,
-    C_y_3: RistrettoPoint,
-    C_y_2_prime: RistrettoPoint,
+impl ProofOfIssuance {
+    /// Deserialise a [`ProofOfIssuance`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ProofOfIssuance, CredentialError> {
+        if bytes.len() < 1 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+        if bytes[0] != PROOF_FORMAT_VERSION {
+            return Err(CredentialError::UnsupportedVersion);
+        }
+
+        Ok(ProofOfIssuance(compact_proof_from_bytes(&bytes[1..])?))
+    }
+
+    /// Serialise this [`ProofOfIssuance`] to a byte array.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::with_capacity(1 + 32 * (1 + self.0.responses.len()));
+
+        v.push(PROOF_FORMAT_VERSION);
+        compact_proof_to_bytes(&self.0, &mut v);
+        v
+    }
 }
 
-impl ProofOfEncryption {
-    /// Prove in zero-knowledge that a ciphertext is a verifiable encryption of
-    /// a plaintext w.r.t. a valid commitment to a secret symmetric key.
+impl_serde_with_to_bytes_and_from_bytes!(ProofOfIssuance, "A valid byte sequence representing a ProofOfIssuance");
+
+/// A non-interactive zero-knowledge proof demonstrating that the hidden scalar
+/// attributes in a [`CredentialRequest`] are well-formed ElGamal encryptions
+/// of \\( G_m_i * m_i \\) to a public key for which the user knows the secret
+/// key, and that the user knows each \\( m_i \\).
+///
+/// Any [`BlindedAttribute::EncryptedPoint`]s are not covered by this proof,
+/// and must be proven to be well-formed separately, e.g. by a
+/// [`ProofOfProfileKeyCommitment`].
+pub struct ProofOfCredentialRequest(CompactProof);
+
+impl ProofOfCredentialRequest {
+    /// Create a [`ProofOfCredentialRequest`].
     ///
     /// # Inputs
     ///
     /// * The [`SystemParameters`] for this anonymous credential instantiation,
-    /// * A `plaintext` of up to thirty bytes.
-    /// * The `index` of the attribute to be encrypted.
-    /// * A symmetric "keypair",
-    /// * The nonce, `z`, must be reused from the outer-lying [`ProofOfValidCredential`].
+    /// * The user's ElGamal `keypair`,
+    /// * The `blinded_attributes` of the request, and
+    /// * The `hidden_scalars`, as `(index, m_i, r_i)` tuples, where `r_i` is
+    ///   the nonce used for the encryption of the `i`th attribute.
+    pub(crate) fn prove(
+        system_parameters: &SystemParameters,
+        keypair: &ElGamalKeypair,
+        blinded_attributes: &Vec<BlindedAttribute>,
+        hidden_scalars: &Vec<(usize, Scalar, Scalar)>,
+    ) -> ProofOfCredentialRequest
+    {
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+        let mut prover = Prover::new(b"2019/1416 credential request proof", &mut transcript);
+
+        // Commit to the secret key and the publics it is formed from.
+        let d      = prover.allocate_scalar(b"d", keypair.secret.d);
+        let (G, _) = prover.allocate_point(b"G", system_parameters.G);
+        let (D, _) = prover.allocate_point(b"D", keypair.public.D);
+
+        // Constraint #1: Prove knowledge of the ElGamal secret key.
+        //                D = G * d
+        prover.constrain(D, vec![(d, G)]);
+
+        for (i, m_i, r_i) in hidden_scalars.iter() {
+            let E_i = match blinded_attributes[*i] {
+                BlindedAttribute::SecretScalar(E_i) => E_i,
+                _ => continue,
+            };
+
+            // XXX fix the zkp crate to take Strings
+            let m        = prover.allocate_scalar(b"m", *m_i);
+            let r        = prover.allocate_scalar(b"r", *r_i);
+            let (G_m, _) = prover.allocate_point(b"G_m", system_parameters.G_m[*i]);
+            let (E_1, _) = prover.allocate_point(b"E_1", E_i.C1);
+            let (E_2, _) = prover.allocate_point(b"E_2", E_i.C2);
+
+            // Constraint #2: The encryption nonce is known.
+            //                E_i1 = G * r_i
+            prover.constrain(E_1, vec![(r, G)]);
+
+            // Constraint #3: The ciphertext encrypts G_m_i * m_i to D.
+            //                E_i2 = G_m_i * m_i + D * r_i
+            prover.constrain(E_2, vec![(m, G_m), (r, D)]);
+        }
+
+        ProofOfCredentialRequest(prover.prove_compact())
+    }
+
+    /// Verify a [`ProofOfCredentialRequest`].
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    /// * The user's ElGamal `public_key`, and
+    /// * The `blinded_attributes` of the request.
     ///
     /// # Returns
     ///
     /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`].
-    pub fn prove(
+    pub fn verify(
+        &self,
         system_parameters: &SystemParameters,
-        plaintext: &Plaintext,
-        index: u16,
-        keypair: &SymmetricKeypair,
-        z: &Scalar,
-    ) -> ProofOfEncryption
+        public_key: &ElGamalPublicKey,
+        blinded_attributes: &Vec<BlindedAttribute>,
+    ) -> Result<(), CredentialError>
     {
-        // Encrypt the plaintext.
-        let ciphertext_ = keypair.encrypt(&plaintext);
+        if blinded_attributes.len() > system_parameters.NUMBER_OF_ATTRIBUTES as usize {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
 
-        // Compute the vector C of commitments to the plaintext.
-        let C_y_1_ = (system_parameters.G_y[0] * z) + plaintext.M1;
-        let C_y_2_ = (system_parameters.G_y[1] * z) + plaintext.M2;
-        let C_y_3_ = (system_parameters.G_y[2] * z) + (system_parameters.G_m[index as usize] * plaintext.m3);
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+        let mut verifier = Verifier::new(b"2019/1416 credential request proof", &mut transcript);
 
-        // Compute C_y_2' = C_y_2 * a1.
-        let C_y_2_prime_ = C_y_2_ * keypair.secret.a1;
+        let d = verifier.allocate_scalar(b"d");
+        let G = verifier.allocate_point(b"G", system_parameters.G.compress())?;
+        let D = verifier.allocate_point(b"D", public_key.D.compress())?;
 
-        // Calculate z1 = -z(a0 + a1 * m3).
-        let z1_ = -z * (keypair.secret.a0 + keypair.secret.a1 * plaintext.m3);
+        // Constraint #1: Prove knowledge of the ElGamal secret key.
+        //                D = G * d
+        verifier.constrain(D, vec![(d, G)]);
 
-        // Construct a protocol transcript and prover.
-        let mut transcript = Transcript::new(b"2019/1416 anonymous credentials");
-        let mut prover = Prover::new(b"2019/1416 proof of encryption", &mut transcript);
+        for (i, attribute) in blinded_attributes.iter().enumerate() {
+            let E_i = match attribute {
+                BlindedAttribute::SecretScalar(E_i) => E_i,
+                _ => continue,
+            };
 
-        // Commit the names of the Camenisch-Stadler secrets to the protocol transcript.
-        let a  = prover.allocate_scalar(b"a",  keypair.secret.a);
-        let a0 = prover.allocate_scalar(b"a0", keypair.secret.a0);
-        let a1 = prover.allocate_scalar(b"a1", keypair.secret.a1);
-        let m3 = prover.allocate_scalar(b"m3", plaintext.m3);
-        let z  = prover.allocate_scalar(b"z",  *z);
-        let z1 = prover.allocate_scalar(b"z1", z1_);
+            // XXX fix the zkp crate to take Strings
+            let m   = verifier.allocate_scalar(b"m");
+            let r   = verifier.allocate_scalar(b"r");
+            let G_m = verifier.allocate_point(b"G_m", system_parameters.G_m[i].compress())?;
+            let E_1 = verifier.allocate_point(b"E_1", E_i.C1.compress())?;
+            let E_2 = verifier.allocate_point(b"E_2", E_i.C2.compress())?;
+
+            // Constraint #2: The encryption nonce is known.
+            //                E_i1 = G * r_i
+            verifier.constrain(E_1, vec![(r, G)]);
+
+            // Constraint #3: The ciphertext encrypts G_m_i * m_i to D.
+            //                E_i2 = G_m_i * m_i + D * r_i
+            verifier.constrain(E_2, vec![(m, G_m), (r, D)]);
+        }
 
-        // Commit to the values and names of the Camenisch-Stadler publics.
-        let (pk, _)             = prover.allocate_point(b"pk",       keypair.public.pk);
-        let (G_a, _)            = prover.allocate_point(b"G_a",      system_parameters.G_a);
-        let (G_a_0, _)          = prover.allocate_point(b"G_a_0",    system_parameters.G_a0);
-        let (G_a_1, _)          = prover.allocate_point(b"G_a_1",    system_parameters.G_a1);
-        let (G_y_1, _)          = prover.allocate_point(b"G_y_1",    system_parameters.G_y[0]);
-        let (G_y_2, _)          = prover.allocate_point(b"G_y_2",    system_parameters.G_y[1]);
-        let (G_y_3, _)          = prover.allocate_point(b"G_y_3",    system_parameters.G_y[2]);
-        let (G_m_3, _)          = prover.allocate_point(b"G_m_3",    system_parameters.G_m[index as usize]);
-        let (C_y_2, _)          = prover.allocate_point(b"C_y_2",    C_y_2_);
-        let (C_y_3, _)          = prover.allocate_point(b"C_y_3",    C_y_3_);
-        let (C_y_2_prime, _)    = prover.allocate_point(b"C_y_2'",   C_y_2_prime_);
-        let (C_y_1_minus_E2, _) = prover.allocate_point(b"C_y_1-E2", C_y_1_ - ciphertext_.E2);
-        let (E1, _)             = prover.allocate_point(b"E1",       ciphertext_.E1);
-        let (minus_E1, _)       = prover.allocate_point(b"-E1",      -ciphertext_.E1);
+        verifier.verify_compact(&self.0).or_else(|_| Err(CredentialError::VerificationFailure))
+    }
+}
 
-        // Constraint #1: Prove knowledge of the secret portions of the symmetric key.
-        //                pk = G_a * a + G_a0 * a0 + G_a1 * a1
-        prover.constrain(pk, vec![(a, G_a), (a0, G_a_0), (a1, G_a_1)]);
+/// A non-interactive zero-knowledge proof that the two
+/// [`BlindedAttribute::EncryptedPoint`]s of a profile key credential request
+/// encrypt the same halves of a profile key as a [`ProfileKeyCommitment`].
+///
+/// Each half \\( M_j \\) is committed to as
+/// \\( (J_j1, J_j2) = (G * s_j, M_j + H * s_j) \\) and encrypted as
+/// \\( (E_j1, E_j2) = (G * r_j, M_j + D * r_j) \\).  Since both are perfectly
+/// binding, proving \\( J_j2 - E_j2 = H * s_j - D * r_j \\) shows that they
+/// contain the same \\( M_j \\), without revealing it.
+pub(crate) struct ProofOfProfileKeyCommitment(CompactProof);
+
+impl ProofOfProfileKeyCommitment {
+    /// Create a [`ProofOfProfileKeyCommitment`].
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    /// * The user's ElGamal `public_key`,
+    /// * The `commitment` to the profile key,
+    /// * The `ciphertexts` of the halves of the profile key, and
+    /// * The `openings`, as `(s_j, r_j)` tuples, of the commitment and ciphertexts.
+    pub(crate) fn prove(
+        system_parameters: &SystemParameters,
+        public_key: &ElGamalPublicKey,
+        commitment: &ProfileKeyCommitment,
+        ciphertexts: &[ElGamalCiphertext; 2],
+        openings: &[(Scalar, Scalar); 2],
+    ) -> ProofOfProfileKeyCommitment
+    {
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+        let mut prover = Prover::new(b"2019/1416 profile key commitment proof", &mut transcript);
 
-        // Constraint #2: The plaintext of this encryption is the message.
-        //                C_y_1 - E2 = G_y_1 * z - E_1 * a
-        prover.constrain(C_y_1_minus_E2, vec![(z, G_y_1), (a, minus_E1)]);
+        let (G, _)       = prover.allocate_point(b"G",  system_parameters.G);
+        let (H, _)       = prover.allocate_point(b"H",  ProfileKeyCommitment::generator());
+        let (minus_D, _) = prover.allocate_point(b"-D", -public_key.D);
 
-        // Constraint #3: The encryption C_y_2' of the commitment C_y_2 is formed correctly w.r.t. the secret key.
-        //                C_y_2' = C_y_2 * a1
-        prover.constrain(C_y_2_prime, vec![(a1, C_y_2)]);
+        for j in 0..2 {
+            let (J_1, J_2) = commitment.J[j];
+            let (s_j, r_j) = openings[j];
 
-        // Constraint #4: The encryption E1 is well formed.
-        //                  E1 = C_y_2            * a0 + C_y_2'                * m3    + G_y_2 * z1
-        // M2 * (a0 + a1 * m3) = (M2 + G_y_2 * z) * a0 + (M2 + G_y_2 * z) * a1 * m3    + G_y_2 * -z (a0 + a1 * m3)
-        // M2(a0) + M2(a1)(m3) = M2(a0) + G_y_2(z)(a0) + M2(a1)(m3) + G_y_2(z)(a1)(m3) + G_y_2(-z)(a0) + G_y_2(-z)(a1)(m3)
-        // M2(a0) + M2(a1)(m3) = M2(a0)                + M2(a1)(m3)
-        prover.constrain(E1, vec![(a0, C_y_2), (m3, C_y_2_prime), (z1, G_y_2)]);
+            let s        = prover.allocate_scalar(b"s", s_j);
+            let r        = prover.allocate_scalar(b"r", r_j);
+            let (J, _)   = prover.allocate_point(b"J_1", J_1);
+            let (E_1, _) = prover.allocate_point(b"E_1", ciphertexts[j].C1);
+            let (K, _)   = prover.allocate_point(b"J_2-E_2", J_2 - ciphertexts[j].C2);
 
-        // Constraint #5: The commitment to the hash m3 is a correct hash of the message commited to.
-        prover.constrain(C_y_3, vec![(z, G_y_3), (m3, G_m_3)]);
+            // Constraint #1: The commitment's nonce is known.
+            //                J_j1 = G * s_j
+            prover.constrain(J, vec![(s, G)]);
 
-        let proof = prover.prove_compact();
+            // Constraint #2: The encryption nonce is known.
+            //                E_j1 = G * r_j
+            prover.constrain(E_1, vec![(r, G)]);
 
-        ProofOfEncryption {
-            proof: proof,
-            public_key: keypair.public,
-            ciphertext: ciphertext_,
-            index: index,
-            C_y_1: C_y_1_,
-            C_y_2: C_y_2_,
-            C_y_3: C_y_3_,
-            C_y_2_prime: C_y_2_prime_,
+            // Constraint #3: The commitment and the ciphertext contain the same M_j.
+            //                J_j2 - E_j2 = H * s_j - D * r_j
+            prover.constrain(K, vec![(s, H), (r, minus_D)]);
         }
+
+        ProofOfProfileKeyCommitment(prover.prove_compact())
     }
 
-    /// Verify that this [`ProofOfEncryption`] proves that a `ciphertext` is a
-    /// correct encryption of a verifiably-encrypted plaintext.
-    ///
-    /// # Inputs
-    ///
-    /// * The [`SystemParameters`] for this anonymous credential instantiation,
-    /// * The [`Ciphertext`] in question,
-    /// * The "public key" for the symmetric verifiable encryption scheme.
+    /// Verify a [`ProofOfProfileKeyCommitment`].
     ///
     /// # Returns
     ///
     /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`].
-    pub fn verify(
+    pub(crate) fn verify(
         &self,
         system_parameters: &SystemParameters,
+        public_key: &ElGamalPublicKey,
+        commitment: &ProfileKeyCommitment,
+        ciphertexts: &[ElGamalCiphertext; 2],
     ) -> Result<(), CredentialError>
     {
-        // Construct a protocol transcript and verifier.
-        let mut transcript = Transcript::new(b"2019/1416 anonymous credentials");
-        let mut verifier = Verifier::new(b"2019/1416 proof of encryption", &mut transcript);
-
-        // Commit the names of the Camenisch-Stadler secrets to the protocol transcript.
-        let a  = verifier.allocate_scalar(b"a");
-        let a0 = verifier.allocate_scalar(b"a0");
-        let a1 = verifier.allocate_scalar(b"a1");
-        let m3 = verifier.allocate_scalar(b"m3");
-        let z  = verifier.allocate_scalar(b"z");
-        let z1 = verifier.allocate_scalar(b"z1");
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+        let mut verifier = Verifier::new(b"2019/1416 profile key commitment proof", &mut transcript);
 
-        // Commit to the values and names of the Camenisch-Stadler publics.
-        let pk             = verifier.allocate_point(b"pk",       self.public_key.pk.compress())?;
-        let G_a            = verifier.allocate_point(b"G_a",      system_parameters.G_a.compress())?;
-        let G_a_0          = verifier.allocate_point(b"G_a_0",    system_parameters.G_a0.compress())?;
-        let G_a_1          = verifier.allocate_point(b"G_a_1",    system_parameters.G_a1.compress())?;
-        let G_y_1          = verifier.allocate_point(b"G_y_1",    system_parameters.G_y[0].compress())?;
-        let G_y_2          = verifier.allocate_point(b"G_y_2",    system_parameters.G_y[1].compress())?;
-        let G_y_3          = verifier.allocate_point(b"G_y_3",    system_parameters.G_y[2].compress())?;
-        let G_m_3          = verifier.allocate_point(b"G_m_3",    system_parameters.G_m[self.index as usize].compress())?;
-        let C_y_2          = verifier.allocate_point(b"C_y_2",    self.C_y_2.compress())?;
-        let C_y_3          = verifier.allocate_point(b"C_y_3",    self.C_y_3.compress())?;
-        let C_y_2_prime    = verifier.allocate_point(b"C_y_2'",   self.C_y_2_prime.compress())?;
-        let C_y_1_minus_E2 = verifier.allocate_point(b"C_y_1-E2", (self.C_y_1 - self.ciphertext.E2).compress())?;
-        let E1             = verifier.allocate_point(b"E1",       self.ciphertext.E1.compress())?;
-        let minus_E1       = verifier.allocate_point(b"-E1",      (-self.ciphertext.E1).compress())?;
+        let G       = verifier.allocate_point(b"G",  system_parameters.G.compress())?;
+        let H       = verifier.allocate_point(b"H",  ProfileKeyCommitment::generator().compress())?;
+        let minus_D = verifier.allocate_point(b"-D", (-public_key.D).compress())?;
 
-        // Constraint #1: Prove knowledge of the secret portions of the symmetric key.
-        //                pk = G_a * a + G_a0 * a0 + G_a1 * a1
-        verifier.constrain(pk, vec![(a, G_a), (a0, G_a_0), (a1, G_a_1)]);
+        for j in 0..2 {
+            let (J_1, J_2) = commitment.J[j];
 
-        // Constraint #2: The plaintext of this encryption is the message.
-        //                C_y_1 - E2 = G_y_1 * z - E_1 * a
-        verifier.constrain(C_y_1_minus_E2, vec![(z, G_y_1), (a, minus_E1)]);
+            let s   = verifier.allocate_scalar(b"s");
+            let r   = verifier.allocate_scalar(b"r");
+            let J   = verifier.allocate_point(b"J_1", J_1.compress())?;
+            let E_1 = verifier.allocate_point(b"E_1", ciphertexts[j].C1.compress())?;
+            let K   = verifier.allocate_point(b"J_2-E_2", (J_2 - ciphertexts[j].C2).compress())?;
 
-        // Constraint #3: The encryption C_y_2' of the commitment C_y_2 is formed correctly w.r.t. the secret key.
-        //                C_y_2' = C_y_2 * a1
-        verifier.constrain(C_y_2_prime, vec![(a1, C_y_2)]);
+            // Constraint #1: J_j1 = G * s_j
+            verifier.constrain(J, vec![(s, G)]);
 
-        // Constraint #4: The encryption E1 is well formed.
-        //                  E1 = C_y_2            * a0 + C_y_2'                * m3    + G_y_2 * z1
-        // M2 * (a0 + a1 * m3) = (M2 + G_y_2 * z) * a0 + (M2 + G_y_2 * z) * a1 * m3    + G_y_2 * -z (a0 + a1 * m3)
-        // M2(a0) + M2(a1)(m3) = M2(a0) + G_y_2(z)(a0) + M2(a1)(m3) + G_y_2(z)(a1)(m3) + G_y_2(-z)(a0) + G_y_2(-z)(a1)(m3)
-        // M2(a0) + M2(a1)(m3) = M2(a0)                + M2(a1)(m3)
-        verifier.constrain(E1, vec![(a0, C_y_2), (m3, C_y_2_prime), (z1, G_y_2)]);
+            // Constraint #2: E_j1 = G * r_j
+            verifier.constrain(E_1, vec![(r, G)]);
 
-        // Constraint #5: The commitment to the hash m3 is a correct hash of the message commited to.
-        verifier.constrain(C_y_3, vec![(z, G_y_3), (m3, G_m_3)]);
+            // Constraint #3: J_j2 - E_j2 = H * s_j - D * r_j
+            verifier.constrain(K, vec![(s, H), (r, minus_D)]);
+        }
 
-        verifier.verify_compact(&self.proof).or_else(|_| Err(CredentialError::VerificationFailure))
+        verifier.verify_compact(&self.0).or_else(|_| Err(CredentialError::VerificationFailure))
     }
 }
 
-/// An incredibly shitty and inelegant hashmap-like structure to store/"index"
-/// hidden scalar attributes during construction of a [`ProofOfValidCredential`].
-struct ProverHiddenScalars(Vec<(usize, ProverScalarVar)>);
+/// A non-interactive zero-knowledge proof demonstrating knowledge of the
+/// issuer's secret key, and that a [`BlindedAmac`] was computed correctly
+/// w.r.t. the published system and issuer parameters and the hidden attributes
+/// of a [`CredentialRequest`].
+pub struct ProofOfBlindedIssuance(CompactProof);
 
-/// An incredibly shitty and inelegant hashmap-like structure to store/"index"
-/// corresponding hidden scalar basepoints during construction of a
-/// [`ProofOfValidCredential`].
-struct ProverHiddenScalarBasepoints(Vec<(usize, ProverPointVar)>);
+impl ProofOfBlindedIssuance {
+    /// Create a [`ProofOfBlindedIssuance`].
+    ///
+    /// # Inputs
+    ///
+    /// * The `issuer`,
+    /// * The user's ElGamal `public_key`,
+    /// * The `blinded_attributes` of the request,
+    /// * The `amac` which was computed over them, and
+    /// * The `nonce` used to encrypt the `amac`.
+    pub(crate) fn prove(
+        issuer: &Issuer,
+        public_key: &ElGamalPublicKey,
+        blinded_attributes: &Vec<BlindedAttribute>,
+        amac: &BlindedAmac,
+        nonce: &Scalar,
+    ) -> ProofOfBlindedIssuance
+    {
+        use zkp::toolbox::prover::PointVar;
+        use zkp::toolbox::prover::ScalarVar;
 
-/// An incredibly shitty and inelegant hashmap-like structure to store/"index"
-/// hidden scalar attributes during verification of a [`ProofOfValidCredential`].
-struct VerifierHiddenScalars(Vec<(usize, VerifierScalarVar)>);
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+        let mut prover = Prover::new(b"2019/1416 blinded issuance proof", &mut transcript);
 
-/// An incredibly shitty and inelegant hashmap-like structure to store/"index"
-/// corresponding hidden scalar basepoints during verification of a
-/// [`ProofOfValidCredential`].
-struct VerifierHiddenScalarBasepoints(Vec<(usize, VerifierPointVar)>);
+        // Commit the names of the Camenisch-Stadler secrets to the protocol transcript.
+        let w       = prover.allocate_scalar(b"w",   issuer.amacs_key.w);
+        let w_prime = prover.allocate_scalar(b"w'",  issuer.amacs_key.w_prime);
+        let x_0     = prover.allocate_scalar(b"x_0", issuer.amacs_key.x_0);
+        let x_1     = prover.allocate_scalar(b"x_1", issuer.amacs_key.x_1);
 
-macro_rules! construct_hidden_scalar_variant {
-    ($scalar_type: ty, $basepoint_type: ty, $scalar_var: ty, $basepoint_var: ty) => {
-        impl Index<usize> for $scalar_type {
-            type Output = $scalar_var;
+        let mut y: Vec<ScalarVar> = Vec::with_capacity(issuer.system_parameters.NUMBER_OF_ATTRIBUTES as usize);
 
-            fn index(&self, i: usize) -> &Self::Output {
-                for (index, scalar) in self.0.iter() {
-                    if *index == i {
-                        return scalar;
-                    }
-                }
-                panic!()
-            }
+        for y_i in issuer.amacs_key.y.iter() {
+            // XXX fix the zkp crate to take Strings
+            y.push(prover.allocate_scalar(b"y", *y_i));
         }
 
-        impl $scalar_type {
-            pub(crate) fn push(&mut self, item: (usize, $scalar_var)) {
-                self.0.push(item);
-            }
-        }
+        let one = prover.allocate_scalar(b"1", Scalar::one());
+        let r   = prover.allocate_scalar(b"r", *nonce);
 
-        impl Index<usize> for $basepoint_type {
-            type Output = $basepoint_var;
+        // Commit to the values and names of the Camenisch-Stadler publics.
+        let (neg_G_V, _)   = prover.allocate_point(b"-G_V",     -issuer.system_parameters.G_V);
+        let (G, _)         = prover.allocate_point(b"G",         issuer.system_parameters.G);
+        let (G_w, _)       = prover.allocate_point(b"G_w",       issuer.system_parameters.G_w);
+        let (G_w_prime, _) = prover.allocate_point(b"G_w_prime", issuer.system_parameters.G_w_prime);
+        let (G_x_0, _)     = prover.allocate_point(b"G_x_0",     issuer.system_parameters.G_x_0);
+        let (G_x_1, _)     = prover.allocate_point(b"G_x_1",     issuer.system_parameters.G_x_1);
 
-            fn index(&self, i: usize) -> &Self::Output {
-                for (index, scalar) in self.0.iter() {
-                    if *index == i {
-                        return scalar;
-                    }
-                }
-                panic!()
+        let mut G_y: Vec<PointVar> = Vec::with_capacity(issuer.system_parameters.NUMBER_OF_ATTRIBUTES as usize);
+
+        for G_y_i in issuer.system_parameters.G_y.iter() {
+            // XXX fix the zkp crate to take Strings
+            let (G_y_x, _) = prover.allocate_point(b"G_y", *G_y_i);
+
+            G_y.push(G_y_x);
+        }
+
+        let (C_W, _)  = prover.allocate_point(b"C_W",  issuer.issuer_parameters.C_W);
+        let (I, _)    = prover.allocate_point(b"I",    issuer.issuer_parameters.I);
+        let (U, _)    = prover.allocate_point(b"U",    amac.U);
+        let (tU, _)   = prover.allocate_point(b"tU",   amac.U * amac.t);
+        let (D, _)    = prover.allocate_point(b"D",    public_key.D);
+        let (E_V1, _) = prover.allocate_point(b"E_V1", amac.V.C1);
+        let (E_V2, _) = prover.allocate_point(b"E_V2", amac.V.C2);
+
+        let messages: Messages = Messages::from_blinded_attributes(blinded_attributes, &issuer.system_parameters);
+
+        // Constraint #3: E_V1 = G * r + \sigma_{i \in \mathcal{H}}{E_i1 * y_i}
+        let mut rhs_1: Vec<(ScalarVar, PointVar)> = Vec::with_capacity(1 + blinded_attributes.len());
+
+        // Constraint #4: E_V2 = G_w * w + U * x_0 + tU * x_1 + D * r +
+        //                       \sigma_{i \notin \mathcal{H}}{M_i * y_i} +
+        //                       \sigma_{i \in \mathcal{H}}{E_i2 * y_i}
+        let mut rhs_2: Vec<(ScalarVar, PointVar)> = Vec::with_capacity(4 + blinded_attributes.len());
+
+        rhs_1.push((r, G));
+        rhs_2.push((w, G_w));
+        rhs_2.push((x_0, U));
+        rhs_2.push((x_1, tU));
+        rhs_2.push((r, D));
+
+        for (i, attribute) in blinded_attributes.iter().enumerate() {
+            match attribute {
+                BlindedAttribute::SecretScalar(E_i) | BlindedAttribute::EncryptedPoint(E_i) => {
+                    // XXX fix the zkp crate to take Strings
+                    let (E_1, _) = prover.allocate_point(b"E_1", E_i.C1);
+                    let (E_2, _) = prover.allocate_point(b"E_2", E_i.C2);
+
+                    rhs_1.push((y[i], E_1));
+                    rhs_2.push((y[i], E_2));
+                },
+                _ => {
+                    // XXX fix the zkp crate to take Strings
+                    let (M_i, _) = prover.allocate_point(b"M", messages.0[i]);
+
+                    rhs_2.push((y[i], M_i));
+                },
             }
         }
 
-        impl $basepoint_type {
-            pub(crate) fn push(&mut self, item: (usize, $basepoint_var)) {
-                self.0.push(item);
+        // Constraint #1: C_W = G_w * w + G_w' * w'
+        prover.constrain(C_W, vec![(w, G_w), (w_prime, G_w_prime)]);
+
+        // Constraint #2: I = -G_V + G_x_0 * x_0 + G_x_1 * x_1 + G_y_1 * y_1 + ... + G_y_n * y_n
+        let mut rhs: Vec<(ScalarVar, PointVar)> = Vec::with_capacity(3 + issuer.system_parameters.NUMBER_OF_ATTRIBUTES as usize);
+
+        rhs.push((one, neg_G_V));
+        rhs.push((x_0, G_x_0));
+        rhs.push((x_1, G_x_1));
+        rhs.extend(y.iter().copied().zip(G_y.iter().copied()));
+
+        prover.constrain(I, rhs);
+        prover.constrain(E_V1, rhs_1);
+        prover.constrain(E_V2, rhs_2);
+
+        ProofOfBlindedIssuance(prover.prove_compact())
+    }
+
+    /// Verify a [`ProofOfBlindedIssuance`].
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    /// * The published [`IssuerParameters`],
+    /// * The user's ElGamal `public_key`,
+    /// * The `blinded_attributes` of the request, and
+    /// * The `amac` which was computed over them.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`].
+    pub(crate) fn verify(
+        &self,
+        system_parameters: &SystemParameters,
+        issuer_parameters: &IssuerParameters,
+        public_key: &ElGamalPublicKey,
+        blinded_attributes: &Vec<BlindedAttribute>,
+        amac: &BlindedAmac,
+    ) -> Result<(), CredentialError>
+    {
+        use zkp::toolbox::verifier::PointVar;
+        use zkp::toolbox::verifier::ScalarVar;
+
+        if blinded_attributes.len() > system_parameters.NUMBER_OF_ATTRIBUTES as usize {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+        let mut verifier = Verifier::new(b"2019/1416 blinded issuance proof", &mut transcript);
+
+        // Commit the names of the Camenisch-Stadler secrets to the protocol transcript.
+        let w       = verifier.allocate_scalar(b"w");
+        let w_prime = verifier.allocate_scalar(b"w'");
+        let x_0     = verifier.allocate_scalar(b"x_0");
+        let x_1     = verifier.allocate_scalar(b"x_1");
+
+        let mut y: Vec<ScalarVar> = Vec::with_capacity(system_parameters.NUMBER_OF_ATTRIBUTES as usize);
+
+        for _ in 0..system_parameters.NUMBER_OF_ATTRIBUTES {
+            // XXX fix the zkp crate to take Strings
+            y.push(verifier.allocate_scalar(b"y"));
+        }
+
+        let one = verifier.allocate_scalar(b"1");
+        let r   = verifier.allocate_scalar(b"r");
+
+        // Commit to the values and names of the Camenisch-Stadler publics.
+        let neg_G_V   = verifier.allocate_point(b"-G_V",    (-system_parameters.G_V).compress())?;
+        let G         = verifier.allocate_point(b"G",         system_parameters.G.compress())?;
+        let G_w       = verifier.allocate_point(b"G_w",       system_parameters.G_w.compress())?;
+        let G_w_prime = verifier.allocate_point(b"G_w_prime", system_parameters.G_w_prime.compress())?;
+        let G_x_0     = verifier.allocate_point(b"G_x_0",     system_parameters.G_x_0.compress())?;
+        let G_x_1     = verifier.allocate_point(b"G_x_1",     system_parameters.G_x_1.compress())?;
+
+        let mut G_y: Vec<PointVar> = Vec::with_capacity(system_parameters.NUMBER_OF_ATTRIBUTES as usize);
+
+        for G_y_i in system_parameters.G_y.iter() {
+            // XXX fix the zkp crate to take Strings
+            G_y.push(verifier.allocate_point(b"G_y", G_y_i.compress())?);
+        }
+
+        let C_W  = verifier.allocate_point(b"C_W",  issuer_parameters.C_W.compress())?;
+        let I    = verifier.allocate_point(b"I",    issuer_parameters.I.compress())?;
+        let U    = verifier.allocate_point(b"U",    amac.U.compress())?;
+        let tU   = verifier.allocate_point(b"tU",   (amac.U * amac.t).compress())?;
+        let D    = verifier.allocate_point(b"D",    public_key.D.compress())?;
+        let E_V1 = verifier.allocate_point(b"E_V1", amac.V.C1.compress())?;
+        let E_V2 = verifier.allocate_point(b"E_V2", amac.V.C2.compress())?;
+
+        let messages: Messages = Messages::from_blinded_attributes(blinded_attributes, system_parameters);
+
+        // Constraint #3: E_V1 = G * r + \sigma_{i \in \mathcal{H}}{E_i1 * y_i}
+        let mut rhs_1: Vec<(ScalarVar, PointVar)> = Vec::with_capacity(1 + blinded_attributes.len());
+
+        // Constraint #4: E_V2 = G_w * w + U * x_0 + tU * x_1 + D * r +
+        //                       \sigma_{i \notin \mathcal{H}}{M_i * y_i} +
+        //                       \sigma_{i \in \mathcal{H}}{E_i2 * y_i}
+        let mut rhs_2: Vec<(ScalarVar, PointVar)> = Vec::with_capacity(4 + blinded_attributes.len());
+
+        rhs_1.push((r, G));
+        rhs_2.push((w, G_w));
+        rhs_2.push((x_0, U));
+        rhs_2.push((x_1, tU));
+        rhs_2.push((r, D));
+
+        for (i, attribute) in blinded_attributes.iter().enumerate() {
+            match attribute {
+                BlindedAttribute::SecretScalar(E_i) | BlindedAttribute::EncryptedPoint(E_i) => {
+                    // XXX fix the zkp crate to take Strings
+                    let E_1 = verifier.allocate_point(b"E_1", E_i.C1.compress())?;
+                    let E_2 = verifier.allocate_point(b"E_2", E_i.C2.compress())?;
+
+                    rhs_1.push((y[i], E_1));
+                    rhs_2.push((y[i], E_2));
+                },
+                _ => {
+                    // XXX fix the zkp crate to take Strings
+                    let M_i = verifier.allocate_point(b"M", messages.0[i].compress())?;
+
+                    rhs_2.push((y[i], M_i));
+                },
             }
         }
+
+        // Constraint #1: C_W = G_w * w + G_w' * w'
+        verifier.constrain(C_W, vec![(w, G_w), (w_prime, G_w_prime)]);
+
+        // Constraint #2: I = -G_V + G_x_0 * x_0 + G_x_1 * x_1 + G_y_1 * y_1 + ... + G_y_n * y_n
+        let mut rhs: Vec<(ScalarVar, PointVar)> = Vec::with_capacity(3 + system_parameters.NUMBER_OF_ATTRIBUTES as usize);
+
+        rhs.push((one, neg_G_V));
+        rhs.push((x_0, G_x_0));
+        rhs.push((x_1, G_x_1));
+        rhs.extend(y.iter().copied().zip(G_y.iter().copied()));
+
+        verifier.constrain(I, rhs);
+        verifier.constrain(E_V1, rhs_1);
+        verifier.constrain(E_V2, rhs_2);
+
+        verifier.verify_compact(&self.0).or_else(|_| Err(CredentialError::VerificationFailure))
     }
 }
 
-construct_hidden_scalar_variant!(ProverHiddenScalars, ProverHiddenScalarBasepoints, ProverScalarVar, ProverPointVar);
-construct_hidden_scalar_variant!(VerifierHiddenScalars, VerifierHiddenScalarBasepoints, VerifierScalarVar, VerifierPointVar);
-
-/// A proof-of-knowledge of a valid `Credential` and its attributes,
-/// which may be either hidden or revealed.
-// XXX the commitments should be compressed
-pub struct ProofOfValidCredential {
-    proof: CompactProof,
-    proofs_of_encryption: Vec<(u16, ProofOfEncryption)>,
-    encrypted_attributes: Vec<EncryptedAttribute>,
-    hidden_scalar_indices: Vec<u16>,
-    C_x_0: RistrettoPoint,
-    C_x_1: RistrettoPoint,
-    C_V:   RistrettoPoint,
-    C_y: Vec<RistrettoPoint>,
+/// A proof-of-knowledge that a ciphertext encrypts a plaintext
+/// committed to in a list of commitments.
+pub struct ProofOfEncryption {
+    proof: BatchableProof,
+    public_key: SymmetricPublicKey,
+    ciphertext: Ciphertext,
+    index: u16,
+    C_y_1: RistrettoPoint,
+    C_y_2: RistrettoPoint,
+    C_y_3: RistrettoPoint,
+    C_y_2_prime: RistrettoPoint,
 }
 
-impl ProofOfValidCredential {
-    /// Create a [`ProofOfValidCredential`]
+impl ProofOfEncryption {
+    /// Prove in zero-knowledge that a ciphertext is a verifiable encryption of
+    /// a plaintext w.r.t. a valid commitment to a secret symmetric key.
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    /// * A `plaintext` of up to thirty bytes.
+    /// * The `index` of the attribute to be encrypted.
+    /// * A symmetric "keypair",
+    /// * The nonce, `z`, must be reused from the outer-lying [`ProofOfValidCredential`].
+    /// * A `csprng`, used to blind the commitments to the remaining portions
+    ///   of the plaintext.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`].
     pub fn prove<C>(
         system_parameters: &SystemParameters,
-        issuer_parameters: &IssuerParameters,
-        credential: &AnonymousCredential,
-        keypair: Option<&SymmetricKeypair>,
+        plaintext: &Plaintext,
+        index: u16,
+        keypair: &SymmetricKeypair,
+        z: &Scalar,
         csprng: &mut C,
-    ) -> Result<ProofOfValidCredential, CredentialError>
+    ) -> ProofOfEncryption
     where
         C: RngCore + CryptoRng,
     {
-        // If a keypair was not supplied and we have encrypted group element attributes, bail early.
-        if keypair.is_none() {
-            for attribute in credential.attributes.iter() {
-                match attribute {
-                    Attribute::SecretPoint(_) => return Err(CredentialError::NoSymmetricKey),
-                    _ => continue,
-                }
-            }
+        // Encrypt the plaintext.
+        let ciphertext_ = keypair.encrypt(&plaintext);
+
+        // Choose a separate nonce for the commitments to M2 and m3, since they
+        // aren't part of the credential and must not be linkable to it.
+        let z_prime_: Scalar = Scalar::random(csprng);
+
+        // Compute the vector C of commitments to the plaintext.  The commitment
+        // to M1 is the same as the commitment C_y_i in the outer-lying
+        // [`ProofOfValidCredential`].
+        let C_y_1_ = (system_parameters.G_y[index as usize] * z) + plaintext.M1;
+        let C_y_2_ = (system_parameters.G_y[1] * z_prime_) + plaintext.M2;
+        let C_y_3_ = (system_parameters.G_y[2] * z_prime_) + (system_parameters.G_m[index as usize] * plaintext.m3);
+
+        // Compute C_y_2' = C_y_2 * a1.
+        let C_y_2_prime_ = C_y_2_ * keypair.secret.a1;
+
+        // Calculate z1 = -z'(a0 + a1 * m3).
+        let z1_ = -z_prime_ * (keypair.secret.a0 + keypair.secret.a1 * plaintext.m3);
+
+        // Construct a protocol transcript and prover.
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credentials");
+        let mut prover = Prover::new(b"2019/1416 proof of encryption", &mut transcript);
+
+        // Commit the names of the Camenisch-Stadler secrets to the protocol transcript.
+        let a       = prover.allocate_scalar(b"a",  keypair.secret.a);
+        let a0      = prover.allocate_scalar(b"a0", keypair.secret.a0);
+        let a1      = prover.allocate_scalar(b"a1", keypair.secret.a1);
+        let m3      = prover.allocate_scalar(b"m3", plaintext.m3);
+        let z       = prover.allocate_scalar(b"z",  *z);
+        let z_prime = prover.allocate_scalar(b"z'", z_prime_);
+        let z1      = prover.allocate_scalar(b"z1", z1_);
+
+        // Commit to the values and names of the Camenisch-Stadler publics.
+        let (pk, _)             = prover.allocate_point(b"pk",       keypair.public.pk);
+        let (G_a, _)            = prover.allocate_point(b"G_a",      system_parameters.G_a);
+        let (G_a_0, _)          = prover.allocate_point(b"G_a_0",    system_parameters.G_a0);
+        let (G_a_1, _)          = prover.allocate_point(b"G_a_1",    system_parameters.G_a1);
+        let (G_y_1, _)          = prover.allocate_point(b"G_y_1",    system_parameters.G_y[index as usize]);
+        let (G_y_2, _)          = prover.allocate_point(b"G_y_2",    system_parameters.G_y[1]);
+        let (G_y_3, _)          = prover.allocate_point(b"G_y_3",    system_parameters.G_y[2]);
+        let (G_m_3, _)          = prover.allocate_point(b"G_m_3",    system_parameters.G_m[index as usize]);
+        let (C_y_2, _)          = prover.allocate_point(b"C_y_2",    C_y_2_);
+        let (C_y_3, _)          = prover.allocate_point(b"C_y_3",    C_y_3_);
+        let (C_y_2_prime, _)    = prover.allocate_point(b"C_y_2'",   C_y_2_prime_);
+        let (C_y_1_minus_E2, _) = prover.allocate_point(b"C_y_1-E2", C_y_1_ - ciphertext_.E2);
+        let (E1, _)             = prover.allocate_point(b"E1",       ciphertext_.E1);
+        let (minus_E1, _)       = prover.allocate_point(b"-E1",      -ciphertext_.E1);
+
+        // Constraint #1: Prove knowledge of the secret portions of the symmetric key.
+        //                pk = G_a * a + G_a0 * a0 + G_a1 * a1
+        prover.constrain(pk, vec![(a, G_a), (a0, G_a_0), (a1, G_a_1)]);
+
+        // Constraint #2: The plaintext of this encryption is the message.
+        //                C_y_1 - E2 = G_y_1 * z - E_1 * a
+        prover.constrain(C_y_1_minus_E2, vec![(z, G_y_1), (a, minus_E1)]);
+
+        // Constraint #3: The encryption C_y_2' of the commitment C_y_2 is formed correctly w.r.t. the secret key.
+        //                C_y_2' = C_y_2 * a1
+        prover.constrain(C_y_2_prime, vec![(a1, C_y_2)]);
+
+        // Constraint #4: The encryption E1 is well formed.
+        //                  E1 = C_y_2             * a0 + C_y_2'                 * m3    + G_y_2 * z1
+        // M2 * (a0 + a1 * m3) = (M2 + G_y_2 * z') * a0 + (M2 + G_y_2 * z') * a1 * m3    + G_y_2 * -z' (a0 + a1 * m3)
+        // M2(a0) + M2(a1)(m3) = M2(a0) + G_y_2(z')(a0) + M2(a1)(m3) + G_y_2(z')(a1)(m3) + G_y_2(-z')(a0) + G_y_2(-z')(a1)(m3)
+        // M2(a0) + M2(a1)(m3) = M2(a0)                 + M2(a1)(m3)
+        prover.constrain(E1, vec![(a0, C_y_2), (m3, C_y_2_prime), (z1, G_y_2)]);
+
+        // Constraint #5: The commitment to the hash m3 is a correct hash of the message commited to.
+        prover.constrain(C_y_3, vec![(z_prime, G_y_3), (m3, G_m_3)]);
+
+        let proof = prover.prove_batchable();
+
+        ProofOfEncryption {
+            proof: proof,
+            public_key: keypair.public,
+            ciphertext: ciphertext_,
+            index: index,
+            C_y_1: C_y_1_,
+            C_y_2: C_y_2_,
+            C_y_3: C_y_3_,
+            C_y_2_prime: C_y_2_prime_,
+        }
+    }
+
+    /// Verify that this [`ProofOfEncryption`] proves that a `ciphertext` is a
+    /// correct encryption of a verifiably-encrypted plaintext.
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    /// * The [`Ciphertext`] in question,
+    /// * The "public key" for the symmetric verifiable encryption scheme.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`].
+    pub fn verify(
+        &self,
+        system_parameters: &SystemParameters,
+    ) -> Result<(), CredentialError>
+    {
+        if self.index as usize >= system_parameters.NUMBER_OF_ATTRIBUTES as usize {
+            return Err(CredentialError::WrongNumberOfAttributes);
         }
 
-        let NUMBER_OF_ATTRIBUTES = system_parameters.NUMBER_OF_ATTRIBUTES as usize;
+        // Construct a protocol transcript and verifier.
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credentials");
+        let mut verifier = Verifier::new(b"2019/1416 proof of encryption", &mut transcript);
+
+        // Commit the names of the Camenisch-Stadler secrets to the protocol transcript.
+        let a       = verifier.allocate_scalar(b"a");
+        let a0      = verifier.allocate_scalar(b"a0");
+        let a1      = verifier.allocate_scalar(b"a1");
+        let m3      = verifier.allocate_scalar(b"m3");
+        let z       = verifier.allocate_scalar(b"z");
+        let z_prime = verifier.allocate_scalar(b"z'");
+        let z1      = verifier.allocate_scalar(b"z1");
+
+        // Commit to the values and names of the Camenisch-Stadler publics.
+        let pk             = verifier.allocate_point(b"pk",       self.public_key.pk.compress())?;
+        let G_a            = verifier.allocate_point(b"G_a",      system_parameters.G_a.compress())?;
+        let G_a_0          = verifier.allocate_point(b"G_a_0",    system_parameters.G_a0.compress())?;
+        let G_a_1          = verifier.allocate_point(b"G_a_1",    system_parameters.G_a1.compress())?;
+        let G_y_1          = verifier.allocate_point(b"G_y_1",    system_parameters.G_y[self.index as usize].compress())?;
+        let G_y_2          = verifier.allocate_point(b"G_y_2",    system_parameters.G_y[1].compress())?;
+        let G_y_3          = verifier.allocate_point(b"G_y_3",    system_parameters.G_y[2].compress())?;
+        let G_m_3          = verifier.allocate_point(b"G_m_3",    system_parameters.G_m[self.index as usize].compress())?;
+        let C_y_2          = verifier.allocate_point(b"C_y_2",    self.C_y_2.compress())?;
+        let C_y_3          = verifier.allocate_point(b"C_y_3",    self.C_y_3.compress())?;
+        let C_y_2_prime    = verifier.allocate_point(b"C_y_2'",   self.C_y_2_prime.compress())?;
+        let C_y_1_minus_E2 = verifier.allocate_point(b"C_y_1-E2", (self.C_y_1 - self.ciphertext.E2).compress())?;
+        let E1             = verifier.allocate_point(b"E1",       self.ciphertext.E1.compress())?;
+        let minus_E1       = verifier.allocate_point(b"-E1",      (-self.ciphertext.E1).compress())?;
+
+        // Constraint #1: Prove knowledge of the secret portions of the symmetric key.
+        //                pk = G_a * a + G_a0 * a0 + G_a1 * a1
+        verifier.constrain(pk, vec![(a, G_a), (a0, G_a_0), (a1, G_a_1)]);
+
+        // Constraint #2: The plaintext of this encryption is the message.
+        //                C_y_1 - E2 = G_y_1 * z - E_1 * a
+        verifier.constrain(C_y_1_minus_E2, vec![(z, G_y_1), (a, minus_E1)]);
+
+        // Constraint #3: The encryption C_y_2' of the commitment C_y_2 is formed correctly w.r.t. the secret key.
+        //                C_y_2' = C_y_2 * a1
+        verifier.constrain(C_y_2_prime, vec![(a1, C_y_2)]);
+
+        // Constraint #4: The encryption E1 is well formed.
+        //                  E1 = C_y_2             * a0 + C_y_2'                 * m3    + G_y_2 * z1
+        // M2 * (a0 + a1 * m3) = (M2 + G_y_2 * z') * a0 + (M2 + G_y_2 * z') * a1 * m3    + G_y_2 * -z' (a0 + a1 * m3)
+        // M2(a0) + M2(a1)(m3) = M2(a0) + G_y_2(z')(a0) + M2(a1)(m3) + G_y_2(z')(a1)(m3) + G_y_2(-z')(a0) + G_y_2(-z')(a1)(m3)
+        // M2(a0) + M2(a1)(m3) = M2(a0)                 + M2(a1)(m3)
+        verifier.constrain(E1, vec![(a0, C_y_2), (m3, C_y_2_prime), (z1, G_y_2)]);
+
+        // Constraint #5: The commitment to the hash m3 is a correct hash of the message commited to.
+        verifier.constrain(C_y_3, vec![(z_prime, G_y_3), (m3, G_m_3)]);
+
+        verifier.verify_batchable(&self.proof).or_else(|_| Err(CredentialError::VerificationFailure))
+    }
+
+    /// Verify many [`ProofOfEncryption`]s at once.
+    ///
+    /// All of the proofs are combined into a single multiscalar
+    /// multiplication.  If the combined check fails, each proof is verified
+    /// individually in order to find the culprit.
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    /// * The `proofs` to verify.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty if every proof verifies, otherwise
+    /// a [`CredentialError::BatchVerificationFailure`] with the index of the
+    /// first proof which failed.
+    pub fn verify_batch(
+        proofs: &[ProofOfEncryption],
+        system_parameters: &SystemParameters,
+    ) -> Result<(), CredentialError>
+    {
+        let references: Vec<&ProofOfEncryption> = proofs.iter().collect();
+
+        if ProofOfEncryption::verify_batchable_proofs(&references, system_parameters).is_ok() {
+            return Ok(());
+        }
+
+        for (j, proof) in proofs.iter().enumerate() {
+            if proof.verify(system_parameters).is_err() {
+                return Err(CredentialError::BatchVerificationFailure{ index: j });
+            }
+        }
+
+        // The combined check failed but every individual check passed, which
+        // should only happen if the batch verifier itself errored.
+        Err(CredentialError::VerificationFailure)
+    }
+
+    /// Verify the Schnorr proofs of many [`ProofOfEncryption`]s in a single
+    /// combined check, without locating any which fail.
+    fn verify_batchable_proofs(
+        proofs: &[&ProofOfEncryption],
+        system_parameters: &SystemParameters,
+    ) -> Result<(), CredentialError>
+    {
+        if proofs.is_empty() {
+            return Ok(());
+        }
+
+        for proof in proofs.iter() {
+            if proof.index as usize >= system_parameters.NUMBER_OF_ATTRIBUTES as usize {
+                return Err(CredentialError::WrongNumberOfAttributes);
+            }
+        }
+
+        // Construct a protocol transcript for each proof and a batch verifier.
+        let mut transcripts: Vec<Transcript> = proofs.iter().map(|_| Transcript::new(b"2019/1416 anonymous credentials")).collect();
+        let mut verifier = BatchVerifier::new(b"2019/1416 proof of encryption", proofs.len(), transcripts.iter_mut().collect())?;
+
+        // Commit the names of the Camenisch-Stadler secrets to the protocol transcripts.
+        let a       = verifier.allocate_scalar(b"a");
+        let a0      = verifier.allocate_scalar(b"a0");
+        let a1      = verifier.allocate_scalar(b"a1");
+        let m3      = verifier.allocate_scalar(b"m3");
+        let z       = verifier.allocate_scalar(b"z");
+        let z_prime = verifier.allocate_scalar(b"z'");
+        let z1      = verifier.allocate_scalar(b"z1");
+
+        // Commit to the values and names of the Camenisch-Stadler publics.  The
+        // generators which depend on the attribute index differ per proof.
+        let pk             = verifier.allocate_instance_point(b"pk",       proofs.iter().map(|p| p.public_key.pk.compress()).collect())?;
+        let G_a            = verifier.allocate_static_point(b"G_a",        system_parameters.G_a.compress())?;
+        let G_a_0          = verifier.allocate_static_point(b"G_a_0",      system_parameters.G_a0.compress())?;
+        let G_a_1          = verifier.allocate_static_point(b"G_a_1",      system_parameters.G_a1.compress())?;
+        let G_y_1          = verifier.allocate_instance_point(b"G_y_1",    proofs.iter().map(|p| system_parameters.G_y[p.index as usize].compress()).collect())?;
+        let G_y_2          = verifier.allocate_static_point(b"G_y_2",      system_parameters.G_y[1].compress())?;
+        let G_y_3          = verifier.allocate_static_point(b"G_y_3",      system_parameters.G_y[2].compress())?;
+        let G_m_3          = verifier.allocate_instance_point(b"G_m_3",    proofs.iter().map(|p| system_parameters.G_m[p.index as usize].compress()).collect())?;
+        let C_y_2          = verifier.allocate_instance_point(b"C_y_2",    proofs.iter().map(|p| p.C_y_2.compress()).collect())?;
+        let C_y_3          = verifier.allocate_instance_point(b"C_y_3",    proofs.iter().map(|p| p.C_y_3.compress()).collect())?;
+        let C_y_2_prime    = verifier.allocate_instance_point(b"C_y_2'",   proofs.iter().map(|p| p.C_y_2_prime.compress()).collect())?;
+        let C_y_1_minus_E2 = verifier.allocate_instance_point(b"C_y_1-E2", proofs.iter().map(|p| (p.C_y_1 - p.ciphertext.E2).compress()).collect())?;
+        let E1             = verifier.allocate_instance_point(b"E1",       proofs.iter().map(|p| p.ciphertext.E1.compress()).collect())?;
+        let minus_E1       = verifier.allocate_instance_point(b"-E1",      proofs.iter().map(|p| (-p.ciphertext.E1).compress()).collect())?;
+
+        // Constraint #1: pk = G_a * a + G_a0 * a0 + G_a1 * a1
+        verifier.constrain(pk, vec![(a, G_a), (a0, G_a_0), (a1, G_a_1)]);
+
+        // Constraint #2: C_y_1 - E2 = G_y_1 * z - E_1 * a
+        verifier.constrain(C_y_1_minus_E2, vec![(z, G_y_1), (a, minus_E1)]);
+
+        // Constraint #3: C_y_2' = C_y_2 * a1
+        verifier.constrain(C_y_2_prime, vec![(a1, C_y_2)]);
+
+        // Constraint #4: E1 = C_y_2 * a0 + C_y_2' * m3 + G_y_2 * z1
+        verifier.constrain(E1, vec![(a0, C_y_2), (m3, C_y_2_prime), (z1, G_y_2)]);
+
+        // Constraint #5: C_y_3 = G_y_3 * z' + G_m_3 * m3
+        verifier.constrain(C_y_3, vec![(z_prime, G_y_3), (m3, G_m_3)]);
+
+        let batchable_proofs: Vec<BatchableProof> = proofs.iter().map(|p| p.proof.clone()).collect();
+
+        verifier.verify_batchable(&batchable_proofs).or_else(|_| Err(CredentialError::VerificationFailure))
+    }
+}
+
+/// The size of a serialised [`ProofOfEncryption`], in bytes.
+///
+/// This is the version, the index, the public key, the ciphertext, the four
+/// commitments, and a proof with five commitments and seven responses.
+pub(crate) const SIZEOF_PROOF_OF_ENCRYPTION: usize = 1 + 2 + 32 + SIZEOF_CIPHERTEXT + (4 * 32) + (12 * 32);
+
+/// The number of constraints, and hence commitments, in a [`ProofOfEncryption`].
+const NUMBER_OF_PROOF_OF_ENCRYPTION_CONSTRAINTS: usize = 5;
+
+impl ProofOfEncryption {
+    /// Deserialise a [`ProofOfEncryption`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ProofOfEncryption, CredentialError> {
+        if bytes.len() != SIZEOF_PROOF_OF_ENCRYPTION {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+        if bytes[0] != PROOF_FORMAT_VERSION {
+            return Err(CredentialError::UnsupportedVersion);
+        }
+
+        let mut index: usize = 1;
+        let mut chunk = [0u8; 32];
+        let mut u16_bytes = [0u8; 2];
+
+        u16_bytes.copy_from_slice(&bytes[index..index+2]); index += 2;
+        let attribute_index: u16 = u16::from_le_bytes(u16_bytes);
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let public_key = SymmetricPublicKey { pk: try_deserialise!("pk", chunk) };
+
+        let ciphertext = Ciphertext::from_bytes(&bytes[index..index+SIZEOF_CIPHERTEXT])?; index += SIZEOF_CIPHERTEXT;
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let C_y_1: RistrettoPoint = try_deserialise!("C_y_1", chunk);
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let C_y_2: RistrettoPoint = try_deserialise!("C_y_2", chunk);
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let C_y_3: RistrettoPoint = try_deserialise!("C_y_3", chunk);
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let C_y_2_prime: RistrettoPoint = try_deserialise!("C_y_2'", chunk);
+
+        let proof = batchable_proof_from_bytes(&bytes[index..], NUMBER_OF_PROOF_OF_ENCRYPTION_CONSTRAINTS)?;
+
+        Ok(ProofOfEncryption { proof, public_key, ciphertext, index: attribute_index, C_y_1, C_y_2, C_y_3, C_y_2_prime })
+    }
+
+    /// Serialise this [`ProofOfEncryption`] to a byte array.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::with_capacity(SIZEOF_PROOF_OF_ENCRYPTION);
+
+        v.push(PROOF_FORMAT_VERSION);
+        v.extend(self.index.to_le_bytes().iter());
+        v.extend(self.public_key.pk.compress().to_bytes().iter());
+        v.extend(self.ciphertext.to_bytes());
+        v.extend(self.C_y_1.compress().to_bytes().iter());
+        v.extend(self.C_y_2.compress().to_bytes().iter());
+        v.extend(self.C_y_3.compress().to_bytes().iter());
+        v.extend(self.C_y_2_prime.compress().to_bytes().iter());
+        batchable_proof_to_bytes(&self.proof, &mut v);
+        v
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(ProofOfEncryption, "A valid byte sequence representing a ProofOfEncryption");
+
+/// A proof that two [`Ciphertext`]s, under two different symmetric keys,
+/// encrypt the same [`Plaintext`].
+///
+/// This allows e.g. a server which receives the same hidden group element
+/// attribute encrypted for two services to check that they are consistent
+/// without learning the attribute, or, via
+/// [`ProofOfPlaintextEquality::reencrypt`], a ciphertext to be moved to the
+/// keypair for a new epoch with [`SymmetricKeypair::derive_for_epoch`].
+///
+/// As in a [`ProofOfEncryption`], the plaintext \( (M_1, M_2, m_3) \) is
+/// hidden behind commitments, to which both encryptions are then proven to be
+/// correct.
+pub struct ProofOfPlaintextEquality {
+    proof: CompactProof,
+    C_1: RistrettoPoint,
+    C_2: RistrettoPoint,
+    C_3: RistrettoPoint,
+    C_2_a: RistrettoPoint,
+    C_2_b: RistrettoPoint,
+}
+
+/// The size of a serialised [`ProofOfPlaintextEquality`], in bytes.
+///
+/// This is the version, the five commitments, and a proof with eleven
+/// responses.
+pub(crate) const SIZEOF_PROOF_OF_PLAINTEXT_EQUALITY: usize = 1 + (5 * 32) + (12 * 32);
+
+impl ProofOfPlaintextEquality {
+    /// Encrypt a `plaintext` under two symmetric keypairs, and prove in
+    /// zero-knowledge that both ciphertexts encrypt the same plaintext.
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    ///   which must have at least three attributes,
+    /// * The `plaintext`,
+    /// * The `first_keypair` and `second_keypair`,
+    /// * A `csprng`, used to blind the commitments to the plaintext.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the encryptions of the `plaintext` under
+    /// the first and second keypairs and a [`ProofOfPlaintextEquality`],
+    /// otherwise a [`CredentialError`].
+    pub fn prove<C>(
+        system_parameters: &SystemParameters,
+        plaintext: &Plaintext,
+        first_keypair: &SymmetricKeypair,
+        second_keypair: &SymmetricKeypair,
+        csprng: &mut C,
+    ) -> Result<(Ciphertext, Ciphertext, ProofOfPlaintextEquality), CredentialError>
+    where
+        C: RngCore + CryptoRng,
+    {
+        if system_parameters.NUMBER_OF_ATTRIBUTES < 3 {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+
+        let first = &first_keypair.secret;
+        let second = &second_keypair.secret;
+
+        // Encrypt the plaintext under both keys.
+        let ciphertext = first_keypair.encrypt(plaintext);
+        let ciphertext_ = second_keypair.encrypt(plaintext);
+
+        // Commit to the plaintext.  Unlike in a [`ProofOfEncryption`], none of
+        // these commitments are part of a credential.
+        let z_: Scalar = Scalar::random(csprng);
+        let z_prime_: Scalar = Scalar::random(csprng);
+
+        let C_1_ = (system_parameters.G_y[0] * z_) + plaintext.M1;
+        let C_2_ = (system_parameters.G_y[1] * z_prime_) + plaintext.M2;
+        let C_3_ = (system_parameters.G_y[2] * z_prime_) + (system_parameters.G_m[0] * plaintext.m3);
+
+        // Compute C_2_a = C_2 * a1 and C_2_b = C_2 * b1.
+        let C_2_a_ = C_2_ * first.a1;
+        let C_2_b_ = C_2_ * second.a1;
+
+        // Calculate w_a = -z'(a0 + a1 * m3) and w_b = -z'(b0 + b1 * m3).
+        let w_a_ = -z_prime_ * (first.a0 + first.a1 * plaintext.m3);
+        let w_b_ = -z_prime_ * (second.a0 + second.a1 * plaintext.m3);
+
+        // Construct a protocol transcript and prover.
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credentials");
+        let mut prover = Prover::new(b"2019/1416 proof of plaintext equality", &mut transcript);
+
+        // Commit the names of the Camenisch-Stadler secrets to the protocol transcript.
+        let a       = prover.allocate_scalar(b"a",   first.a);
+        let a0      = prover.allocate_scalar(b"a0",  first.a0);
+        let a1      = prover.allocate_scalar(b"a1",  first.a1);
+        let b       = prover.allocate_scalar(b"b",   second.a);
+        let b0      = prover.allocate_scalar(b"b0",  second.a0);
+        let b1      = prover.allocate_scalar(b"b1",  second.a1);
+        let m3      = prover.allocate_scalar(b"m3",  plaintext.m3);
+        let z       = prover.allocate_scalar(b"z",   z_);
+        let z_prime = prover.allocate_scalar(b"z'",  z_prime_);
+        let w_a     = prover.allocate_scalar(b"w_a", w_a_);
+        let w_b     = prover.allocate_scalar(b"w_b", w_b_);
+
+        // Commit to the values and names of the Camenisch-Stadler publics.
+        let (pk_a, _)         = prover.allocate_point(b"pk_a",    first_keypair.public.pk);
+        let (pk_b, _)         = prover.allocate_point(b"pk_b",    second_keypair.public.pk);
+        let (G_a, _)          = prover.allocate_point(b"G_a",     system_parameters.G_a);
+        let (G_a_0, _)        = prover.allocate_point(b"G_a_0",   system_parameters.G_a0);
+        let (G_a_1, _)        = prover.allocate_point(b"G_a_1",   system_parameters.G_a1);
+        let (G_y_1, _)        = prover.allocate_point(b"G_y_1",   system_parameters.G_y[0]);
+        let (G_y_2, _)        = prover.allocate_point(b"G_y_2",   system_parameters.G_y[1]);
+        let (G_y_3, _)        = prover.allocate_point(b"G_y_3",   system_parameters.G_y[2]);
+        let (G_m_3, _)        = prover.allocate_point(b"G_m_3",   system_parameters.G_m[0]);
+        let (C_2, _)          = prover.allocate_point(b"C_2",     C_2_);
+        let (C_3, _)          = prover.allocate_point(b"C_3",     C_3_);
+        let (C_2_a, _)        = prover.allocate_point(b"C_2_a",   C_2_a_);
+        let (C_2_b, _)        = prover.allocate_point(b"C_2_b",   C_2_b_);
+        let (C_1_minus_E2, _) = prover.allocate_point(b"C_1-E2",  C_1_ - ciphertext.E2);
+        let (C_1_minus_F2, _) = prover.allocate_point(b"C_1-E2'", C_1_ - ciphertext_.E2);
+        let (E1, _)           = prover.allocate_point(b"E1",      ciphertext.E1);
+        let (F1, _)           = prover.allocate_point(b"E1'",     ciphertext_.E1);
+        let (minus_E1, _)     = prover.allocate_point(b"-E1",     -ciphertext.E1);
+        let (minus_F1, _)     = prover.allocate_point(b"-E1'",    -ciphertext_.E1);
+
+        // Constraint #1: Prove knowledge of both symmetric keys.
+        //                pk_a = G_a * a + G_a0 * a0 + G_a1 * a1
+        //                pk_b = G_a * b + G_a0 * b0 + G_a1 * b1
+        prover.constrain(pk_a, vec![(a, G_a), (a0, G_a_0), (a1, G_a_1)]);
+        prover.constrain(pk_b, vec![(b, G_a), (b0, G_a_0), (b1, G_a_1)]);
+
+        // Constraint #2: Both ciphertexts encrypt the committed M1.
+        //                C_1 - E2  = G_y_1 * z - E1  * a
+        //                C_1 - E2' = G_y_1 * z - E1' * b
+        prover.constrain(C_1_minus_E2, vec![(z, G_y_1), (a, minus_E1)]);
+        prover.constrain(C_1_minus_F2, vec![(z, G_y_1), (b, minus_F1)]);
+
+        // Constraint #3: C_2_a = C_2 * a1 and C_2_b = C_2 * b1
+        prover.constrain(C_2_a, vec![(a1, C_2)]);
+        prover.constrain(C_2_b, vec![(b1, C_2)]);
+
+        // Constraint #4: Both ciphertexts are well formed w.r.t. the committed M2 and m3,
+        //                as in constraint #4 of a [`ProofOfEncryption`].
+        //                E1  = C_2 * a0 + C_2_a * m3 + G_y_2 * w_a
+        //                E1' = C_2 * b0 + C_2_b * m3 + G_y_2 * w_b
+        prover.constrain(E1, vec![(a0, C_2), (m3, C_2_a), (w_a, G_y_2)]);
+        prover.constrain(F1, vec![(b0, C_2), (m3, C_2_b), (w_b, G_y_2)]);
+
+        // Constraint #5: C_3 = G_y_3 * z' + G_m_3 * m3
+        prover.constrain(C_3, vec![(z_prime, G_y_3), (m3, G_m_3)]);
+
+        let proof = ProofOfPlaintextEquality {
+            proof: prover.prove_compact(),
+            C_1: C_1_,
+            C_2: C_2_,
+            C_3: C_3_,
+            C_2_a: C_2_a_,
+            C_2_b: C_2_b_,
+        };
+
+        Ok((ciphertext, ciphertext_, proof))
+    }
+
+    /// Re-encrypt a `ciphertext` under a new symmetric keypair, e.g. after a
+    /// key rotation, and prove in zero-knowledge that the old and new
+    /// ciphertexts encrypt the same plaintext.
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    /// * The `old_keypair`, which the `ciphertext` is encrypted under,
+    /// * The `new_keypair`, to re-encrypt the plaintext under,
+    /// * The `ciphertext`,
+    /// * A `csprng`, used to blind the commitments to the plaintext.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the new [`Ciphertext`] and a
+    /// [`ProofOfPlaintextEquality`], otherwise a [`CredentialError`], which is
+    /// [`CredentialError::UndecryptableAttribute`] if the `ciphertext` is not
+    /// a valid encryption under the `old_keypair`.
+    pub fn reencrypt<C>(
+        system_parameters: &SystemParameters,
+        old_keypair: &SymmetricKeypair,
+        new_keypair: &SymmetricKeypair,
+        ciphertext: &Ciphertext,
+        csprng: &mut C,
+    ) -> Result<(Ciphertext, ProofOfPlaintextEquality), CredentialError>
+    where
+        C: RngCore + CryptoRng,
+    {
+        let plaintext = old_keypair.decrypt(ciphertext)?;
+        let (_, new_ciphertext, proof) = ProofOfPlaintextEquality::prove(system_parameters, &plaintext,
+                                                                         old_keypair, new_keypair, csprng)?;
+
+        Ok((new_ciphertext, proof))
+    }
+
+    /// Verify that this [`ProofOfPlaintextEquality`] proves that the
+    /// `first_ciphertext` and the `second_ciphertext` encrypt the same
+    /// plaintext.
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    /// * The `first_public_key` and the `first_ciphertext` encrypted under it, and
+    /// * The `second_public_key` and the `second_ciphertext` encrypted under it.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`].
+    pub fn verify(
+        &self,
+        system_parameters: &SystemParameters,
+        first_public_key: &SymmetricPublicKey,
+        first_ciphertext: &Ciphertext,
+        second_public_key: &SymmetricPublicKey,
+        second_ciphertext: &Ciphertext,
+    ) -> Result<(), CredentialError>
+    {
+        if system_parameters.NUMBER_OF_ATTRIBUTES < 3 {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+
+        // Construct a protocol transcript and verifier.
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credentials");
+        let mut verifier = Verifier::new(b"2019/1416 proof of plaintext equality", &mut transcript);
+
+        // Commit the names of the Camenisch-Stadler secrets to the protocol transcript.
+        let a       = verifier.allocate_scalar(b"a");
+        let a0      = verifier.allocate_scalar(b"a0");
+        let a1      = verifier.allocate_scalar(b"a1");
+        let b       = verifier.allocate_scalar(b"b");
+        let b0      = verifier.allocate_scalar(b"b0");
+        let b1      = verifier.allocate_scalar(b"b1");
+        let m3      = verifier.allocate_scalar(b"m3");
+        let z       = verifier.allocate_scalar(b"z");
+        let z_prime = verifier.allocate_scalar(b"z'");
+        let w_a     = verifier.allocate_scalar(b"w_a");
+        let w_b     = verifier.allocate_scalar(b"w_b");
+
+        // Commit to the values and names of the Camenisch-Stadler publics.
+        let pk_a         = verifier.allocate_point(b"pk_a",    first_public_key.pk.compress())?;
+        let pk_b         = verifier.allocate_point(b"pk_b",    second_public_key.pk.compress())?;
+        let G_a          = verifier.allocate_point(b"G_a",     system_parameters.G_a.compress())?;
+        let G_a_0        = verifier.allocate_point(b"G_a_0",   system_parameters.G_a0.compress())?;
+        let G_a_1        = verifier.allocate_point(b"G_a_1",   system_parameters.G_a1.compress())?;
+        let G_y_1        = verifier.allocate_point(b"G_y_1",   system_parameters.G_y[0].compress())?;
+        let G_y_2        = verifier.allocate_point(b"G_y_2",   system_parameters.G_y[1].compress())?;
+        let G_y_3        = verifier.allocate_point(b"G_y_3",   system_parameters.G_y[2].compress())?;
+        let G_m_3        = verifier.allocate_point(b"G_m_3",   system_parameters.G_m[0].compress())?;
+        let C_2          = verifier.allocate_point(b"C_2",     self.C_2.compress())?;
+        let C_3          = verifier.allocate_point(b"C_3",     self.C_3.compress())?;
+        let C_2_a        = verifier.allocate_point(b"C_2_a",   self.C_2_a.compress())?;
+        let C_2_b        = verifier.allocate_point(b"C_2_b",   self.C_2_b.compress())?;
+        let C_1_minus_E2 = verifier.allocate_point(b"C_1-E2",  (self.C_1 - first_ciphertext.E2).compress())?;
+        let C_1_minus_F2 = verifier.allocate_point(b"C_1-E2'", (self.C_1 - second_ciphertext.E2).compress())?;
+        let E1           = verifier.allocate_point(b"E1",      first_ciphertext.E1.compress())?;
+        let F1           = verifier.allocate_point(b"E1'",     second_ciphertext.E1.compress())?;
+        let minus_E1     = verifier.allocate_point(b"-E1",     (-first_ciphertext.E1).compress())?;
+        let minus_F1     = verifier.allocate_point(b"-E1'",    (-second_ciphertext.E1).compress())?;
+
+        // Constraint #1: pk_a = G_a * a + G_a0 * a0 + G_a1 * a1
+        //                pk_b = G_a * b + G_a0 * b0 + G_a1 * b1
+        verifier.constrain(pk_a, vec![(a, G_a), (a0, G_a_0), (a1, G_a_1)]);
+        verifier.constrain(pk_b, vec![(b, G_a), (b0, G_a_0), (b1, G_a_1)]);
+
+        // Constraint #2: C_1 - E2  = G_y_1 * z - E1  * a
+        //                C_1 - E2' = G_y_1 * z - E1' * b
+        verifier.constrain(C_1_minus_E2, vec![(z, G_y_1), (a, minus_E1)]);
+        verifier.constrain(C_1_minus_F2, vec![(z, G_y_1), (b, minus_F1)]);
+
+        // Constraint #3: C_2_a = C_2 * a1 and C_2_b = C_2 * b1
+        verifier.constrain(C_2_a, vec![(a1, C_2)]);
+        verifier.constrain(C_2_b, vec![(b1, C_2)]);
+
+        // Constraint #4: E1  = C_2 * a0 + C_2_a * m3 + G_y_2 * w_a
+        //                E1' = C_2 * b0 + C_2_b * m3 + G_y_2 * w_b
+        verifier.constrain(E1, vec![(a0, C_2), (m3, C_2_a), (w_a, G_y_2)]);
+        verifier.constrain(F1, vec![(b0, C_2), (m3, C_2_b), (w_b, G_y_2)]);
+
+        // Constraint #5: C_3 = G_y_3 * z' + G_m_3 * m3
+        verifier.constrain(C_3, vec![(z_prime, G_y_3), (m3, G_m_3)]);
+
+        verifier.verify_compact(&self.proof).or_else(|_| Err(CredentialError::VerificationFailure))
+    }
+
+    /// Deserialise a [`ProofOfPlaintextEquality`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ProofOfPlaintextEquality, CredentialError> {
+        if bytes.len() != SIZEOF_PROOF_OF_PLAINTEXT_EQUALITY {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+        if bytes[0] != PROOF_FORMAT_VERSION {
+            return Err(CredentialError::UnsupportedVersion);
+        }
+
+        let mut index: usize = 1;
+        let mut chunk = [0u8; 32];
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let C_1: RistrettoPoint = try_deserialise!("C_1", chunk);
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let C_2: RistrettoPoint = try_deserialise!("C_2", chunk);
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let C_3: RistrettoPoint = try_deserialise!("C_3", chunk);
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let C_2_a: RistrettoPoint = try_deserialise!("C_2_a", chunk);
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let C_2_b: RistrettoPoint = try_deserialise!("C_2_b", chunk);
+
+        let proof = compact_proof_from_bytes(&bytes[index..])?;
+
+        Ok(ProofOfPlaintextEquality { proof, C_1, C_2, C_3, C_2_a, C_2_b })
+    }
+
+    /// Serialise this [`ProofOfPlaintextEquality`] to a byte array.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::with_capacity(SIZEOF_PROOF_OF_PLAINTEXT_EQUALITY);
+
+        v.push(PROOF_FORMAT_VERSION);
+        v.extend(self.C_1.compress().to_bytes().iter());
+        v.extend(self.C_2.compress().to_bytes().iter());
+        v.extend(self.C_3.compress().to_bytes().iter());
+        v.extend(self.C_2_a.compress().to_bytes().iter());
+        v.extend(self.C_2_b.compress().to_bytes().iter());
+        compact_proof_to_bytes(&self.proof, &mut v);
+        v
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(ProofOfPlaintextEquality, "A valid byte sequence representing a ProofOfPlaintextEquality");
+
+/// An incredibly shitty and inelegant hashmap-like structure to store/"index"
+/// hidden scalar attributes during construction of a [`ProofOfValidCredential`].
+struct ProverHiddenScalars(Vec<(usize, ProverScalarVar)>);
+
+/// An incredibly shitty and inelegant hashmap-like structure to store/"index"
+/// corresponding hidden scalar basepoints during construction of a
+/// [`ProofOfValidCredential`].
+struct ProverHiddenScalarBasepoints(Vec<(usize, ProverPointVar)>);
+
+/// An incredibly shitty and inelegant hashmap-like structure to store/"index"
+/// hidden scalar attributes during verification of a [`ProofOfValidCredential`].
+struct VerifierHiddenScalars(Vec<(usize, VerifierScalarVar)>);
+
+/// An incredibly shitty and inelegant hashmap-like structure to store/"index"
+/// corresponding hidden scalar basepoints during verification of a
+/// [`ProofOfValidCredential`].
+struct VerifierHiddenScalarBasepoints(Vec<(usize, VerifierPointVar)>);
+
+/// An incredibly shitty and inelegant hashmap-like structure to store/"index"
+/// hidden scalar attributes during batch verification of [`ProofOfValidCredential`]s.
+struct BatchVerifierHiddenScalars(Vec<(usize, BatchVerifierScalarVar)>);
+
+/// An incredibly shitty and inelegant hashmap-like structure to store/"index"
+/// corresponding hidden scalar basepoints during batch verification of
+/// [`ProofOfValidCredential`]s.
+struct BatchVerifierHiddenScalarBasepoints(Vec<(usize, BatchVerifierPointVar)>);
+
+macro_rules! construct_hidden_scalar_variant {
+    ($scalar_type: ty, $basepoint_type: ty, $scalar_var: ty, $basepoint_var: ty) => {
+        impl Index<usize> for $scalar_type {
+            type Output = $scalar_var;
+
+            fn index(&self, i: usize) -> &Self::Output {
+                for (index, scalar) in self.0.iter() {
+                    if *index == i {
+                        return scalar;
+                    }
+                }
+                panic!()
+            }
+        }
+
+        impl $scalar_type {
+            pub(crate) fn push(&mut self, item: (usize, $scalar_var)) {
+                self.0.push(item);
+            }
+        }
+
+        impl Index<usize> for $basepoint_type {
+            type Output = $basepoint_var;
+
+            fn index(&self, i: usize) -> &Self::Output {
+                for (index, scalar) in self.0.iter() {
+                    if *index == i {
+                        return scalar;
+                    }
+                }
+                panic!()
+            }
+        }
+
+        impl $basepoint_type {
+            pub(crate) fn push(&mut self, item: (usize, $basepoint_var)) {
+                self.0.push(item);
+            }
+        }
+    }
+}
+
+construct_hidden_scalar_variant!(ProverHiddenScalars, ProverHiddenScalarBasepoints, ProverScalarVar, ProverPointVar);
+construct_hidden_scalar_variant!(VerifierHiddenScalars, VerifierHiddenScalarBasepoints, VerifierScalarVar, VerifierPointVar);
+construct_hidden_scalar_variant!(BatchVerifierHiddenScalars, BatchVerifierHiddenScalarBasepoints, BatchVerifierScalarVar, BatchVerifierPointVar);
+
+/// The number of bits in the range of values which a [`RangeProof`] covers.
+pub(crate) const RANGE_PROOF_BITS: usize = 64;
+
+/// The size of a serialised [`Predicate`], in bytes.
+///
+/// This is a tag, the index of the attribute, and the bound.
+const SIZEOF_PREDICATE: usize = 1 + 2 + 8;
+
+/// The size of a serialised [`RangeProof`], in bytes.
+///
+/// This is the predicate, a commitment to each bit, the challenge, and three
+/// scalars for each bit.
+pub(crate) const SIZEOF_RANGE_PROOF: usize = SIZEOF_PREDICATE + (32 * RANGE_PROOF_BITS) + 32 + (3 * 32 * RANGE_PROOF_BITS);
+
+const TAG_AT_LEAST: u8 = 0;
+const TAG_LESS_THAN: u8 = 1;
+
+/// A predicate over a hidden scalar attribute, which may be proven in
+/// zero-knowledge when presenting a credential without revealing the
+/// attribute itself.
+///
+/// Attributes are interpreted as unsigned 64-bit integers, i.e. they should be
+/// issued as e.g. `Attribute::SecretScalar(Scalar::from(age))`.  To prove
+/// that a value is strictly greater than some bound, e.g. that an expiry time
+/// is after now, use [`Predicate::AtLeast`] with the bound plus one.
+///
+/// Note that [`Predicate::AtLeast`] holds for any attribute of at least the
+/// bound, whereas [`Predicate::LessThan`] is only meaningful if the issuer
+/// ensured that the attribute is less than \( 2^{64} \).
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Predicate {
+    /// The attribute at `index` is greater than or equal to `bound`.
+    AtLeast { index: u16, bound: u64 },
+    /// The attribute at `index` is strictly less than `bound`.
+    LessThan { index: u16, bound: u64 },
+}
+
+impl Predicate {
+    /// The index of the attribute this predicate concerns.
+    pub fn index(&self) -> usize {
+        match self {
+            Predicate::AtLeast { index, .. }  => *index as usize,
+            Predicate::LessThan { index, .. } => *index as usize,
+        }
+    }
+
+    /// Compute a commitment, \( D \), to the value which must lie in
+    /// \( [0, 2^{64}) \) for this predicate to hold, from the commitment,
+    /// \( C_y_i = G_y_i * z + G_m_i * m_i \), to the attribute.
+    ///
+    /// For \( m_i \geq b \) this is \( C_y_i - G_m_i * b = G_y_i * z + G_m_i * (m_i - b) \),
+    /// and for \( m_i < b \) it is \( G_m_i * (b - 1) - C_y_i = G_y_i * -z + G_m_i * (b - 1 - m_i) \).
+    fn commitment(
+        &self,
+        system_parameters: &SystemParameters,
+        C_y_i: &RistrettoPoint,
+    ) -> RistrettoPoint
+    {
+        let G_m_i = system_parameters.G_m[self.index()];
+
+        match self {
+            Predicate::AtLeast { bound, .. }  => C_y_i - G_m_i * Scalar::from(*bound),
+            Predicate::LessThan { bound, .. } => G_m_i * (Scalar::from(*bound) - Scalar::one()) - C_y_i,
+        }
+    }
+
+    fn to_bytes(&self) -> [u8; SIZEOF_PREDICATE] {
+        let mut bytes = [0u8; SIZEOF_PREDICATE];
+
+        let (tag, index, bound) = match self {
+            Predicate::AtLeast { index, bound }  => (TAG_AT_LEAST, index, bound),
+            Predicate::LessThan { index, bound } => (TAG_LESS_THAN, index, bound),
+        };
+        bytes[0] = tag;
+        bytes[1..3].copy_from_slice(&index.to_le_bytes());
+        bytes[3..11].copy_from_slice(&bound.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Predicate, CredentialError> {
+        if bytes.len() != SIZEOF_PREDICATE {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut u16_bytes = [0u8; 2];
+        let mut u64_bytes = [0u8; 8];
+
+        u16_bytes.copy_from_slice(&bytes[1..3]);
+        u64_bytes.copy_from_slice(&bytes[3..11]);
+
+        let index = u16::from_le_bytes(u16_bytes);
+        let bound = u64::from_le_bytes(u64_bytes);
+
+        match bytes[0] {
+            TAG_AT_LEAST  => Ok(Predicate::AtLeast { index, bound }),
+            TAG_LESS_THAN => Ok(Predicate::LessThan { index, bound }),
+            _             => Err(CredentialError::BadAttribute),
+        }
+    }
+}
+
+/// A proof that a hidden scalar attribute satisfies a [`Predicate`].
+///
+/// The value committed to by [`Predicate::commitment`] is decomposed into
+/// [`RANGE_PROOF_BITS`] bits, \( v = \sigma{2^j * b_j} \), each of which is
+/// committed to as \( B_j = G_y_i * r_j + G_m_i * b_j \) and proven to be
+/// either zero or one with a disjunctive Schnorr proof.  The blinding factors
+/// are chosen such that \( \sigma{2^j * r_j} \) is the nonce \( z \) (or its
+/// negation) of the outer-lying [`ProofOfValidCredential`], so that the
+/// verifier may check that \( \sigma{2^j * B_j} = D \).  This binds the range
+/// proof to the very same commitment \( C_y_i \) whose opening is proven in
+/// the presentation.
+#[derive(Clone)]
+pub(crate) struct RangeProof {
+    predicate: Predicate,
+    commitments: Vec<RistrettoPoint>,
+    challenge: Scalar,
+    /// For each bit, the challenge for the case that the bit is zero, and the
+    /// responses for the cases that it is zero and one.
+    responses: Vec<(Scalar, Scalar, Scalar)>,
+}
+
+impl RangeProof {
+    /// Create a transcript for a range proof, bound to the `predicate`, the
+    /// attribute's commitment, and the commitments to the bits.
+    fn transcript(
+        system_parameters: &SystemParameters,
+        predicate: &Predicate,
+        C_y_i: &RistrettoPoint,
+        commitments: &[RistrettoPoint],
+    ) -> Transcript
+    {
+        let index = predicate.index();
+        let mut transcript = Transcript::new(b"2019/1416 range proof");
+
+        transcript.append_message(b"predicate", &predicate.to_bytes());
+        transcript.append_message(b"G_y", system_parameters.G_y[index].compress().as_bytes());
+        transcript.append_message(b"G_m", system_parameters.G_m[index].compress().as_bytes());
+        transcript.append_message(b"C_y", C_y_i.compress().as_bytes());
+
+        for B_j in commitments.iter() {
+            transcript.append_message(b"B", B_j.compress().as_bytes());
+        }
+        transcript
+    }
+
+    /// Derive the challenge once all the Schnorr commitments have been added.
+    fn challenge(transcript: &mut Transcript) -> Scalar {
+        let mut bytes = [0u8; 64];
+
+        transcript.challenge_bytes(b"c", &mut bytes);
+        Scalar::from_bytes_mod_order_wide(&bytes)
+    }
+
+    /// Create a [`RangeProof`] that the hidden scalar attribute `m` satisfies
+    /// the `predicate`.
+    ///
+    /// # Inputs
+    ///
+    /// * The `system_parameters`,
+    /// * The `predicate` to prove,
+    /// * The hidden scalar attribute, `m`, at the predicate's index,
+    /// * Its commitment, \( C_y_i \),
+    /// * The nonce, `z`, which must be reused from the outer-lying
+    ///   [`ProofOfValidCredential`],
+    /// * A `csprng`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`RangeProof`], otherwise
+    /// [`CredentialError::UnsatisfiedPredicate`] if `m` does not satisfy the
+    /// `predicate`.
+    fn prove<C>(
+        system_parameters: &SystemParameters,
+        predicate: &Predicate,
+        m: &Scalar,
+        C_y_i: &RistrettoPoint,
+        z: &Scalar,
+        csprng: &mut C,
+    ) -> Result<RangeProof, CredentialError>
+    where
+        C: RngCore + CryptoRng,
+    {
+        let index = predicate.index();
+        let G_y_i = system_parameters.G_y[index];
+        let G_m_i = system_parameters.G_m[index];
+
+        // The value which must lie in range, and the blinding factor of its commitment.
+        let (v, r) = match predicate {
+            Predicate::AtLeast { bound, .. }  => (m - Scalar::from(*bound), *z),
+            Predicate::LessThan { bound, .. } => (Scalar::from(*bound) - Scalar::one() - m, -z),
+        };
+        let v_bytes = v.to_bytes();
+
+        if v_bytes[RANGE_PROOF_BITS / 8..].iter().any(|byte| *byte != 0) {
+            return Err(CredentialError::UnsatisfiedPredicate);
+        }
+
+        // Choose the blinding factors for all but the last bit at random, and
+        // then the last such that they sum to r when weighted by powers of two.
+        let mut blindings: Vec<Scalar> = Vec::with_capacity(RANGE_PROOF_BITS);
+        let mut sum = Scalar::zero();
+        let mut power_of_two = Scalar::one();
+
+        for _ in 0..RANGE_PROOF_BITS - 1 {
+            let r_j = Scalar::random(csprng);
+
+            sum += r_j * power_of_two;
+            power_of_two += power_of_two;
+            blindings.push(r_j);
+        }
+        blindings.push((r - sum) * power_of_two.invert());
+
+        let mut bits: Vec<u8> = Vec::with_capacity(RANGE_PROOF_BITS);
+        let mut commitments: Vec<RistrettoPoint> = Vec::with_capacity(RANGE_PROOF_BITS);
+
+        for j in 0..RANGE_PROOF_BITS {
+            let bit = (v_bytes[j / 8] >> (j % 8)) & 1;
+
+            commitments.push(G_y_i * blindings[j] + G_m_i * Scalar::from(bit));
+            bits.push(bit);
+        }
+
+        let mut transcript = RangeProof::transcript(system_parameters, predicate, C_y_i, &commitments);
+
+        // For each bit, prove knowledge of the discrete log of either
+        // P_0 = B_j or P_1 = B_j - G_m_i w.r.t. G_y_i, by proving the true
+        // case honestly and simulating the other.
+        let mut nonces: Vec<(Scalar, Scalar, Scalar)> = Vec::with_capacity(RANGE_PROOF_BITS);
+
+        for j in 0..RANGE_PROOF_BITS {
+            let choice = Choice::from(bits[j]);
+            let k = Scalar::random(csprng);
+            let c_simulated = Scalar::random(csprng);
+            let s_simulated = Scalar::random(csprng);
+
+            // The simulated case is P_{1-b} = B_j - G_m_i * (1 - b).
+            let P_simulated = commitments[j] - G_m_i * Scalar::from(1 - bits[j]);
+            let R_real = G_y_i * k;
+            let R_simulated = G_y_i * s_simulated - P_simulated * c_simulated;
+
+            let R_0 = RistrettoPoint::conditional_select(&R_real, &R_simulated, choice);
+            let R_1 = RistrettoPoint::conditional_select(&R_simulated, &R_real, choice);
+
+            transcript.append_message(b"R_0", R_0.compress().as_bytes());
+            transcript.append_message(b"R_1", R_1.compress().as_bytes());
+            nonces.push((k, c_simulated, s_simulated));
+        }
+
+        let challenge = RangeProof::challenge(&mut transcript);
+        let mut responses: Vec<(Scalar, Scalar, Scalar)> = Vec::with_capacity(RANGE_PROOF_BITS);
+
+        for j in 0..RANGE_PROOF_BITS {
+            let choice = Choice::from(bits[j]);
+            let (k, c_simulated, s_simulated) = nonces[j];
+            let c_real = challenge - c_simulated;
+            let s_real = k + c_real * blindings[j];
+
+            let c_0 = Scalar::conditional_select(&c_real, &c_simulated, choice);
+            let s_0 = Scalar::conditional_select(&s_real, &s_simulated, choice);
+            let s_1 = Scalar::conditional_select(&s_simulated, &s_real, choice);
+
+            responses.push((c_0, s_0, s_1));
+        }
+
+        blindings.zeroize();
+
+        Ok(RangeProof { predicate: *predicate, commitments, challenge, responses })
+    }
+
+    /// Verify this [`RangeProof`] against the commitment, \( C_y_i \), to the
+    /// attribute at its predicate's index.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`].
+    fn verify(
+        &self,
+        system_parameters: &SystemParameters,
+        C_y_i: &RistrettoPoint,
+    ) -> Result<(), CredentialError>
+    {
+        if self.commitments.len() != RANGE_PROOF_BITS || self.responses.len() != RANGE_PROOF_BITS {
+            return Err(CredentialError::VerificationFailure);
+        }
+
+        let index = self.predicate.index();
+        let G_y_i = system_parameters.G_y[index];
+        let G_m_i = system_parameters.G_m[index];
+
+        // Check that the bits are a decomposition of the committed value,
+        // i.e. that \sigma{2^j * B_j} = D, using Horner's method.
+        let mut D = self.commitments[RANGE_PROOF_BITS - 1];
+
+        for B_j in self.commitments.iter().rev().skip(1) {
+            D = D + D + B_j;
+        }
+        if D != self.predicate.commitment(system_parameters, C_y_i) {
+            return Err(CredentialError::VerificationFailure);
+        }
+
+        let mut transcript = RangeProof::transcript(system_parameters, &self.predicate, C_y_i, &self.commitments);
+
+        for (B_j, (c_0, s_0, s_1)) in self.commitments.iter().zip(self.responses.iter()) {
+            let c_1 = self.challenge - c_0;
+            let R_0 = G_y_i * s_0 - B_j * c_0;
+            let R_1 = G_y_i * s_1 - (B_j - G_m_i) * c_1;
+
+            transcript.append_message(b"R_0", R_0.compress().as_bytes());
+            transcript.append_message(b"R_1", R_1.compress().as_bytes());
+        }
+
+        if RangeProof::challenge(&mut transcript) != self.challenge {
+            return Err(CredentialError::VerificationFailure);
+        }
+
+        Ok(())
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<RangeProof, CredentialError> {
+        if bytes.len() != SIZEOF_RANGE_PROOF {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let predicate = Predicate::from_bytes(&bytes[..SIZEOF_PREDICATE])?;
+
+        let mut index: usize = SIZEOF_PREDICATE;
+        let mut chunk = [0u8; 32];
+        let mut commitments: Vec<RistrettoPoint> = Vec::with_capacity(RANGE_PROOF_BITS);
+
+        for j in 0..RANGE_PROOF_BITS {
+            chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+            commitments.push(try_deserialise!(format!("B_{}", j), chunk));
+        }
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let challenge: Scalar = try_deserialise_scalar!("challenge", chunk);
+
+        let mut responses: Vec<(Scalar, Scalar, Scalar)> = Vec::with_capacity(RANGE_PROOF_BITS);
+
+        for j in 0..RANGE_PROOF_BITS {
+            chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+            let c_0: Scalar = try_deserialise_scalar!(format!("c_0_{}", j), chunk);
+
+            chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+            let s_0: Scalar = try_deserialise_scalar!(format!("s_0_{}", j), chunk);
+
+            chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+            let s_1: Scalar = try_deserialise_scalar!(format!("s_1_{}", j), chunk);
+
+            responses.push((c_0, s_0, s_1));
+        }
+
+        Ok(RangeProof { predicate, commitments, challenge, responses })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::with_capacity(SIZEOF_RANGE_PROOF);
+
+        v.extend(self.predicate.to_bytes().iter());
+
+        for B_j in self.commitments.iter() {
+            v.extend(B_j.compress().to_bytes().iter());
+        }
+
+        v.extend(self.challenge.as_bytes().iter());
+
+        for (c_0, s_0, s_1) in self.responses.iter() {
+            v.extend(c_0.as_bytes().iter());
+            v.extend(s_0.as_bytes().iter());
+            v.extend(s_1.as_bytes().iter());
+        }
+        v
+    }
+}
+
+/// A scope, such as a single service, within which presentations of a
+/// credential are linkable by a [`Pseudonym`] derived from one of its hidden
+/// scalar attributes, while remaining unlinkable across different scopes.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Scope {
+    /// The index of the hidden scalar attribute, \( k \), from which to derive the pseudonym.
+    pub index: u16,
+    /// A label for the scope, of at most 255 bytes, e.g. `b"example.com"`.
+    pub label: Vec<u8>,
+}
+
+impl Scope {
+    /// Compute the generator for this scope, \( H(scope) \), by hashing its label.
+    pub(crate) fn generator(&self) -> RistrettoPoint {
+        let mut input: Vec<u8> = Vec::with_capacity(25 + self.label.len());
+
+        input.extend(b"2019/1416 pseudonym scope".iter());
+        input.extend(self.label.iter());
+
+        RistrettoPoint::hash_from_bytes::<Sha512>(&input)
+    }
+}
+
+/// A pseudonym, \( H(scope) * k \), for the holder of a credential within
+/// some [`Scope`].
+///
+/// Every presentation of the same credential in the same scope has the same
+/// pseudonym, allowing a verifier to e.g. rate-limit or ban its holder, while
+/// pseudonyms in different scopes cannot be linked to one another.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Pseudonym(pub(crate) RistrettoPoint);
+
+impl Pseudonym {
+    /// Deserialise a [`Pseudonym`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Pseudonym, CredentialError> {
+        if bytes.len() != 32 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut chunk = [0u8; 32];
+
+        chunk.copy_from_slice(bytes);
+
+        Ok(Pseudonym(try_deserialise!("pseudonym", chunk)))
+    }
+
+    /// Serialise this [`Pseudonym`] to a byte array, e.g. for storage by a verifier.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.compress().to_bytes()
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(Pseudonym, "A valid byte sequence representing a Pseudonym");
+
+/// A serial number, \( H_s * s \), for a credential, derived from one of its
+/// hidden scalar attributes, \( s \), where \( H_s \) is a fixed generator.
+///
+/// Every presentation of the same credential with a serial number has the same
+/// one, in every context, so a verifier which stores the serial numbers it
+/// has seen can reject any credential which is presented more than once.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SerialNumber(pub(crate) RistrettoPoint);
+
+impl SerialNumber {
+    /// Compute the generator for serial numbers, \( H_s \).
+    pub(crate) fn generator() -> RistrettoPoint {
+        RistrettoPoint::hash_from_bytes::<Sha512>(b"2019/1416 serial number")
+    }
+
+    /// Deserialise a [`SerialNumber`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<SerialNumber, CredentialError> {
+        if bytes.len() != 32 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut chunk = [0u8; 32];
+
+        chunk.copy_from_slice(bytes);
+
+        Ok(SerialNumber(try_deserialise!("serial number", chunk)))
+    }
+
+    /// Serialise this [`SerialNumber`] to a byte array, e.g. for storage by a verifier.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.compress().to_bytes()
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(SerialNumber, "A valid byte sequence representing a SerialNumber");
+
+/// A challenge chosen by a verifier, to which a presentation is bound so that
+/// it cannot be replayed to another verifier or within another session.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct Challenge {
+    /// A nonce, chosen freshly by the verifier for each presentation.
+    pub nonce: [u8; 32],
+    /// The context of the presentation, of at most 65535 bytes, e.g. the
+    /// verifier's name and the action being authorised.
+    pub context: Vec<u8>,
+}
+
+impl Challenge {
+    /// Create a [`Challenge`] with a random nonce for the given `context`.
+    pub fn generate<C>(
+        context: &[u8],
+        csprng: &mut C,
+    ) -> Challenge
+    where
+        C: RngCore + CryptoRng,
+    {
+        let mut nonce = [0u8; 32];
+
+        csprng.fill_bytes(&mut nonce);
+
+        Challenge { nonce, context: context.to_vec() }
+    }
+
+    /// Absorb this [`Challenge`] into a `transcript`.
+    fn commit(&self, transcript: &mut Transcript) {
+        transcript.append_message(b"nonce", &self.nonce);
+        transcript.append_message(b"context", &self.context);
+    }
+
+    fn to_bytes(&self, v: &mut Vec<u8>) {
+        v.extend(self.nonce.iter());
+        v.extend((self.context.len() as u16).to_le_bytes().iter());
+        v.extend(self.context.iter());
+    }
+
+    /// Deserialise a [`Challenge`] from the start of `bytes`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the [`Challenge`] and the number of
+    /// bytes it occupied, otherwise a [`CredentialError`].
+    fn from_bytes(bytes: &[u8]) -> Result<(Challenge, usize), CredentialError> {
+        if bytes.len() < 32 + 2 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut nonce = [0u8; 32];
+        let mut u16_bytes = [0u8; 2];
+
+        nonce.copy_from_slice(&bytes[0..32]);
+        u16_bytes.copy_from_slice(&bytes[32..34]);
+        let context_length = u16::from_le_bytes(u16_bytes) as usize;
+
+        if bytes.len() < 34 + context_length {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let context = bytes[34..34+context_length].to_vec();
+
+        Ok((Challenge { nonce, context }, 34 + context_length))
+    }
+}
+
+/// The greatest [`RateLimit::limit`] which is accepted.
+///
+/// A [`RateLimitProof`] has a branch for every counter below the limit, so
+/// this bounds the work a verifier does for a presentation.
+pub const MAX_RATE_LIMIT: u16 = 1024;
+
+/// A limit on the number of times a credential may be presented to some
+/// verifier within an [`Epoch`].
+///
+/// Each presentation carries a [`Tag`], \( H(label, epoch, j) * s \), derived
+/// from a hidden scalar seed attribute, \( s \), and a counter,
+/// \( 0 \leq j < limit \), with a proof that the counter is in range.  A
+/// credential presented more than `limit` times within the epoch must repeat
+/// a tag, while tags for different counters, epochs, or labels are unlinkable.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct RateLimit {
+    /// The index of the hidden scalar seed attribute, \( s \).
+    pub index: u16,
+    /// A label for the verifier, of at most 255 bytes, e.g. `b"example.com"`.
+    pub label: Vec<u8>,
+    /// The epoch within which presentations are counted.
+    pub epoch: Epoch,
+    /// The number of presentations allowed per epoch, \( k \), which is at
+    /// most [`MAX_RATE_LIMIT`].
+    pub limit: u16,
+}
+
+impl RateLimit {
+    /// Compute the generator for the `j`th tag, \( H(label, epoch, j) \).
+    fn generator(&self, j: u16) -> RistrettoPoint {
+        let mut input: Vec<u8> = Vec::with_capacity(24 + 8 + 2 + self.label.len());
+
+        input.extend(b"2019/1416 rate limit tag".iter());
+        input.extend(self.epoch.to_le_bytes().iter());
+        input.extend(j.to_le_bytes().iter());
+        input.extend(self.label.iter());
+
+        RistrettoPoint::hash_from_bytes::<Sha512>(&input)
+    }
+
+    fn to_bytes(&self, v: &mut Vec<u8>) {
+        v.extend(self.index.to_le_bytes().iter());
+        v.push(self.label.len() as u8);
+        v.extend(self.label.iter());
+        v.extend(self.epoch.to_le_bytes().iter());
+        v.extend(self.limit.to_le_bytes().iter());
+    }
+
+    /// Deserialise a [`RateLimit`] from the start of `bytes`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the [`RateLimit`] and the number of
+    /// bytes it occupied, otherwise a [`CredentialError`].
+    fn from_bytes(bytes: &[u8]) -> Result<(RateLimit, usize), CredentialError> {
+        if bytes.len() < 3 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut u16_bytes = [0u8; 2];
+        let mut u64_bytes = [0u8; 8];
+
+        u16_bytes.copy_from_slice(&bytes[0..2]);
+        let index = u16::from_le_bytes(u16_bytes);
+        let label_length = bytes[2] as usize;
+
+        if bytes.len() < 3 + label_length + 8 + 2 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let label = bytes[3..3+label_length].to_vec();
+        let mut offset = 3 + label_length;
+
+        u64_bytes.copy_from_slice(&bytes[offset..offset+8]); offset += 8;
+        u16_bytes.copy_from_slice(&bytes[offset..offset+2]); offset += 2;
+
+        let epoch = u64::from_le_bytes(u64_bytes);
+        let limit = u16::from_le_bytes(u16_bytes);
+
+        Ok((RateLimit { index, label, epoch, limit }, offset))
+    }
+}
+
+/// A rate-limiting tag, \( H(label, epoch, j) * s \), which a verifier should
+/// check it has not seen before within the epoch.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Tag(pub(crate) RistrettoPoint);
+
+impl Tag {
+    /// Deserialise a [`Tag`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Tag, CredentialError> {
+        if bytes.len() != 32 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut chunk = [0u8; 32];
+
+        chunk.copy_from_slice(bytes);
+
+        Ok(Tag(try_deserialise!("tag", chunk)))
+    }
+
+    /// Serialise this [`Tag`] to a byte array, e.g. for storage by a verifier.
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0.compress().to_bytes()
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(Tag, "A valid byte sequence representing a Tag");
+
+/// A proof that a [`Tag`] was derived from the hidden scalar attribute
+/// committed to in \( C_y_i \) and some counter less than the limit.
+///
+/// For each possible counter \( j \), the statement is knowledge of
+/// \( (s, z) \) such that \( T = H_j * s \) and
+/// \( C_y_i = G_y_i * z + G_m_i * s \).  The true case is proven honestly
+/// and all others are simulated, such that the verifier learns only that one
+/// of them holds.  Since \( C_y_i \) is binding, \( s \) is the very
+/// attribute whose opening is proven in the outer-lying
+/// [`ProofOfValidCredential`].
+#[derive(Clone)]
+pub(crate) struct RateLimitProof {
+    rate_limit: RateLimit,
+    tag: Tag,
+    /// For each counter, the challenge and the responses for \( s \) and \( z \).
+    responses: Vec<(Scalar, Scalar, Scalar)>,
+}
+
+impl RateLimitProof {
+    /// Create a transcript for a rate limit proof, bound to the `rate_limit`,
+    /// the attribute's commitment, and the `tag`.
+    fn transcript(
+        system_parameters: &SystemParameters,
+        rate_limit: &RateLimit,
+        C_y_i: &RistrettoPoint,
+        tag: &Tag,
+    ) -> Transcript
+    {
+        let index = rate_limit.index as usize;
+        let mut transcript = Transcript::new(b"2019/1416 rate limit proof");
+        let mut bytes: Vec<u8> = Vec::new();
+
+        rate_limit.to_bytes(&mut bytes);
+
+        transcript.append_message(b"rate limit", &bytes);
+        transcript.append_message(b"G_y", system_parameters.G_y[index].compress().as_bytes());
+        transcript.append_message(b"G_m", system_parameters.G_m[index].compress().as_bytes());
+        transcript.append_message(b"C_y", C_y_i.compress().as_bytes());
+        transcript.append_message(b"T", tag.0.compress().as_bytes());
+        transcript
+    }
+
+    /// Create a [`RateLimitProof`] for the `j`th presentation of the hidden
+    /// scalar seed attribute `s` within the `rate_limit`'s epoch.
+    ///
+    /// # Inputs
+    ///
+    /// * The `system_parameters`,
+    /// * The `rate_limit`,
+    /// * The counter, `j`,
+    /// * The seed attribute, `s`, and its commitment, \( C_y_i \),
+    /// * The nonce, `z`, which must be reused from the outer-lying
+    ///   [`ProofOfValidCredential`],
+    /// * A `csprng`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`RateLimitProof`], otherwise
+    /// [`CredentialError::UnsatisfiedPredicate`] if `j` is not less than the limit.
+    fn prove<C>(
+        system_parameters: &SystemParameters,
+        rate_limit: &RateLimit,
+        j: u16,
+        s: &Scalar,
+        C_y_i: &RistrettoPoint,
+        z: &Scalar,
+        csprng: &mut C,
+    ) -> Result<RateLimitProof, CredentialError>
+    where
+        C: RngCore + CryptoRng,
+    {
+        if j >= rate_limit.limit {
+            return Err(CredentialError::UnsatisfiedPredicate);
+        }
+
+        let index = rate_limit.index as usize;
+        let G_y_i = system_parameters.G_y[index];
+        let G_m_i = system_parameters.G_m[index];
+        let tag = Tag(rate_limit.generator(j) * s);
+
+        let mut transcript = RateLimitProof::transcript(system_parameters, rate_limit, C_y_i, &tag);
+
+        // For each counter, commit honestly if it is the true one and
+        // otherwise simulate, without branching on which is which.
+        let k_s = Scalar::random(csprng);
+        let k_z = Scalar::random(csprng);
+        let mut simulations: Vec<(Choice, Scalar, Scalar, Scalar)> = Vec::with_capacity(rate_limit.limit as usize);
+
+        for b in 0..rate_limit.limit {
+            let choice = b.ct_eq(&j);
+            let H_b = rate_limit.generator(b);
+            let c_simulated = Scalar::random(csprng);
+            let s_simulated = Scalar::random(csprng);
+            let z_simulated = Scalar::random(csprng);
+
+            let R_T_real = H_b * k_s;
+            let R_C_real = G_y_i * k_z + G_m_i * k_s;
+            let R_T_simulated = H_b * s_simulated - tag.0 * c_simulated;
+            let R_C_simulated = G_y_i * z_simulated + G_m_i * s_simulated - C_y_i * c_simulated;
+
+            let R_T = RistrettoPoint::conditional_select(&R_T_simulated, &R_T_real, choice);
+            let R_C = RistrettoPoint::conditional_select(&R_C_simulated, &R_C_real, choice);
+
+            transcript.append_message(b"R_T", R_T.compress().as_bytes());
+            transcript.append_message(b"R_C", R_C.compress().as_bytes());
+            simulations.push((choice, c_simulated, s_simulated, z_simulated));
+        }
+
+        let challenge = RangeProof::challenge(&mut transcript);
+        let mut c_real = challenge;
+
+        for (choice, c_simulated, _, _) in simulations.iter() {
+            c_real -= Scalar::conditional_select(c_simulated, &Scalar::zero(), *choice);
+        }
+
+        let s_real = k_s + c_real * s;
+        let z_real = k_z + c_real * z;
+        let responses = simulations.iter().map(|(choice, c_simulated, s_simulated, z_simulated)| {
+            (Scalar::conditional_select(c_simulated, &c_real, *choice),
+             Scalar::conditional_select(s_simulated, &s_real, *choice),
+             Scalar::conditional_select(z_simulated, &z_real, *choice))
+        }).collect();
+
+        Ok(RateLimitProof { rate_limit: rate_limit.clone(), tag, responses })
+    }
+
+    /// Verify this [`RateLimitProof`] against the commitment, \( C_y_i \),
+    /// to the seed attribute.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`].
+    fn verify(
+        &self,
+        system_parameters: &SystemParameters,
+        C_y_i: &RistrettoPoint,
+    ) -> Result<(), CredentialError>
+    {
+        if self.responses.len() != self.rate_limit.limit as usize {
+            return Err(CredentialError::VerificationFailure);
+        }
+
+        let index = self.rate_limit.index as usize;
+        let G_y_i = system_parameters.G_y[index];
+        let G_m_i = system_parameters.G_m[index];
+
+        let mut transcript = RateLimitProof::transcript(system_parameters, &self.rate_limit, C_y_i, &self.tag);
+        let mut sum = Scalar::zero();
+
+        for (b, (c_b, s_b, z_b)) in self.responses.iter().enumerate() {
+            let H_b = self.rate_limit.generator(b as u16);
+            let R_T = H_b * s_b - self.tag.0 * c_b;
+            let R_C = G_y_i * z_b + G_m_i * s_b - C_y_i * c_b;
+
+            transcript.append_message(b"R_T", R_T.compress().as_bytes());
+            transcript.append_message(b"R_C", R_C.compress().as_bytes());
+            sum += c_b;
+        }
+
+        if RangeProof::challenge(&mut transcript) != sum {
+            return Err(CredentialError::VerificationFailure);
+        }
+
+        Ok(())
+    }
+
+    /// Deserialise a [`RateLimitProof`] from the start of `bytes`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the [`RateLimitProof`] and the number of
+    /// bytes it occupied, otherwise a [`CredentialError`].
+    fn from_bytes(bytes: &[u8]) -> Result<(RateLimitProof, usize), CredentialError> {
+        let (rate_limit, mut index) = RateLimit::from_bytes(bytes)?;
+
+        if bytes.len() < index + 32 + 96 * rate_limit.limit as usize {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let tag = Tag::from_bytes(&bytes[index..index+32])?; index += 32;
+
+        let mut chunk = [0u8; 32];
+        let mut responses: Vec<(Scalar, Scalar, Scalar)> = Vec::with_capacity(rate_limit.limit as usize);
+
+        for b in 0..rate_limit.limit {
+            chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+            let c_b: Scalar = try_deserialise_scalar!(format!("c_{}", b), chunk);
+
+            chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+            let s_b: Scalar = try_deserialise_scalar!(format!("s_{}", b), chunk);
+
+            chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+            let z_b: Scalar = try_deserialise_scalar!(format!("z_{}", b), chunk);
+
+            responses.push((c_b, s_b, z_b));
+        }
+
+        Ok((RateLimitProof { rate_limit, tag, responses }, index))
+    }
+
+    fn to_bytes(&self, v: &mut Vec<u8>) {
+        self.rate_limit.to_bytes(v);
+        v.extend(self.tag.to_bytes().iter());
+
+        for (c_b, s_b, z_b) in self.responses.iter() {
+            v.extend(c_b.as_bytes().iter());
+            v.extend(s_b.as_bytes().iter());
+            v.extend(z_b.as_bytes().iter());
+        }
+    }
+}
+
+/// Hidden attributes of a presentation which are additionally encrypted to an
+/// auditor's ElGamal public key, e.g. for abuse handling, such that only the
+/// auditor may later de-anonymise the credential's holder.
+///
+/// The auditor decrypts a hidden group element attribute to its encoding,
+/// \( M_1 \), which may be decoded with
+/// [`crate::encoding::decode_from_group`], and a hidden scalar attribute to
+/// \( G_m_i * m_i \), which may be compared against known values.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Escrow {
+    /// The auditor's ElGamal public key.
+    pub public_key: ElGamalPublicKey,
+    /// The indices of the hidden attributes to encrypt, of which there may be
+    /// at most 255.
+    pub indices: Vec<u16>,
+}
+
+/// A proof that each of an [`Escrow`]'s ciphertexts encrypts the attribute
+/// committed to in the corresponding \( C_y_i \).
+///
+/// Every hidden attribute's commitment is \( C_y_i = G_y_i * z + M_i \), where
+/// \( M_i \) is either \( G_m_i * m_i \) or \( M_1 \), so the ciphertext
+/// \( (E_i1, E_i2) = (G * r_i, M_i + A * r_i) \) to the auditor's key
+/// \( A \) satisfies \( C_y_i - E_i2 = G_y_i * z - A * r_i \) without the
+/// prover needing to know the discrete log of \( M_i \).  The nonce \( z \)
+/// is bound to that of the outer-lying [`ProofOfValidCredential`] by proving
+/// \( Z = I * z \) here as well.
+#[derive(Clone)]
+pub(crate) struct ProofOfEscrow {
+    escrow: Escrow,
+    ciphertexts: Vec<ElGamalCiphertext>,
+    proof: CompactProof,
+}
+
+impl ProofOfEscrow {
+    /// Encrypt the attributes committed to in `C_y` at the `escrow`'s indices,
+    /// and prove that the ciphertexts are correct.
+    ///
+    /// # Inputs
+    ///
+    /// * The `system_parameters` and `issuer_parameters`,
+    /// * The `escrow`, whose indices must be those of hidden attributes,
+    /// * The commitments, `C_y`, and the nonce, `z`, of the outer-lying
+    ///   [`ProofOfValidCredential`],
+    /// * A `csprng`.
+    fn prove<C>(
+        system_parameters: &SystemParameters,
+        issuer_parameters: &IssuerParameters,
+        escrow: &Escrow,
+        C_y: &[RistrettoPoint],
+        z: &Scalar,
+        csprng: &mut C,
+    ) -> ProofOfEscrow
+    where
+        C: RngCore + CryptoRng,
+    {
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+        let mut prover = Prover::new(b"2019/1416 escrow proof", &mut transcript);
+
+        let z_ = z;
+        let z = prover.allocate_scalar(b"z", *z_);
+        let (G, _) = prover.allocate_point(b"G", system_parameters.G);
+        let (A, _) = prover.allocate_point(b"-A", -escrow.public_key.D);
+        let (I, _) = prover.allocate_point(b"I", issuer_parameters.I);
+        let (Z, _) = prover.allocate_point(b"Z", issuer_parameters.I * z_);
+
+        // Constraint #1: The nonce is that of the presentation.
+        //                Z = I * z
+        prover.constrain(Z, vec![(z, I)]);
+
+        let mut ciphertexts: Vec<ElGamalCiphertext> = Vec::with_capacity(escrow.indices.len());
+
+        for index in escrow.indices.iter() {
+            let i = *index as usize;
+            let r_i = Scalar::random(csprng);
+            let M_i = C_y[i] - system_parameters.G_y[i] * z_;
+            let E_i = escrow.public_key.encrypt_with_nonce(system_parameters, &M_i, &r_i);
+
+            let r        = prover.allocate_scalar(b"r", r_i);
+            let (G_y, _) = prover.allocate_point(b"G_y", system_parameters.G_y[i]);
+            let (E_1, _) = prover.allocate_point(b"E_1", E_i.C1);
+            let (D, _)   = prover.allocate_point(b"C_y-E_2", C_y[i] - E_i.C2);
+
+            // Constraint #2: The encryption nonce is known.
+            //                E_i1 = G * r_i
+            prover.constrain(E_1, vec![(r, G)]);
+
+            // Constraint #3: The ciphertext encrypts the committed attribute.
+            //                C_y_i - E_i2 = G_y_i * z - A * r_i
+            prover.constrain(D, vec![(z, G_y), (r, A)]);
+
+            ciphertexts.push(E_i);
+        }
+
+        ProofOfEscrow { escrow: escrow.clone(), ciphertexts, proof: prover.prove_compact() }
+    }
+
+    /// Verify this [`ProofOfEscrow`] against the `issuer`'s recomputation,
+    /// `Z`, of the presentation's \( Z \), and its commitments, `C_y`.
+    fn verify(
+        &self,
+        issuer: &Issuer,
+        Z: &RistrettoPoint,
+        C_y: &[RistrettoPoint],
+    ) -> Result<(), CredentialError>
+    {
+        if self.ciphertexts.len() != self.escrow.indices.len() {
+            return Err(CredentialError::VerificationFailure);
+        }
+
+        let system_parameters = &issuer.system_parameters;
+
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+        let mut verifier = Verifier::new(b"2019/1416 escrow proof", &mut transcript);
+
+        let z = verifier.allocate_scalar(b"z");
+        let G = verifier.allocate_point(b"G", system_parameters.G.compress())?;
+        let A = verifier.allocate_point(b"-A", (-self.escrow.public_key.D).compress())?;
+        let I = verifier.allocate_point(b"I", issuer.issuer_parameters.I.compress())?;
+        let Z = verifier.allocate_point(b"Z", Z.compress())?;
+
+        // Constraint #1: Z = I * z
+        verifier.constrain(Z, vec![(z, I)]);
+
+        for (index, E_i) in self.escrow.indices.iter().zip(self.ciphertexts.iter()) {
+            let i = *index as usize;
+
+            let r   = verifier.allocate_scalar(b"r");
+            let G_y = verifier.allocate_point(b"G_y", system_parameters.G_y[i].compress())?;
+            let E_1 = verifier.allocate_point(b"E_1", E_i.C1.compress())?;
+            let D   = verifier.allocate_point(b"C_y-E_2", (C_y[i] - E_i.C2).compress())?;
+
+            // Constraint #2: E_i1 = G * r_i
+            verifier.constrain(E_1, vec![(r, G)]);
+
+            // Constraint #3: C_y_i - E_i2 = G_y_i * z - A * r_i
+            verifier.constrain(D, vec![(z, G_y), (r, A)]);
+        }
+
+        verifier.verify_compact(&self.proof).or_else(|_| Err(CredentialError::VerificationFailure))
+    }
+
+    /// Deserialise a [`ProofOfEscrow`] from the start of `bytes`.
+    ///
+    /// The format is the auditor's public key, the number of escrowed
+    /// attributes, and for each its index as two little-endian bytes and its
+    /// ciphertext, followed by the proof.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the [`ProofOfEscrow`] and the number of
+    /// bytes it occupied, otherwise a [`CredentialError`].
+    fn from_bytes(bytes: &[u8]) -> Result<(ProofOfEscrow, usize), CredentialError> {
+        if bytes.len() < 33 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let public_key = ElGamalPublicKey::from_bytes(&bytes[..32])?;
+        let number_of_ciphertexts = bytes[32] as usize;
+        let mut index: usize = 33;
+
+        // The proof has responses for z and each nonce.
+        let length = index + (2 + 64) * number_of_ciphertexts + 32 * (2 + number_of_ciphertexts);
+
+        if bytes.len() < length {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut indices: Vec<u16> = Vec::with_capacity(number_of_ciphertexts);
+        let mut ciphertexts: Vec<ElGamalCiphertext> = Vec::with_capacity(number_of_ciphertexts);
+        let mut u16_bytes = [0u8; 2];
+
+        for _ in 0..number_of_ciphertexts {
+            u16_bytes.copy_from_slice(&bytes[index..index+2]); index += 2;
+            indices.push(u16::from_le_bytes(u16_bytes));
+            ciphertexts.push(ElGamalCiphertext::from_bytes(&bytes[index..index+64])?); index += 64;
+        }
+
+        let proof = compact_proof_from_bytes(&bytes[index..length])?;
+
+        Ok((ProofOfEscrow { escrow: Escrow { public_key, indices }, ciphertexts, proof }, length))
+    }
+
+    fn to_bytes(&self, v: &mut Vec<u8>) {
+        v.extend(self.escrow.public_key.to_bytes().iter());
+        v.push(self.ciphertexts.len() as u8);
+
+        for (index, E_i) in self.escrow.indices.iter().zip(self.ciphertexts.iter()) {
+            v.extend(index.to_le_bytes().iter());
+            v.extend(E_i.to_bytes().iter());
+        }
+
+        compact_proof_to_bytes(&self.proof, v);
+    }
+}
+
+/// Additional statements which a [`ProofOfValidCredential`] may prove about
+/// the credential being presented.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct PresentationOptions {
+    /// Predicates over hidden scalar attributes, each proven with a range proof.
+    pub predicates: Vec<Predicate>,
+    /// A scope within which to present a [`Pseudonym`].
+    pub scope: Option<Scope>,
+    /// The index of a hidden scalar attribute from which to derive a [`SerialNumber`].
+    pub serial_number: Option<u16>,
+    /// A verifier's challenge to which to bind the presentation.
+    pub challenge: Option<Challenge>,
+    /// A [`RateLimit`] within which to present a [`Tag`], along with the
+    /// counter, \( j \), of this presentation within its epoch.
+    pub rate_limit: Option<(RateLimit, u16)>,
+    /// Hidden attributes to additionally encrypt to an auditor.
+    pub escrow: Option<Escrow>,
+}
+
+/// The commitments to, and layout of the attributes of, a presented
+/// credential, along with the proofs of encryption of its hidden group element
+/// attributes.  These are common to a [`ProofOfValidCredential`] and to each
+/// of the credentials in a [`ProofOfLinkedCredentials`].
+struct CredentialCommitments {
+    key_id: KeyId,
+    proofs_of_encryption: Vec<(u16, ProofOfEncryption)>,
+    encrypted_attributes: Vec<EncryptedAttribute>,
+    C_x_0: RistrettoPoint,
+    C_x_1: RistrettoPoint,
+    C_V:   RistrettoPoint,
+    C_y: Vec<RistrettoPoint>,
+}
+
+impl CredentialCommitments {
+    /// Commit to the `credential` with a fresh nonce, and encrypt its hidden
+    /// group element attributes under the `keypair`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a tuple of the [`CredentialCommitments`],
+    /// the nonce \( z \), and \( Z = I * z \), otherwise a [`CredentialError`],
+    /// which is [`CredentialError::NoSymmetricKey`] if the credential has
+    /// hidden group element attributes but no `keypair` was given.
+    fn commit<C>(
+        system_parameters: &SystemParameters,
+        issuer_parameters: &IssuerParameters,
+        credential: &AnonymousCredential,
+        keypair: Option<&SymmetricKeypair>,
+        csprng: &mut C,
+    ) -> Result<(CredentialCommitments, Scalar, RistrettoPoint), CredentialError>
+    where
+        C: RngCore + CryptoRng,
+    {
+        // If a keypair was not supplied and we have encrypted group element attributes, bail early.
+        if keypair.is_none() {
+            for attribute in credential.attributes.iter() {
+                match attribute {
+                    Attribute::SecretPoint(_) => return Err(CredentialError::NoSymmetricKey),
+                    _ => continue,
+                }
+            }
+        }
+
+        let NUMBER_OF_ATTRIBUTES = system_parameters.NUMBER_OF_ATTRIBUTES as usize;
+
+        if credential.attributes.len() > NUMBER_OF_ATTRIBUTES {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+
+        // Rebuild the attributes for our credential to send to the verifier.
+        //
+        // Attributes which were not present upon issuance contribute nothing to
+        // the AMAC, and so they are presented as revealed scalar attributes
+        // equal to zero, in order for the verifier to be able to account for
+        // their G_y_i * y_i terms in the issuer parameters.
+        let mut encrypted_attributes: Vec<EncryptedAttribute> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+
+        for attribute in credential.attributes.iter() {
+            match attribute {
+                Attribute::PublicScalar(x) => encrypted_attributes.push(EncryptedAttribute::PublicScalar(*x)),
+                Attribute::SecretScalar(_) => encrypted_attributes.push(EncryptedAttribute::SecretScalar),
+                Attribute::PublicPoint(x)  => encrypted_attributes.push(EncryptedAttribute::PublicPoint(*x)),
+                Attribute::SecretPoint(_)  => encrypted_attributes.push(EncryptedAttribute::SecretPoint),
+            }
+        }
+        for _ in credential.attributes.len()..NUMBER_OF_ATTRIBUTES {
+            encrypted_attributes.push(EncryptedAttribute::PublicScalar(Scalar::zero()));
+        }
+
+        // Choose a nonce for the commitments, and commit to the credential attributes.
+        let z: Scalar = Scalar::random(csprng);
+        let mut C_y: Vec<RistrettoPoint> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+
+        for i in 0..NUMBER_OF_ATTRIBUTES {
+            match credential.attributes.get(i) {
+                Some(Attribute::SecretPoint(p))  => C_y.push(system_parameters.G_y[i] * z + p.M1),
+                Some(Attribute::SecretScalar(m)) => C_y.push(system_parameters.G_y[i] * z + system_parameters.G_m[i] * m),
+                _                                => C_y.push(system_parameters.G_y[i] * z),
+            };
+        }
+
+        // Construct proofs of correct encryptions for the hidden group attributes.
+        //
+        // Each proof of encryption only shows that C_y_i = G_y_i * z' + M_i for
+        // some z', so alone it would let a prover encrypt M_i + G_y_i * (z - z')
+        // instead of M_i.  The presentation proof therefore also proves
+        // C_y_i - E2 = G_y_i * z - E1 * a with its own z, and knowledge of a
+        // w.r.t. pk, so that the ciphertext decrypts to the attribute committed
+        // to under the nonce of the AMAC.
+        let mut proofs_of_encryption: Vec<(u16, ProofOfEncryption)> = Vec::new();
+
+        for (i, attribute) in credential.attributes.iter().enumerate() {
+            if let Attribute::SecretPoint(pt) = attribute {
+                let keypair = keypair.ok_or(CredentialError::NoSymmetricKey)?;
+
+                proofs_of_encryption.push((i as u16, ProofOfEncryption::prove(&system_parameters, &pt, i as u16,
+                                                                              &keypair, &z, csprng)));
+            }
+        }
+
+        let commitments = CredentialCommitments {
+            key_id: issuer_parameters.key_id(),
+            proofs_of_encryption: proofs_of_encryption,
+            encrypted_attributes: encrypted_attributes,
+            C_x_0: (system_parameters.G_x_0 * z) +  credential.amac.U,
+            C_x_1: (system_parameters.G_x_1 * z) + (credential.amac.U * credential.amac.t),
+            C_V:   (system_parameters.G_V   * z) +  credential.amac.V,
+            C_y: C_y,
+        };
+
+        Ok((commitments, z, issuer_parameters.I * z))
+    }
+
+    /// The number of hidden scalar attributes.
+    fn number_of_hidden_scalars(&self) -> usize {
+        self.encrypted_attributes.iter().filter(|attribute| {
+            match attribute {
+                EncryptedAttribute::SecretScalar => true,
+                _ => false,
+            }
+        }).count()
+    }
+
+    /// The number of hidden group element attributes.
+    fn number_of_hidden_points(&self) -> usize {
+        self.encrypted_attributes.iter().filter(|attribute| {
+            match attribute {
+                EncryptedAttribute::SecretPoint => true,
+                _ => false,
+            }
+        }).count()
+    }
+
+    /// Check that these [`CredentialCommitments`] are well-formed w.r.t. the
+    /// `issuer`, i.e. that they claim the issuer's key, that they have the
+    /// right number of attributes and exactly one [`ProofOfEncryption`] for
+    /// each hidden group element attribute, in order, under the same
+    /// symmetric key, and bound to the same commitment.
+    fn check_structure(
+        &self,
+        issuer: &Issuer,
+    ) -> Result<(), CredentialError>
+    {
+        let NUMBER_OF_ATTRIBUTES = issuer.system_parameters.NUMBER_OF_ATTRIBUTES as usize;
+
+        if self.key_id != issuer.issuer_parameters.key_id() {
+            return Err(CredentialError::UnknownKey);
+        }
+
+        if self.encrypted_attributes.len() != NUMBER_OF_ATTRIBUTES || self.C_y.len() != NUMBER_OF_ATTRIBUTES {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+
+        if self.proofs_of_encryption.len() != self.number_of_hidden_points() {
+            return Err(CredentialError::MissingData);
+        }
+
+        for (j, (i, proof_of_encryption)) in self.proofs_of_encryption.iter().enumerate() {
+            let index = *i as usize;
+
+            if proof_of_encryption.index != *i || index >= NUMBER_OF_ATTRIBUTES {
+                return Err(CredentialError::VerificationFailure);
+            }
+            // The proofs of encryption are ordered by attribute, and are all
+            // under the one symmetric key whose knowledge is proven alongside
+            // the commitments.
+            if j > 0 && (self.proofs_of_encryption[j - 1].0 >= *i ||
+                         self.proofs_of_encryption[0].1.public_key.pk != proof_of_encryption.public_key.pk) {
+                return Err(CredentialError::VerificationFailure);
+            }
+            match self.encrypted_attributes[index] {
+                EncryptedAttribute::SecretPoint => (),
+                _ => return Err(CredentialError::VerificationFailure),
+            }
+            if proof_of_encryption.C_y_1 != self.C_y[index] {
+                return Err(CredentialError::VerificationFailure);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Recompute the prover's Z value.
+    ///
+    /// Let \( \mathcal{H} \) denote the set of hidden attributes, both those
+    /// which are group elements and those which are scalars.
+    ///
+    /// Let \( M_i \) be a revealed group element attribute, if so, and
+    /// otherwise if a revealed scalar attribute, \( m_i \), then let
+    /// \( M_i = G_m_i * m_i \).
+    ///
+    /// \( Z = (W + C_x0 * x0 + C_x1 * x1 + \sigma_{i \in \mathcal{H}}{C_y_i * y_i} +
+    ///      \sigma_{i \notin \mathcal{H}}{(C_y_i + M_i) * y_i}) - C_V \)
+    ///
+    /// (The issuer parameter I is defined as
+    /// \( -G_V + G_x_0 * x0 + G_x_1 * x1 + \sigma{G_y_i * y_i} \),
+    /// hence the subtraction of C_V rather than of the rest.)
+    fn compute_Z(
+        &self,
+        issuer: &Issuer,
+    ) -> RistrettoPoint
+    {
+        let mut Z_ = issuer.amacs_key.W + self.C_x_0 * issuer.amacs_key.x_0 + self.C_x_1 * issuer.amacs_key.x_1 - self.C_V;
+
+        for (i, attribute) in self.encrypted_attributes.iter().enumerate() {
+            match attribute {
+                EncryptedAttribute::PublicScalar(m_i) => Z_ += (self.C_y[i] + (issuer.system_parameters.G_m[i] * m_i)) * issuer.amacs_key.y[i],
+                EncryptedAttribute::SecretScalar      => Z_ +=  self.C_y[i]                                            * issuer.amacs_key.y[i],
+                EncryptedAttribute::PublicPoint(M_i)  => Z_ += (self.C_y[i] + M_i)                                     * issuer.amacs_key.y[i],
+                EncryptedAttribute::SecretPoint       => Z_ +=  self.C_y[i]                                            * issuer.amacs_key.y[i],
+            }
+        }
+        Z_
+    }
+
+    /// Deserialise [`CredentialCommitments`] from the start of `bytes`.
+    ///
+    /// The format is the issuer's [`KeyId`], the number of attributes `n`, the
+    /// `n` tagged attributes, the commitments \( (C_x_0, C_x_1, C_V) \), the
+    /// `n` commitments \( C_y \), and the number of proofs of encryption
+    /// followed by each of them.  All points are compressed.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a tuple of the [`CredentialCommitments`]
+    /// and the number of bytes which were consumed, otherwise a [`CredentialError`].
+    fn from_bytes(bytes: &[u8]) -> Result<(CredentialCommitments, usize), CredentialError> {
+        if bytes.len() < 1 + SIZEOF_KEY_ID {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut key_id = KeyId([0u8; SIZEOF_KEY_ID]);
+
+        key_id.0.copy_from_slice(&bytes[..SIZEOF_KEY_ID]);
+
+        let NUMBER_OF_ATTRIBUTES: usize = bytes[SIZEOF_KEY_ID] as usize;
+        let mut index: usize = 1 + SIZEOF_KEY_ID;
+        let mut chunk = [0u8; 32];
+
+        let mut encrypted_attributes: Vec<EncryptedAttribute> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+
+        for i in 0..NUMBER_OF_ATTRIBUTES {
+            if bytes.len() < index + 1 {
+                return Err(CredentialError::WrongNumberOfBytes);
+            }
+            let tag = bytes[index]; index += 1;
+
+            if tag == TAG_PUBLIC_SCALAR || tag == TAG_PUBLIC_POINT {
+                if bytes.len() < index + 32 {
+                    return Err(CredentialError::WrongNumberOfBytes);
+                }
+                chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+            }
+
+            match tag {
+                TAG_PUBLIC_SCALAR => encrypted_attributes.push(EncryptedAttribute::PublicScalar(try_deserialise_scalar!(format!("m_{}", i), chunk))),
+                TAG_SECRET_SCALAR => encrypted_attributes.push(EncryptedAttribute::SecretScalar),
+                TAG_PUBLIC_POINT  => encrypted_attributes.push(EncryptedAttribute::PublicPoint(try_deserialise!(format!("M_{}", i), chunk))),
+                TAG_SECRET_POINT  => encrypted_attributes.push(EncryptedAttribute::SecretPoint),
+                _                 => return Err(CredentialError::BadAttribute),
+            }
+        }
+
+        if bytes.len() < index + 32 * (3 + NUMBER_OF_ATTRIBUTES) + 1 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let C_x_0: RistrettoPoint = try_deserialise!("C_x_0", chunk);
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let C_x_1: RistrettoPoint = try_deserialise!("C_x_1", chunk);
+
+        chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+        let C_V: RistrettoPoint = try_deserialise!("C_V", chunk);
+
+        let mut C_y: Vec<RistrettoPoint> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+
+        for i in 0..NUMBER_OF_ATTRIBUTES {
+            chunk.copy_from_slice(&bytes[index..index+32]); index += 32;
+            C_y.push(try_deserialise!(format!("C_y_{}", i), chunk));
+        }
+
+        let number_of_proofs_of_encryption: usize = bytes[index] as usize; index += 1;
+
+        if number_of_proofs_of_encryption > NUMBER_OF_ATTRIBUTES {
+            return Err(CredentialError::BadAttribute);
+        }
+
+        let mut proofs_of_encryption: Vec<(u16, ProofOfEncryption)> = Vec::with_capacity(number_of_proofs_of_encryption);
+
+        for _ in 0..number_of_proofs_of_encryption {
+            if bytes.len() < index + SIZEOF_PROOF_OF_ENCRYPTION {
+                return Err(CredentialError::WrongNumberOfBytes);
+            }
+            let proof_of_encryption = ProofOfEncryption::from_bytes(&bytes[index..index+SIZEOF_PROOF_OF_ENCRYPTION])?;
+            index += SIZEOF_PROOF_OF_ENCRYPTION;
+
+            proofs_of_encryption.push((proof_of_encryption.index, proof_of_encryption));
+        }
+
+        Ok((CredentialCommitments { key_id, proofs_of_encryption, encrypted_attributes, C_x_0, C_x_1, C_V, C_y }, index))
+    }
+
+    fn to_bytes(&self, v: &mut Vec<u8>) {
+        v.extend(self.key_id.0.iter());
+        v.push(self.encrypted_attributes.len() as u8);
+
+        for attribute in self.encrypted_attributes.iter() {
+            match attribute {
+                EncryptedAttribute::PublicScalar(m) => { v.push(TAG_PUBLIC_SCALAR); v.extend(m.as_bytes().iter()); },
+                EncryptedAttribute::SecretScalar    =>   v.push(TAG_SECRET_SCALAR),
+                EncryptedAttribute::PublicPoint(M)  => { v.push(TAG_PUBLIC_POINT);  v.extend(M.compress().to_bytes().iter()); },
+                EncryptedAttribute::SecretPoint     =>   v.push(TAG_SECRET_POINT),
+            }
+        }
+
+        v.extend(self.C_x_0.compress().to_bytes().iter());
+        v.extend(self.C_x_1.compress().to_bytes().iter());
+        v.extend(self.C_V.compress().to_bytes().iter());
+
+        for C_y_i in self.C_y.iter() {
+            v.extend(C_y_i.compress().to_bytes().iter());
+        }
+
+        v.push(self.proofs_of_encryption.len() as u8);
+
+        for (_, proof_of_encryption) in self.proofs_of_encryption.iter() {
+            v.extend(proof_of_encryption.to_bytes());
+        }
+    }
+}
+
+/// A proof-of-knowledge of a valid `Credential` and its attributes,
+/// which may be either hidden or revealed.
+// XXX the commitments should be compressed
+pub struct ProofOfValidCredential {
+    commitments: CredentialCommitments,
+    proof: BatchableProof,
+    range_proofs: Vec<RangeProof>,
+    pseudonym: Option<(Scope, Pseudonym)>,
+    serial_number: Option<(u16, SerialNumber)>,
+    challenge: Option<Challenge>,
+    rate_limit: Option<RateLimitProof>,
+    escrow: Option<ProofOfEscrow>,
+}
+
+impl ProofOfValidCredential {
+    /// Create a [`ProofOfValidCredential`]
+    pub fn prove<C>(
+        system_parameters: &SystemParameters,
+        issuer_parameters: &IssuerParameters,
+        credential: &AnonymousCredential,
+        keypair: Option<&SymmetricKeypair>,
+        csprng: &mut C,
+    ) -> Result<ProofOfValidCredential, CredentialError>
+    where
+        C: RngCore + CryptoRng,
+    {
+        ProofOfValidCredential::prove_with_options(system_parameters, issuer_parameters, credential,
+                                                   keypair, &PresentationOptions::default(), csprng)
+    }
+
+    /// Create a [`ProofOfValidCredential`] which additionally proves the
+    /// statements in the `options`.
+    ///
+    /// # Inputs
+    ///
+    /// * The `system_parameters` and `issuer_parameters`,
+    /// * The `credential` to present,
+    /// * A symmetric `keypair`, if the credential has hidden group element attributes,
+    /// * The `options`, whose predicates and scope must each concern a hidden
+    ///   scalar attribute,
+    /// * A `csprng`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`ProofOfValidCredential`], otherwise
+    /// a [`CredentialError`], which is [`CredentialError::UnsatisfiedPredicate`]
+    /// if the credential does not satisfy one of the predicates.
+    pub fn prove_with_options<C>(
+        system_parameters: &SystemParameters,
+        issuer_parameters: &IssuerParameters,
+        credential: &AnonymousCredential,
+        keypair: Option<&SymmetricKeypair>,
+        options: &PresentationOptions,
+        csprng: &mut C,
+    ) -> Result<ProofOfValidCredential, CredentialError>
+    where
+        C: RngCore + CryptoRng,
+    {
+        let (proof, _) = ProofOfValidCredential::prove_with_nonce(system_parameters, issuer_parameters,
+                                                                  credential, keypair, options, csprng)?;
+        Ok(proof)
+    }
+
+    /// As [`ProofOfValidCredential::prove_with_options`], but additionally
+    /// returning the nonce \( z \) used for the commitments, such that other
+    /// proofs may be made about the openings of the commitments \( C_y_i \).
+    pub(crate) fn prove_with_nonce<C>(
+        system_parameters: &SystemParameters,
+        issuer_parameters: &IssuerParameters,
+        credential: &AnonymousCredential,
+        keypair: Option<&SymmetricKeypair>,
+        options: &PresentationOptions,
+        csprng: &mut C,
+    ) -> Result<(ProofOfValidCredential, Scalar), CredentialError>
+    where
+        C: RngCore + CryptoRng,
+    {
+        let predicates = &options.predicates;
+        let NUMBER_OF_ATTRIBUTES = system_parameters.NUMBER_OF_ATTRIBUTES as usize;
+
+        // Commit to the credential, and encrypt its hidden group element attributes.
+        let (commitments, z_, Z_) = CredentialCommitments::commit(system_parameters, issuer_parameters,
+                                                                   credential, keypair, csprng)?;
+
+        if predicates.len() > u8::max_value() as usize {
+            return Err(CredentialError::BadAttribute);
+        }
+
+        // Predicates may only be proven about hidden scalar attributes.
+        for predicate in predicates.iter() {
+            match credential.attributes.get(predicate.index()) {
+                Some(Attribute::SecretScalar(_)) => continue,
+                _ => return Err(CredentialError::BadAttribute),
+            }
+        }
+
+        // Derive the pseudonym, if any, from a hidden scalar attribute.
+        let pseudonym: Option<(Scope, RistrettoPoint, Pseudonym)> = match &options.scope {
+            None => None,
+            Some(scope) => {
+                if scope.label.len() > u8::max_value() as usize {
+                    return Err(CredentialError::WrongNumberOfBytes);
+                }
+                match credential.attributes.get(scope.index as usize) {
+                    Some(Attribute::SecretScalar(k)) => {
+                        let H = scope.generator();
+
+                        Some((scope.clone(), H, Pseudonym(H * k)))
+                    },
+                    _ => return Err(CredentialError::BadAttribute),
+                }
+            },
+        };
+
+        // Derive the serial number, if any, from a hidden scalar attribute.
+        let serial_number: Option<(u16, SerialNumber)> = match options.serial_number {
+            None => None,
+            Some(index) => match credential.attributes.get(index as usize) {
+                Some(Attribute::SecretScalar(s)) => Some((index, SerialNumber(SerialNumber::generator() * s))),
+                _ => return Err(CredentialError::BadAttribute),
+            },
+        };
+
+        if let Some(challenge) = &options.challenge {
+            if challenge.context.len() > u16::max_value() as usize {
+                return Err(CredentialError::WrongNumberOfBytes);
+            }
+        }
+
+        if let Some((rate_limit, _)) = &options.rate_limit {
+            if rate_limit.label.len() > u8::max_value() as usize {
+                return Err(CredentialError::WrongNumberOfBytes);
+            }
+            if rate_limit.limit > MAX_RATE_LIMIT {
+                return Err(CredentialError::UnsatisfiedPredicate);
+            }
+            match credential.attributes.get(rate_limit.index as usize) {
+                Some(Attribute::SecretScalar(_)) => (),
+                _ => return Err(CredentialError::BadAttribute),
+            }
+        }
+
+        // Only hidden attributes may be escrowed, each at most once.
+        if let Some(escrow) = &options.escrow {
+            if escrow.indices.len() > u8::max_value() as usize {
+                return Err(CredentialError::BadAttribute);
+            }
+            for (j, index) in escrow.indices.iter().enumerate() {
+                match credential.attributes.get(*index as usize) {
+                    Some(Attribute::SecretScalar(_)) | Some(Attribute::SecretPoint(_)) => (),
+                    _ => return Err(CredentialError::BadAttribute),
+                }
+                if escrow.indices[..j].contains(index) {
+                    return Err(CredentialError::BadAttribute);
+                }
+            }
+        }
+
+        let z_0_: Scalar = (-credential.amac.t * z_).reduce();
+
+        // Store the hidden scalar attributes in H_s.
+        let mut H_s_: Vec<(usize, RistrettoPoint, Scalar)> = Vec::new();
+
+        for (i, attribute) in credential.attributes.iter().enumerate() {
+            if let Attribute::SecretScalar(m) = attribute {
+                H_s_.push((i, system_parameters.G_m[i], *m));
+            }
+        }
+
+        // Create a transcript, bound to the verifier's challenge if any, and a prover.
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+
+        if let Some(challenge) = &options.challenge {
+            challenge.commit(&mut transcript);
+        }
+
+        let mut prover = Prover::new(b"2019/1416 presentation proof", &mut transcript);
+
+        // Feed the domain separators for the Camenisch-Stadler secrets into the protocol transcript.
+        let z   = prover.allocate_scalar(b"z", z_);
+        let z_0 = prover.allocate_scalar(b"z_0", z_0_);
+        let t   = prover.allocate_scalar(b"t", credential.amac.t);
+
+        let mut H_s = ProverHiddenScalars(Vec::with_capacity(H_s_.len()));
+
+        for (i, _, scalar) in H_s_.iter() {
+            // XXX Fix zkp crate to take Strings
+            //H_s.push(prover.allocate_scalar(format!(b"H_s_{}", i), scalar));
+            H_s.push((*i, prover.allocate_scalar(b"m", *scalar)));
+        }
+
+        // The secret portions of the symmetric key, if any hidden group element
+        // attributes are encrypted under it.
+        let mut a_a0_a1: Option<(ProverScalarVar, ProverScalarVar, ProverScalarVar)> = None;
+
+        if !commitments.proofs_of_encryption.is_empty() {
+            let secret = &keypair.ok_or(CredentialError::NoSymmetricKey)?.secret;
+
+            a_a0_a1 = Some((prover.allocate_scalar(b"a",  secret.a),
+                            prover.allocate_scalar(b"a0", secret.a0),
+                            prover.allocate_scalar(b"a1", secret.a1)));
+        }
+
+        // Feed in the domain separators and values for the publics into the transcript.
+        let (Z, _)     = prover.allocate_point(b"Z", Z_);
+        let (I, _)     = prover.allocate_point(b"I", issuer_parameters.I);
+        let (C_x_1, _) = prover.allocate_point(b"C_x_1", commitments.C_x_1);
+        let (C_x_0, _) = prover.allocate_point(b"C_x_0", commitments.C_x_0);
+        let (G_x_0, _) = prover.allocate_point(b"G_x_0", system_parameters.G_x_0);
+        let (G_x_1, _) = prover.allocate_point(b"G_x_1", system_parameters.G_x_1);
+
+        let mut C_y: Vec<(usize, ProverPointVar)> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+        let mut G_y: Vec<ProverPointVar> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+
+        // We only prove knowledge of commitment openings for hidden scalar
+        // attributes and all revealed attributes; for hidden group element
+        // attributes we use proofs of encryption.
+        for (i, commitment) in commitments.C_y.iter().enumerate() {
+            match commitments.encrypted_attributes[i] {
+                EncryptedAttribute::SecretPoint => continue,
+                _ => {
+                    // XXX Fix zkp crate to take Strings
+                    //let (C_y_i, _) = prover.allocate_point(format!(b"C_y_{}", i), commitment);
+                    let (C_y_i, _) = prover.allocate_point(b"C_y", *commitment);
+
+                    C_y.push((i, C_y_i));
+                },
+            };
+        }
+
+        for basepoint in system_parameters.G_y.iter() {
+            // XXX Fix zkp crate to take Strings
+            // let (G_y_i, _) = prover.allocate_point(format!(b"G_y_{}", i), basepoint);
+            let (G_y_i, _) = prover.allocate_point(b"G_y", *basepoint);
+
+            G_y.push(G_y_i);
+        }
+
+        let mut G_m = ProverHiddenScalarBasepoints(Vec::with_capacity(H_s_.len()));
+
+        for (i, basepoint, _) in H_s_.iter() {
+            // XXX Fix zkp crate to take Strings
+            // let (G_m_i, _) = prover.allocate_point(format!(b"G_m_{}", i), basepoint);
+            let (G_m_i, _) = prover.allocate_point(b"G_m", *basepoint);
+
+            G_m.push((*i, G_m_i));
+        }
+
+        let mut P_H: Option<(usize, ProverPointVar, ProverPointVar)> = None;
+
+        if let Some((scope, H_, P_)) = &pseudonym {
+            let (P, _) = prover.allocate_point(b"P", P_.0);
+            let (H, _) = prover.allocate_point(b"H_scope", *H_);
+
+            P_H = Some((scope.index as usize, P, H));
+        }
+
+        let mut S_H: Option<(usize, ProverPointVar, ProverPointVar)> = None;
+
+        if let Some((index, S_)) = &serial_number {
+            let (S, _) = prover.allocate_point(b"S", S_.0);
+            let (H, _) = prover.allocate_point(b"H_serial", SerialNumber::generator());
+
+            S_H = Some((*index as usize, S, H));
+        }
+
+        let mut pk_G_a: Option<(ProverPointVar, ProverPointVar, ProverPointVar, ProverPointVar)> = None;
+        let mut E: Vec<(usize, ProverPointVar, ProverPointVar)> = Vec::with_capacity(commitments.proofs_of_encryption.len());
+
+        if let Some((_, proof_of_encryption)) = commitments.proofs_of_encryption.first() {
+            let (pk, _)    = prover.allocate_point(b"pk",    proof_of_encryption.public_key.pk);
+            let (G_a, _)   = prover.allocate_point(b"G_a",   system_parameters.G_a);
+            let (G_a_0, _) = prover.allocate_point(b"G_a_0", system_parameters.G_a0);
+            let (G_a_1, _) = prover.allocate_point(b"G_a_1", system_parameters.G_a1);
+
+            pk_G_a = Some((pk, G_a, G_a_0, G_a_1));
+        }
+
+        for (i, proof_of_encryption) in commitments.proofs_of_encryption.iter() {
+            let ciphertext = &proof_of_encryption.ciphertext;
+            let (C_y_i_minus_E2, _) = prover.allocate_point(b"C_y-E2", commitments.C_y[*i as usize] - ciphertext.E2);
+            let (minus_E1, _)       = prover.allocate_point(b"-E1", -ciphertext.E1);
+
+            E.push((*i as usize, C_y_i_minus_E2, minus_E1));
+        }
+
+        // Constraint #1: Prove knowledge of the nonce, z, and the correctness of the AMAC with Z.
+        //                Z = I * z
+        prover.constrain(Z, vec![(z, I)]);
+
+        // Constraint #2: Prove correctness of t and U.
+        //                C_x_1 = C_x_0 * t          + G_x_0 * z_0 + G_x_1 * z
+        //    G_x_1 * z + U * t = G_x_0 * zt + U * t + G_x_0 * -tz + G_x_1 * z
+        //    G_x_1 * z + U * t =              U * t +               G_x_1 * z
+        prover.constrain(C_x_1, vec![(t, C_x_0), (z_0, G_x_0), (z, G_x_1)]);
+
+        // Constraint #3: Prove correctness/validation of attributes.
+        //        C_y_i = { G_y_i * z + G_m_i * m_i          if i is a hidden scalar attribute
+        //                { G_y_i * z                        if i is a revealed attribute
+        for (i, C_y_i) in C_y.iter() {
+            match commitments.encrypted_attributes[*i] {
+                EncryptedAttribute::SecretScalar => prover.constrain(*C_y_i, vec![(z, G_y[*i]), (H_s[*i], G_m[*i])]),
+                _                                => prover.constrain(*C_y_i, vec![(z, G_y[*i])]),
+            }
+        }
+
+        // Constraint #4: Prove that the pseudonym, if any, uses the same hidden scalar, k = m_i.
+        //                P = H(scope) * m_i
+        if let Some((i, P, H)) = P_H {
+            prover.constrain(P, vec![(H_s[i], H)]);
+        }
+
+        // Constraint #5: Prove that the serial number, if any, uses the same hidden scalar, s = m_i.
+        //                S = H_serial * m_i
+        if let Some((i, S, H)) = S_H {
+            prover.constrain(S, vec![(H_s[i], H)]);
+        }
+
+        // Constraint #6: Prove knowledge of the symmetric key, if any, to which hidden group elements are encrypted.
+        //                pk = G_a * a + G_a0 * a0 + G_a1 * a1
+        //
+        // Constraint #7: Prove that each encryption is of the group element committed to with the same nonce, z.
+        //                C_y_i - E2_i = G_y_i * z - E1_i * a
+        if let (Some((a, a0, a1)), Some((pk, G_a, G_a_0, G_a_1))) = (a_a0_a1, pk_G_a) {
+            prover.constrain(pk, vec![(a, G_a), (a0, G_a_0), (a1, G_a_1)]);
+
+            for (i, C_y_i_minus_E2, minus_E1) in E.iter() {
+                prover.constrain(*C_y_i_minus_E2, vec![(z, G_y[*i]), (a, *minus_E1)]);
+            }
+        }
+
+        // Notes:
+        //
+        // 1. Prover recalculates Z', so it is not sent.
+        // 2. C_V, the commitment to the actual AMAC (recall that the t and U
+        //    values in the AMAC are nonces), is sent, but V is kept private to
+        //    provide anonymity, so we do not prove anything about it.
+        // 3; That z_0 actually equals -tz (mod \ell) is never proven, but this
+        //    should not matter as we prove knowledge of t and z, and constraint
+        //    #2 would never pass verification if either were other than the
+        //    values used to compute z_0.
+        let proof = prover.prove_batchable();
+
+        // Construct range proofs for the predicates over hidden scalar attributes.
+        let mut range_proofs: Vec<RangeProof> = Vec::with_capacity(predicates.len());
+
+        for predicate in predicates.iter() {
+            let i = predicate.index();
+
+            if let Attribute::SecretScalar(m) = &credential.attributes[i] {
+                range_proofs.push(RangeProof::prove(&system_parameters, predicate, m, &commitments.C_y[i], &z_, csprng)?);
+            }
+        }
+
+        // Construct the rate limit proof for the tag, if any.
+        let rate_limit = match &options.rate_limit {
+            None => None,
+            Some((rate_limit, j)) => {
+                let i = rate_limit.index as usize;
+
+                match &credential.attributes[i] {
+                    Attribute::SecretScalar(s) => Some(RateLimitProof::prove(&system_parameters, rate_limit, *j, s,
+                                                                             &commitments.C_y[i], &z_, csprng)?),
+                    _ => None,
+                }
+            },
+        };
+
+        // Encrypt the escrowed attributes, if any, to the auditor.
+        let escrow = options.escrow.as_ref().map(|escrow| {
+            ProofOfEscrow::prove(&system_parameters, &issuer_parameters, escrow, &commitments.C_y, &z_, csprng)
+        });
+
+        Ok((ProofOfValidCredential {
+            commitments: commitments,
+            proof: proof,
+            range_proofs: range_proofs,
+            pseudonym: pseudonym.map(|(scope, _, P)| (scope, P)),
+            serial_number: serial_number,
+            challenge: options.challenge.clone(),
+            rate_limit: rate_limit,
+            escrow: escrow,
+        }, z_))
+    }
+
+    /// Check that this [`ProofOfValidCredential`] is well-formed w.r.t. the
+    /// `issuer`, i.e. that its commitments are, as in
+    /// [`CredentialCommitments::check_structure`], and that any
+    /// [`RangeProof`]s, pseudonym, serial number, and rate limit concern
+    /// hidden scalar attributes, and any escrow distinct hidden attributes.
+    fn check_structure(
+        &self,
+        issuer: &Issuer,
+    ) -> Result<(), CredentialError>
+    {
+        self.commitments.check_structure(issuer)?;
+
+        for range_proof in self.range_proofs.iter() {
+            match self.commitments.encrypted_attributes.get(range_proof.predicate.index()) {
+                Some(EncryptedAttribute::SecretScalar) => (),
+                _ => return Err(CredentialError::VerificationFailure),
+            }
+        }
+
+        if let Some((scope, _)) = &self.pseudonym {
+            match self.commitments.encrypted_attributes.get(scope.index as usize) {
+                Some(EncryptedAttribute::SecretScalar) => (),
+                _ => return Err(CredentialError::VerificationFailure),
+            }
+        }
+
+        if let Some((index, _)) = &self.serial_number {
+            match self.commitments.encrypted_attributes.get(*index as usize) {
+                Some(EncryptedAttribute::SecretScalar) => (),
+                _ => return Err(CredentialError::VerificationFailure),
+            }
+        }
+
+        if let Some(rate_limit_proof) = &self.rate_limit {
+            if rate_limit_proof.rate_limit.limit > MAX_RATE_LIMIT {
+                return Err(CredentialError::VerificationFailure);
+            }
+            match self.commitments.encrypted_attributes.get(rate_limit_proof.rate_limit.index as usize) {
+                Some(EncryptedAttribute::SecretScalar) => (),
+                _ => return Err(CredentialError::VerificationFailure),
+            }
+        }
+
+        if let Some(proof_of_escrow) = &self.escrow {
+            let indices = &proof_of_escrow.escrow.indices;
+
+            for (j, index) in indices.iter().enumerate() {
+                match self.commitments.encrypted_attributes.get(*index as usize) {
+                    Some(EncryptedAttribute::SecretScalar) | Some(EncryptedAttribute::SecretPoint) => (),
+                    _ => return Err(CredentialError::VerificationFailure),
+                }
+                if indices[..j].contains(index) {
+                    return Err(CredentialError::VerificationFailure);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Verify this [`ProofOfValidCredential`].
+    ///
+    /// Verification requires only the `issuer`'s keys and the presentation
+    /// itself, i.e. the revealed attributes, the layout of the hidden ones, the
+    /// commitments, and the proofs.  Notably, the verifier never learns the
+    /// hidden attributes of the credential being presented.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`].
+    pub fn verify(
+        &self,
+        issuer: &Issuer,
+    ) -> Result<(), CredentialError>
+    {
+        self.check_structure(issuer)?;
+
+        let NUMBER_OF_ATTRIBUTES = issuer.system_parameters.NUMBER_OF_ATTRIBUTES as usize;
+        let Z_ = self.commitments.compute_Z(issuer);
+
+        // Create a transcript, bound to the verifier's challenge if any, and a verifier.
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+
+        if let Some(challenge) = &self.challenge {
+            challenge.commit(&mut transcript);
+        }
+
+        let mut verifier = Verifier::new(b"2019/1416 presentation proof", &mut transcript);
+
+        // Feed the domain separators for the Camenisch-Stadler secrets into the protocol transcript.
+        let z   = verifier.allocate_scalar(b"z");
+        let z_0 = verifier.allocate_scalar(b"z_0");
+        let t   = verifier.allocate_scalar(b"t");
+
+        let mut H_s = VerifierHiddenScalars(Vec::new());
+
+        for (i, attribute) in self.commitments.encrypted_attributes.iter().enumerate() {
+            if let EncryptedAttribute::SecretScalar = attribute {
+                // XXX Fix zkp crate to take Strings
+                //H_s.push(*i, verifier.allocate_scalar(format!(b"H_s_{}", i)));
+                H_s.push((i, verifier.allocate_scalar(b"m")));
+            }
+        }
+
+        // The secret portions of the symmetric key, if any hidden group element
+        // attributes are encrypted under it.
+        let mut a_a0_a1: Option<(VerifierScalarVar, VerifierScalarVar, VerifierScalarVar)> = None;
+
+        if !self.commitments.proofs_of_encryption.is_empty() {
+            a_a0_a1 = Some((verifier.allocate_scalar(b"a"),
+                            verifier.allocate_scalar(b"a0"),
+                            verifier.allocate_scalar(b"a1")));
+        }
+
+        // Feed in the domain separators and values for the publics into the transcript.
+        let Z     = verifier.allocate_point(b"Z", Z_.compress())?;
+        let I     = verifier.allocate_point(b"I", issuer.issuer_parameters.I.compress())?;
+        let C_x_1 = verifier.allocate_point(b"C_x_1", self.commitments.C_x_1.compress())?;
+        let C_x_0 = verifier.allocate_point(b"C_x_0", self.commitments.C_x_0.compress())?;
+        let G_x_0 = verifier.allocate_point(b"G_x_0", issuer.system_parameters.G_x_0.compress())?;
+        let G_x_1 = verifier.allocate_point(b"G_x_1", issuer.system_parameters.G_x_1.compress())?;
+
+        let mut C_y: Vec<(usize, VerifierPointVar)> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+        let mut G_y: Vec<VerifierPointVar> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+
+        // We only prove knowledge of commitment openings for hidden scalar
+        // attributes and all revealed attributes; for hidden group element
+        // attributes we use proofs of encryption.
+        for (i, commitment) in self.commitments.C_y.iter().enumerate() {
+            match self.commitments.encrypted_attributes[i] {
+                EncryptedAttribute::SecretPoint => continue,
+                _ => {
+                    // XXX Fix zkp crate to take Strings
+                    // C_y.push(verifier.allocate_point(format!(b"C_y_{}", i), commitment.compress())?);
+                    C_y.push((i, verifier.allocate_point(b"C_y", commitment.compress())?));
+                },
+            };
+        }
+
+        for basepoint in issuer.system_parameters.G_y.iter() {
+            // XXX Fix zkp crate to take Strings
+            // G_y.push(verifier.allocate_point(format!(b"G_y_{}", i), basepoint.compress())?);
+            G_y.push(verifier.allocate_point(b"G_y", basepoint.compress())?);
+        }
+
+        let mut G_m = VerifierHiddenScalarBasepoints(Vec::with_capacity(H_s.0.len()));
+
+        for (i, _) in H_s.0.iter() {
+            // XXX Fix zkp crate to take Strings
+            // G_m.push(verifier.allocate_point(format!(b"G_m_{}", i), issuer.system_parameters.G_m[i].compress())?);
+            G_m.push((*i, verifier.allocate_point(b"G_m", issuer.system_parameters.G_m[*i].compress())?));
+        }
+
+        let mut P_H: Option<(usize, VerifierPointVar, VerifierPointVar)> = None;
+
+        if let Some((scope, P_)) = &self.pseudonym {
+            let P = verifier.allocate_point(b"P", P_.0.compress())?;
+            let H = verifier.allocate_point(b"H_scope", scope.generator().compress())?;
+
+            P_H = Some((scope.index as usize, P, H));
+        }
+
+        let mut S_H: Option<(usize, VerifierPointVar, VerifierPointVar)> = None;
+
+        if let Some((index, S_)) = &self.serial_number {
+            let S = verifier.allocate_point(b"S", S_.0.compress())?;
+            let H = verifier.allocate_point(b"H_serial", SerialNumber::generator().compress())?;
+
+            S_H = Some((*index as usize, S, H));
+        }
+
+        let mut pk_G_a: Option<(VerifierPointVar, VerifierPointVar, VerifierPointVar, VerifierPointVar)> = None;
+        let mut E: Vec<(usize, VerifierPointVar, VerifierPointVar)> = Vec::with_capacity(self.commitments.proofs_of_encryption.len());
+
+        if let Some((_, proof_of_encryption)) = self.commitments.proofs_of_encryption.first() {
+            let pk    = verifier.allocate_point(b"pk",    proof_of_encryption.public_key.pk.compress())?;
+            let G_a   = verifier.allocate_point(b"G_a",   issuer.system_parameters.G_a.compress())?;
+            let G_a_0 = verifier.allocate_point(b"G_a_0", issuer.system_parameters.G_a0.compress())?;
+            let G_a_1 = verifier.allocate_point(b"G_a_1", issuer.system_parameters.G_a1.compress())?;
+
+            pk_G_a = Some((pk, G_a, G_a_0, G_a_1));
+        }
+
+        for (i, proof_of_encryption) in self.commitments.proofs_of_encryption.iter() {
+            let ciphertext = &proof_of_encryption.ciphertext;
+            let C_y_i_minus_E2 = verifier.allocate_point(b"C_y-E2", (self.commitments.C_y[*i as usize] - ciphertext.E2).compress())?;
+            let minus_E1       = verifier.allocate_point(b"-E1", (-ciphertext.E1).compress())?;
+
+            E.push((*i as usize, C_y_i_minus_E2, minus_E1));
+        }
+
+        // Constraint #1: Prove knowledge of the nonce, z, and the correctness of the AMAC with Z.
+        //                Z = I * z
+        verifier.constrain(Z, vec![(z, I)]);
+
+        // Constraint #2: Prove correctness of t and U.
+        //                C_x_1 = C_x_0 * t          + G_x_0 * z_0 + G_x_1 * z
+        //    G_x_1 * z + U * t = G_x_0 * zt + U * t + G_x_0 * -tz + G_x_1 * z
+        //    G_x_1 * z + U * t =              U * t +               G_x_1 * z
+        verifier.constrain(C_x_1, vec![(t, C_x_0), (z_0, G_x_0), (z, G_x_1)]);
+
+        // Constraint #3: Prove correctness/validation of attributes.
+        //        C_y_i = { G_y_i * z + G_m_i * m_i          if i is a hidden scalar attribute
+        //                { G_y_i * z                        if i is a revealed attribute
+        for (i, C_y_i) in C_y.iter() {
+            match self.commitments.encrypted_attributes[*i] {
+                EncryptedAttribute::SecretScalar => verifier.constrain(*C_y_i, vec![(z, G_y[*i]), (H_s[*i], G_m[*i])]),
+                _                                => verifier.constrain(*C_y_i, vec![(z, G_y[*i])]),
+            }
+        }
+
+        // Constraint #4: Prove that the pseudonym, if any, uses the same hidden scalar, k = m_i.
+        //                P = H(scope) * m_i
+        if let Some((i, P, H)) = P_H {
+            verifier.constrain(P, vec![(H_s[i], H)]);
+        }
+
+        // Constraint #5: Prove that the serial number, if any, uses the same hidden scalar, s = m_i.
+        //                S = H_serial * m_i
+        if let Some((i, S, H)) = S_H {
+            verifier.constrain(S, vec![(H_s[i], H)]);
+        }
+
+        // Constraint #6: Prove knowledge of the symmetric key, if any, to which hidden group elements are encrypted.
+        //                pk = G_a * a + G_a0 * a0 + G_a1 * a1
+        //
+        // Constraint #7: Prove that each encryption is of the group element committed to with the same nonce, z.
+        //                C_y_i - E2_i = G_y_i * z - E1_i * a
+        if let (Some((a, a0, a1)), Some((pk, G_a, G_a_0, G_a_1))) = (a_a0_a1, pk_G_a) {
+            verifier.constrain(pk, vec![(a, G_a), (a0, G_a_0), (a1, G_a_1)]);
+
+            for (i, C_y_i_minus_E2, minus_E1) in E.iter() {
+                verifier.constrain(*C_y_i_minus_E2, vec![(z, G_y[*i]), (a, *minus_E1)]);
+            }
+        }
+
+        verifier.verify_batchable(&self.proof).or_else(|_| Err(CredentialError::VerificationFailure))?;
+
+        // Check the proofs of correct encryptions and fail if any cannot be verified.
+        for (_, proof_of_encryption) in self.commitments.proofs_of_encryption.iter() {
+            proof_of_encryption.verify(&issuer.system_parameters)?;
+        }
+
+        self.verify_auxiliary_proofs(issuer)
+    }
+
+    /// Verify this [`ProofOfValidCredential`], and that it proves each of the
+    /// `predicates` required by the verifier.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`],
+    /// which is [`CredentialError::UnsatisfiedPredicate`] if one of the
+    /// `predicates` was not proven.
+    pub fn verify_with_predicates(
+        &self,
+        issuer: &Issuer,
+        predicates: &[Predicate],
+    ) -> Result<(), CredentialError>
+    {
+        self.verify(issuer)?;
+
+        for predicate in predicates.iter() {
+            if !self.range_proofs.iter().any(|range_proof| range_proof.predicate == *predicate) {
+                return Err(CredentialError::UnsatisfiedPredicate);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Verify this [`ProofOfValidCredential`] against the issuers for several
+    /// epochs, and that the credential has not expired at the time `now`.
+    ///
+    /// The credential must have been presented with a range proof that its
+    /// expiry time is later than some bound which is itself later than `now`,
+    /// such as [`crate::expiry::not_expired`] for the prover's own clock.
+    ///
+    /// # Inputs
+    ///
+    /// * The `issuers` for each [`Epoch`] whose credentials may not yet have expired,
+    /// * The verifier's clock, `now`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the [`Epoch`] whose issuer issued the
+    /// credential, otherwise a [`CredentialError`], which is
+    /// [`CredentialError::UnsatisfiedPredicate`] if the credential may have
+    /// expired.
+    pub fn verify_unexpired(
+        &self,
+        issuers: &[(Epoch, &Issuer)],
+        now: u64,
+    ) -> Result<Epoch, CredentialError>
+    {
+        for (epoch, issuer) in issuers.iter() {
+            if self.verify(issuer).is_err() {
+                continue;
+            }
+
+            let index = expiry_index(&issuer.system_parameters)?;
+            let unexpired = self.range_proofs.iter().any(|range_proof| {
+                match range_proof.predicate {
+                    Predicate::AtLeast { index: i, bound } => i as usize == index && bound > now,
+                    _ => false,
+                }
+            });
+
+            if unexpired {
+                return Ok(*epoch);
+            }
+            return Err(CredentialError::UnsatisfiedPredicate);
+        }
+
+        Err(CredentialError::VerificationFailure)
+    }
+
+    /// Verify this [`ProofOfValidCredential`], and that it presents a
+    /// [`Pseudonym`] within the verifier's `scope`.
+    ///
+    /// Both the scope's label and the index of the attribute it is derived
+    /// from must match, since a pseudonym derived from any other hidden scalar
+    /// attribute would give the holder another identity within the scope.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the [`Pseudonym`] of the credential's
+    /// holder within the scope, otherwise a [`CredentialError`], which is
+    /// [`CredentialError::MissingData`] if no pseudonym was presented.
+    pub fn verify_in_scope(
+        &self,
+        issuer: &Issuer,
+        scope: &Scope,
+    ) -> Result<Pseudonym, CredentialError>
+    {
+        match &self.pseudonym {
+            Some((s, P)) if s == scope => { self.verify(issuer)?; Ok(*P) },
+            Some(_) => Err(CredentialError::VerificationFailure),
+            None    => Err(CredentialError::MissingData),
+        }
+    }
+
+    /// Verify this [`ProofOfValidCredential`], and that it is bound to the
+    /// verifier's `challenge`, such that it cannot have been replayed from
+    /// elsewhere.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`],
+    /// which is [`CredentialError::MissingData`] if the presentation is not
+    /// bound to any challenge.
+    pub fn verify_with_challenge(
+        &self,
+        issuer: &Issuer,
+        challenge: &Challenge,
+    ) -> Result<(), CredentialError>
+    {
+        match &self.challenge {
+            Some(c) if c == challenge => self.verify(issuer),
+            Some(_) => Err(CredentialError::VerificationFailure),
+            None    => Err(CredentialError::MissingData),
+        }
+    }
+
+    /// Verify this [`ProofOfValidCredential`], and that it presents a
+    /// [`SerialNumber`] derived from the hidden scalar attribute at `index`.
+    ///
+    /// A credential has a different serial number for each of its hidden
+    /// scalar attributes, so the verifier must fix which one it expects, or
+    /// else a credential could be spent once per hidden scalar attribute.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the [`SerialNumber`], which the verifier
+    /// should check it has not seen before, otherwise a [`CredentialError`],
+    /// which is [`CredentialError::MissingData`] if no serial number was
+    /// presented.
+    pub fn verify_with_serial_number(
+        &self,
+        issuer: &Issuer,
+        index: u16,
+    ) -> Result<SerialNumber, CredentialError>
+    {
+        match &self.serial_number {
+            Some((i, S)) if *i == index => { self.verify(issuer)?; Ok(*S) },
+            Some(_) => Err(CredentialError::VerificationFailure),
+            None    => Err(CredentialError::MissingData),
+        }
+    }
+
+    /// The [`SerialNumber`] of the presented credential, if any, along with
+    /// the index of the hidden scalar attribute it claims to be derived from.
+    ///
+    /// Neither is checked; see [`ProofOfValidCredential::verify_with_serial_number`].
+    pub fn serial_number(&self) -> Option<(u16, SerialNumber)> {
+        self.serial_number
+    }
+
+    /// Verify this [`ProofOfValidCredential`], and that it presents a [`Tag`]
+    /// within the verifier's `rate_limit`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the [`Tag`], which the verifier should
+    /// check it has not seen before within the epoch, otherwise a
+    /// [`CredentialError`], which is [`CredentialError::MissingData`] if no
+    /// tag was presented.
+    pub fn verify_rate_limited(
+        &self,
+        issuer: &Issuer,
+        rate_limit: &RateLimit,
+    ) -> Result<Tag, CredentialError>
+    {
+        match &self.rate_limit {
+            Some(proof) if proof.rate_limit == *rate_limit => { self.verify(issuer)?; Ok(proof.tag) },
+            Some(_) => Err(CredentialError::VerificationFailure),
+            None    => Err(CredentialError::MissingData),
+        }
+    }
+
+    /// Verify this [`ProofOfValidCredential`], and that it encrypts the hidden
+    /// attributes required by the verifier's `escrow` to the auditor.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the ciphertexts of the escrowed
+    /// attributes, in the order of the `escrow`'s indices, which the verifier
+    /// should keep in case it needs to hand them to the auditor, otherwise a
+    /// [`CredentialError`], which is [`CredentialError::MissingData`] if no
+    /// attributes were escrowed.
+    pub fn verify_escrowed(
+        &self,
+        issuer: &Issuer,
+        escrow: &Escrow,
+    ) -> Result<Vec<ElGamalCiphertext>, CredentialError>
+    {
+        match &self.escrow {
+            Some(proof) if proof.escrow == *escrow => { self.verify(issuer)?; Ok(proof.ciphertexts.clone()) },
+            Some(_) => Err(CredentialError::VerificationFailure),
+            None    => Err(CredentialError::MissingData),
+        }
+    }
+
+    /// The [`Ciphertext`] of the hidden group element attribute at `index`,
+    /// and the public key of the symmetric keypair it was encrypted under, if
+    /// that attribute is a hidden group element.
+    ///
+    /// Once this proof is verified, the ciphertext is known to encrypt the
+    /// attribute certified by the issuer.
+    pub fn encrypted_attribute(&self, index: u16) -> Option<(SymmetricPublicKey, Ciphertext)> {
+        self.commitments.proofs_of_encryption.iter()
+            .find(|(i, _)| *i == index)
+            .map(|(_, proof_of_encryption)| (proof_of_encryption.public_key, proof_of_encryption.ciphertext))
+    }
+
+    /// The [`KeyId`] of the issuer key which the presented credential claims
+    /// to have been issued under.
+    pub fn key_id(&self) -> KeyId {
+        self.commitments.key_id
+    }
+
+    /// The predicates over hidden scalar attributes which this presentation
+    /// proves, once verified.
+    pub fn predicates(&self) -> Vec<Predicate> {
+        self.range_proofs.iter().map(|range_proof| range_proof.predicate).collect()
+    }
+
+    /// Verify the [`RangeProof`]s, [`RateLimitProof`], and [`ProofOfEscrow`],
+    /// if any, of this well-formed [`ProofOfValidCredential`].
+    fn verify_auxiliary_proofs(
+        &self,
+        issuer: &Issuer,
+    ) -> Result<(), CredentialError>
+    {
+        for range_proof in self.range_proofs.iter() {
+            range_proof.verify(&issuer.system_parameters, &self.commitments.C_y[range_proof.predicate.index()])?;
+        }
+
+        if let Some(rate_limit_proof) = &self.rate_limit {
+            rate_limit_proof.verify(&issuer.system_parameters, &self.commitments.C_y[rate_limit_proof.rate_limit.index as usize])?;
+        }
+
+        if let Some(proof_of_escrow) = &self.escrow {
+            proof_of_escrow.verify(issuer, &self.commitments.compute_Z(issuer), &self.commitments.C_y)?;
+        }
+
+        Ok(())
+    }
+
+    /// Verify many [`ProofOfValidCredential`]s for the same `issuer` at once.
+    ///
+    /// Presentations which reveal and hide the same types of attributes prove
+    /// the same statement, and so are combined into a single multiscalar
+    /// multiplication, as are all of their [`ProofOfEncryption`]s.  If a
+    /// combined check fails, the proofs within it are verified individually in
+    /// order to find the culprit.
+    ///
+    /// # Inputs
+    ///
+    /// * The `proofs` to verify,
+    /// * The `issuer` whose credentials are being presented.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty if every proof verifies, otherwise
+    /// a [`CredentialError::BatchVerificationFailure`] with the index of the
+    /// first proof which failed.
+    pub fn verify_batch(
+        proofs: &[ProofOfValidCredential],
+        issuer: &Issuer,
+    ) -> Result<(), CredentialError>
+    {
+        let mut failures: Vec<usize> = Vec::new();
+
+        // Group the well-formed presentations by the types of their attributes.
+        let mut batches: Vec<(Vec<u8>, Vec<usize>)> = Vec::new();
+
+        for (j, proof) in proofs.iter().enumerate() {
+            if proof.check_structure(issuer).is_err() {
+                failures.push(j);
+                continue;
+            }
+
+            let mut layout: Vec<u8> = proof.commitments.encrypted_attributes.iter().map(|attribute| {
+                match attribute {
+                    EncryptedAttribute::PublicScalar(_) => TAG_PUBLIC_SCALAR,
+                    EncryptedAttribute::SecretScalar    => TAG_SECRET_SCALAR,
+                    EncryptedAttribute::PublicPoint(_)  => TAG_PUBLIC_POINT,
+                    EncryptedAttribute::SecretPoint     => TAG_SECRET_POINT,
+                }
+            }).collect();
+
+            // A pseudonym or serial number adds a constraint on the attribute
+            // it is derived from, each marked by a byte which is not a tag.
+            if let Some((scope, _)) = &proof.pseudonym {
+                layout.push(0xfe);
+                layout.extend(scope.index.to_le_bytes().iter());
+            }
+            if let Some((index, _)) = &proof.serial_number {
+                layout.push(0xff);
+                layout.extend(index.to_le_bytes().iter());
+            }
+
+            match batches.iter_mut().find(|(l, _)| *l == layout) {
+                Some((_, indices)) => indices.push(j),
+                None => batches.push((layout, vec![j])),
+            }
+        }
+
+        for (_, indices) in batches.iter() {
+            let batch: Vec<&ProofOfValidCredential> = indices.iter().map(|j| &proofs[*j]).collect();
+
+            if ProofOfValidCredential::verify_batchable_proofs(&batch, issuer).is_err() {
+                for j in indices.iter() {
+                    if proofs[*j].verify(issuer).is_err() {
+                        failures.push(*j);
+                    }
+                }
+            }
+        }
+
+        // Range, rate limit, and escrow proofs are not batched, since each has its own challenge.
+        for (j, proof) in proofs.iter().enumerate() {
+            if !failures.contains(&j) && proof.verify_auxiliary_proofs(issuer).is_err() {
+                failures.push(j);
+            }
+        }
+
+        // Batch all the proofs of encryption together, regardless of which
+        // presentation they belong to.
+        let mut proofs_of_encryption: Vec<&ProofOfEncryption> = Vec::new();
+        let mut owners: Vec<usize> = Vec::new();
+
+        for (j, proof) in proofs.iter().enumerate() {
+            if failures.contains(&j) {
+                continue;
+            }
+            for (_, proof_of_encryption) in proof.commitments.proofs_of_encryption.iter() {
+                proofs_of_encryption.push(proof_of_encryption);
+                owners.push(j);
+            }
+        }
+
+        if ProofOfEncryption::verify_batchable_proofs(&proofs_of_encryption, &issuer.system_parameters).is_err() {
+            for (proof_of_encryption, j) in proofs_of_encryption.iter().zip(owners.iter()) {
+                if proof_of_encryption.verify(&issuer.system_parameters).is_err() {
+                    failures.push(*j);
+                }
+            }
+            // The combined check failed but every individual check passed,
+            // which should only happen if the batch verifier itself errored.
+            if failures.is_empty() {
+                return Err(CredentialError::VerificationFailure);
+            }
+        }
+
+        match failures.iter().min() {
+            Some(index) => Err(CredentialError::BatchVerificationFailure{ index: *index }),
+            None        => Ok(()),
+        }
+    }
+
+    /// Verify the Schnorr proofs of many well-formed [`ProofOfValidCredential`]s
+    /// with identical attribute types in a single combined check, without
+    /// verifying their proofs of encryption or locating any which fail.
+    fn verify_batchable_proofs(
+        proofs: &[&ProofOfValidCredential],
+        issuer: &Issuer,
+    ) -> Result<(), CredentialError>
+    {
+        if proofs.is_empty() {
+            return Ok(());
+        }
+
+        let NUMBER_OF_ATTRIBUTES = issuer.system_parameters.NUMBER_OF_ATTRIBUTES as usize;
+        let encrypted_attributes = &proofs[0].commitments.encrypted_attributes;
+
+        // Create a transcript for each proof, bound to its challenge if any, and a batch verifier.
+        let mut transcripts: Vec<Transcript> = proofs.iter().map(|p| {
+            let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+
+            if let Some(challenge) = &p.challenge {
+                challenge.commit(&mut transcript);
+            }
+            transcript
+        }).collect();
+        let mut verifier = BatchVerifier::new(b"2019/1416 presentation proof", proofs.len(), transcripts.iter_mut().collect())?;
+
+        // Feed the domain separators for the Camenisch-Stadler secrets into the protocol transcripts.
+        let z   = verifier.allocate_scalar(b"z");
+        let z_0 = verifier.allocate_scalar(b"z_0");
+        let t   = verifier.allocate_scalar(b"t");
+
+        let mut H_s = BatchVerifierHiddenScalars(Vec::new());
+
+        for (i, attribute) in encrypted_attributes.iter().enumerate() {
+            if let EncryptedAttribute::SecretScalar = attribute {
+                H_s.push((i, verifier.allocate_scalar(b"m")));
+            }
+        }
+
+        // The proofs in a batch either all have hidden group element
+        // attributes, at the same indices, or none do.
+        let mut a_a0_a1: Option<(BatchVerifierScalarVar, BatchVerifierScalarVar, BatchVerifierScalarVar)> = None;
+
+        if !proofs[0].commitments.proofs_of_encryption.is_empty() {
+            a_a0_a1 = Some((verifier.allocate_scalar(b"a"),
+                            verifier.allocate_scalar(b"a0"),
+                            verifier.allocate_scalar(b"a1")));
+        }
+
+        // Feed in the domain separators and values for the publics into the
+        // transcripts, in the same order as for a single verifier.
+        let Z     = verifier.allocate_instance_point(b"Z", proofs.iter().map(|p| p.commitments.compute_Z(issuer).compress()).collect())?;
+        let I     = verifier.allocate_static_point(b"I", issuer.issuer_parameters.I.compress())?;
+        let C_x_1 = verifier.allocate_instance_point(b"C_x_1", proofs.iter().map(|p| p.commitments.C_x_1.compress()).collect())?;
+        let C_x_0 = verifier.allocate_instance_point(b"C_x_0", proofs.iter().map(|p| p.commitments.C_x_0.compress()).collect())?;
+        let G_x_0 = verifier.allocate_static_point(b"G_x_0", issuer.system_parameters.G_x_0.compress())?;
+        let G_x_1 = verifier.allocate_static_point(b"G_x_1", issuer.system_parameters.G_x_1.compress())?;
+
+        let mut C_y: Vec<(usize, BatchVerifierPointVar)> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+        let mut G_y: Vec<BatchVerifierPointVar> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+
+        for i in 0..NUMBER_OF_ATTRIBUTES {
+            match encrypted_attributes[i] {
+                EncryptedAttribute::SecretPoint => continue,
+                _ => C_y.push((i, verifier.allocate_instance_point(b"C_y", proofs.iter().map(|p| p.commitments.C_y[i].compress()).collect())?)),
+            };
+        }
+
+        for basepoint in issuer.system_parameters.G_y.iter() {
+            G_y.push(verifier.allocate_static_point(b"G_y", basepoint.compress())?);
+        }
+
+        let mut G_m = BatchVerifierHiddenScalarBasepoints(Vec::with_capacity(H_s.0.len()));
+
+        for (i, _) in H_s.0.iter() {
+            G_m.push((*i, verifier.allocate_static_point(b"G_m", issuer.system_parameters.G_m[*i].compress())?));
+        }
+
+        // The proofs in a batch either all have a pseudonym for the same
+        // attribute or none do, but their scopes may differ.
+        let mut P_H: Option<(usize, BatchVerifierPointVar, BatchVerifierPointVar)> = None;
+
+        if let Some((scope, _)) = &proofs[0].pseudonym {
+            let pseudonyms: Option<Vec<(CompressedRistretto, CompressedRistretto)>> = proofs.iter().map(|p| {
+                p.pseudonym.as_ref().map(|(scope, P)| (P.0.compress(), scope.generator().compress()))
+            }).collect();
+            let (P_, H_): (Vec<_>, Vec<_>) = pseudonyms.ok_or(CredentialError::VerificationFailure)?.into_iter().unzip();
+
+            let P = verifier.allocate_instance_point(b"P", P_)?;
+            let H = verifier.allocate_instance_point(b"H_scope", H_)?;
+
+            P_H = Some((scope.index as usize, P, H));
+        }
+
+        // Likewise for serial numbers.
+        let mut S_H: Option<(usize, BatchVerifierPointVar, BatchVerifierPointVar)> = None;
+
+        if let Some((index, _)) = &proofs[0].serial_number {
+            let serial_numbers: Option<Vec<CompressedRistretto>> = proofs.iter().map(|p| {
+                p.serial_number.map(|(_, S)| S.0.compress())
+            }).collect();
+
+            let S = verifier.allocate_instance_point(b"S", serial_numbers.ok_or(CredentialError::VerificationFailure)?)?;
+            let H = verifier.allocate_static_point(b"H_serial", SerialNumber::generator().compress())?;
+
+            S_H = Some((*index as usize, S, H));
+        }
+
+        let mut pk_G_a: Option<(BatchVerifierPointVar, BatchVerifierPointVar, BatchVerifierPointVar, BatchVerifierPointVar)> = None;
+        let mut E: Vec<(usize, BatchVerifierPointVar, BatchVerifierPointVar)> = Vec::with_capacity(proofs[0].commitments.proofs_of_encryption.len());
+
+        if a_a0_a1.is_some() {
+            let pk    = verifier.allocate_instance_point(b"pk", proofs.iter().map(|p| {
+                p.commitments.proofs_of_encryption[0].1.public_key.pk.compress()
+            }).collect())?;
+            let G_a   = verifier.allocate_static_point(b"G_a",   issuer.system_parameters.G_a.compress())?;
+            let G_a_0 = verifier.allocate_static_point(b"G_a_0", issuer.system_parameters.G_a0.compress())?;
+            let G_a_1 = verifier.allocate_static_point(b"G_a_1", issuer.system_parameters.G_a1.compress())?;
+
+            pk_G_a = Some((pk, G_a, G_a_0, G_a_1));
+        }
+
+        for (k, (i, _)) in proofs[0].commitments.proofs_of_encryption.iter().enumerate() {
+            let index = *i as usize;
+            let C_y_i_minus_E2 = verifier.allocate_instance_point(b"C_y-E2", proofs.iter().map(|p| {
+                (p.commitments.C_y[index] - p.commitments.proofs_of_encryption[k].1.ciphertext.E2).compress()
+            }).collect())?;
+            let minus_E1 = verifier.allocate_instance_point(b"-E1", proofs.iter().map(|p| {
+                (-p.commitments.proofs_of_encryption[k].1.ciphertext.E1).compress()
+            }).collect())?;
+
+            E.push((index, C_y_i_minus_E2, minus_E1));
+        }
+
+        // Constraint #1: Z = I * z
+        verifier.constrain(Z, vec![(z, I)]);
+
+        // Constraint #2: C_x_1 = C_x_0 * t + G_x_0 * z_0 + G_x_1 * z
+        verifier.constrain(C_x_1, vec![(t, C_x_0), (z_0, G_x_0), (z, G_x_1)]);
+
+        // Constraint #3: C_y_i = { G_y_i * z + G_m_i * m_i          if i is a hidden scalar attribute
+        //                        { G_y_i * z                        if i is a revealed attribute
+        for (i, C_y_i) in C_y.iter() {
+            match encrypted_attributes[*i] {
+                EncryptedAttribute::SecretScalar => verifier.constrain(*C_y_i, vec![(z, G_y[*i]), (H_s[*i], G_m[*i])]),
+                _                                => verifier.constrain(*C_y_i, vec![(z, G_y[*i])]),
+            }
+        }
+
+        // Constraint #4: P = H(scope) * m_i
+        if let Some((i, P, H)) = P_H {
+            verifier.constrain(P, vec![(H_s[i], H)]);
+        }
+
+        // Constraint #5: S = H_serial * m_i
+        if let Some((i, S, H)) = S_H {
+            verifier.constrain(S, vec![(H_s[i], H)]);
+        }
+
+        // Constraint #6: pk = G_a * a + G_a0 * a0 + G_a1 * a1
+        // Constraint #7: C_y_i - E2_i = G_y_i * z - E1_i * a
+        if let (Some((a, a0, a1)), Some((pk, G_a, G_a_0, G_a_1))) = (a_a0_a1, pk_G_a) {
+            verifier.constrain(pk, vec![(a, G_a), (a0, G_a_0), (a1, G_a_1)]);
+
+            for (i, C_y_i_minus_E2, minus_E1) in E.iter() {
+                verifier.constrain(*C_y_i_minus_E2, vec![(z, G_y[*i]), (a, *minus_E1)]);
+            }
+        }
+
+        let batchable_proofs: Vec<BatchableProof> = proofs.iter().map(|p| p.proof.clone()).collect();
+
+        verifier.verify_batchable(&batchable_proofs).or_else(|_| Err(CredentialError::VerificationFailure))
+    }
+}
+
+impl ProofOfValidCredential {
+    /// Deserialise a [`ProofOfValidCredential`] from bytes.
+    ///
+    /// The format is a version byte, the issuer's [`KeyId`], the number of
+    /// attributes `n`, the `n` tagged attributes, the commitments
+    /// \( (C_x_0, C_x_1, C_V) \), the `n`
+    /// commitments \( C_y \), the number of proofs of encryption followed by
+    /// each of them, the number of range proofs followed by each of them, a
+    /// byte which is one if there is a pseudonym and otherwise zero, the
+    /// pseudonym's attribute index, scope label length, scope label, and the
+    /// pseudonym itself if there is one, a byte which is one if there is a
+    /// serial number and otherwise zero, its attribute index and the serial
+    /// number itself if there is one, a byte which is one if there is a
+    /// challenge and otherwise zero, its nonce, context length as two
+    /// little-endian bytes, and context if there is one, a byte which is one
+    /// if there is a rate limit and otherwise zero, its seed attribute index,
+    /// label length, label, epoch, limit, tag, and the responses for each
+    /// counter if there is one, a byte which is one if there is an escrow and
+    /// otherwise zero, and the escrow if there is one, and finally the proof
+    /// itself.  All points are compressed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ProofOfValidCredential, CredentialError> {
+        if bytes.len() < 1 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+        if bytes[0] != PROOF_FORMAT_VERSION {
+            return Err(CredentialError::UnsupportedVersion);
+        }
+
+        let (commitments, length) = CredentialCommitments::from_bytes(&bytes[1..])?;
+        let mut index: usize = 1 + length;
+
+        if bytes.len() < index + 1 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let number_of_range_proofs: usize = bytes[index] as usize; index += 1;
+        let mut range_proofs: Vec<RangeProof> = Vec::with_capacity(number_of_range_proofs);
+
+        for _ in 0..number_of_range_proofs {
+            if bytes.len() < index + SIZEOF_RANGE_PROOF {
+                return Err(CredentialError::WrongNumberOfBytes);
+            }
+            range_proofs.push(RangeProof::from_bytes(&bytes[index..index+SIZEOF_RANGE_PROOF])?);
+            index += SIZEOF_RANGE_PROOF;
+        }
+
+        if bytes.len() < index + 1 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let has_pseudonym = bytes[index]; index += 1;
+
+        let pseudonym: Option<(Scope, Pseudonym)> = match has_pseudonym {
+            0 => None,
+            1 => {
+                if bytes.len() < index + 3 {
+                    return Err(CredentialError::WrongNumberOfBytes);
+                }
+
+                let mut u16_bytes = [0u8; 2];
+
+                u16_bytes.copy_from_slice(&bytes[index..index+2]); index += 2;
+                let scope_index = u16::from_le_bytes(u16_bytes);
+                let label_length = bytes[index] as usize; index += 1;
+
+                if bytes.len() < index + label_length + 32 {
+                    return Err(CredentialError::WrongNumberOfBytes);
+                }
+
+                let label = bytes[index..index+label_length].to_vec(); index += label_length;
+                let P = Pseudonym::from_bytes(&bytes[index..index+32])?; index += 32;
+
+                Some((Scope { index: scope_index, label }, P))
+            },
+            _ => return Err(CredentialError::BadAttribute),
+        };
+
+        if bytes.len() < index + 1 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let has_serial_number = bytes[index]; index += 1;
+
+        let serial_number: Option<(u16, SerialNumber)> = match has_serial_number {
+            0 => None,
+            1 => {
+                if bytes.len() < index + 2 + 32 {
+                    return Err(CredentialError::WrongNumberOfBytes);
+                }
+
+                let mut u16_bytes = [0u8; 2];
+
+                u16_bytes.copy_from_slice(&bytes[index..index+2]); index += 2;
+                let S = SerialNumber::from_bytes(&bytes[index..index+32])?; index += 32;
+
+                Some((u16::from_le_bytes(u16_bytes), S))
+            },
+            _ => return Err(CredentialError::BadAttribute),
+        };
+
+        if bytes.len() < index + 1 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let has_challenge = bytes[index]; index += 1;
+
+        let challenge: Option<Challenge> = match has_challenge {
+            0 => None,
+            1 => {
+                let (challenge, length) = Challenge::from_bytes(&bytes[index..])?;
+
+                index += length;
+                Some(challenge)
+            },
+            _ => return Err(CredentialError::BadAttribute),
+        };
+
+        if bytes.len() < index + 1 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let has_rate_limit = bytes[index]; index += 1;
+
+        let rate_limit: Option<RateLimitProof> = match has_rate_limit {
+            0 => None,
+            1 => {
+                let (rate_limit_proof, length) = RateLimitProof::from_bytes(&bytes[index..])?;
+
+                index += length;
+                Some(rate_limit_proof)
+            },
+            _ => return Err(CredentialError::BadAttribute),
+        };
+
+        if bytes.len() < index + 1 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let has_escrow = bytes[index]; index += 1;
+
+        let escrow: Option<ProofOfEscrow> = match has_escrow {
+            0 => None,
+            1 => {
+                let (proof_of_escrow, length) = ProofOfEscrow::from_bytes(&bytes[index..])?;
+
+                index += length;
+                Some(proof_of_escrow)
+            },
+            _ => return Err(CredentialError::BadAttribute),
+        };
+
+        // The proof has a commitment for each of the first two constraints,
+        // for each attribute, for the pseudonym and serial number if any, and
+        // for the symmetric key if there are hidden group element attributes,
+        // and responses for z, z_0, t, each hidden scalar attribute, and the
+        // symmetric key's a, a0, and a1 if any.
+        let has_symmetric_key = (commitments.number_of_hidden_points() > 0) as usize;
+        let number_of_commitments = 2 + commitments.encrypted_attributes.len() + has_symmetric_key +
+            pseudonym.iter().count() + serial_number.iter().count();
+        let number_of_responses = 3 + commitments.number_of_hidden_scalars() + 3 * has_symmetric_key;
+
+        if bytes.len() - index != 32 * (number_of_commitments + number_of_responses) {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let proof = batchable_proof_from_bytes(&bytes[index..], number_of_commitments)?;
+
+        Ok(ProofOfValidCredential {
+            commitments, proof, range_proofs, pseudonym, serial_number, challenge, rate_limit, escrow,
+        })
+    }
+
+    /// Serialise this [`ProofOfValidCredential`] to a byte array.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::new();
+
+        v.push(PROOF_FORMAT_VERSION);
+        self.commitments.to_bytes(&mut v);
+
+        v.push(self.range_proofs.len() as u8);
+
+        for range_proof in self.range_proofs.iter() {
+            v.extend(range_proof.to_bytes());
+        }
+
+        match &self.pseudonym {
+            None => v.push(0),
+            Some((scope, P)) => {
+                v.push(1);
+                v.extend(scope.index.to_le_bytes().iter());
+                v.push(scope.label.len() as u8);
+                v.extend(scope.label.iter());
+                v.extend(P.to_bytes().iter());
+            },
+        }
+
+        match &self.serial_number {
+            None => v.push(0),
+            Some((index, S)) => {
+                v.push(1);
+                v.extend(index.to_le_bytes().iter());
+                v.extend(S.to_bytes().iter());
+            },
+        }
+
+        match &self.challenge {
+            None => v.push(0),
+            Some(challenge) => {
+                v.push(1);
+                challenge.to_bytes(&mut v);
+            },
+        }
+
+        match &self.rate_limit {
+            None => v.push(0),
+            Some(rate_limit_proof) => {
+                v.push(1);
+                rate_limit_proof.to_bytes(&mut v);
+            },
+        }
+
+        match &self.escrow {
+            None => v.push(0),
+            Some(proof_of_escrow) => {
+                v.push(1);
+                proof_of_escrow.to_bytes(&mut v);
+            },
+        }
+
+        batchable_proof_to_bytes(&self.proof, &mut v);
+        v
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(ProofOfValidCredential, "A valid byte sequence representing a ProofOfValidCredential");
+
+/// A set of hidden scalar attributes across several credentials which are
+/// proven to be equal, each given as the index of a credential in a
+/// [`ProofOfLinkedCredentials`] and the index of an attribute within it.
+pub type Link = Vec<(usize, usize)>;
+
+/// Check that every attribute in the `links` is a hidden scalar attribute in
+/// one of the presentations described by `layouts`, and that no attribute
+/// appears in more than one place.
+fn check_links(
+    links: &[Link],
+    layouts: &[&[EncryptedAttribute]],
+) -> Result<(), CredentialError>
+{
+    if links.len() > u8::max_value() as usize {
+        return Err(CredentialError::BadAttribute);
+    }
+
+    let mut seen: Vec<(usize, usize)> = Vec::new();
+
+    for link in links.iter() {
+        if link.is_empty() || link.len() > u8::max_value() as usize {
+            return Err(CredentialError::BadAttribute);
+        }
+        for (a, i) in link.iter() {
+            match layouts.get(*a).and_then(|layout| layout.get(*i)) {
+                Some(EncryptedAttribute::SecretScalar) => (),
+                _ => return Err(CredentialError::BadAttribute),
+            }
+            if seen.contains(&(*a, *i)) {
+                return Err(CredentialError::BadAttribute);
+            }
+            seen.push((*a, *i));
+        }
+    }
+
+    Ok(())
+}
+
+/// A proof-of-knowledge of several valid credentials, possibly from different
+/// issuers, which are presented together and whose hidden scalar attributes
+/// may be proven to be equal without being revealed.
+///
+/// For example, a user may prove that two credentials were issued to the same
+/// user ID, by linking the attributes holding it.  All credentials are proven
+/// in a single transcript, and each set of linked attributes is represented
+/// by a single secret variable, so the responses for them are shared.
+pub struct ProofOfLinkedCredentials {
+    proof: CompactProof,
+    presentations: Vec<CredentialCommitments>,
+    links: Vec<Link>,
+    challenge: Option<Challenge>,
+}
+
+impl ProofOfLinkedCredentials {
+    /// Create a [`ProofOfLinkedCredentials`].
+    ///
+    /// # Inputs
+    ///
+    /// * The `credentials` to present, each with the system and issuer
+    ///   parameters under which it was issued,
+    /// * The `links` between their hidden scalar attributes,
+    /// * A symmetric `keypair`, if any credential has hidden group element attributes,
+    /// * A verifier's `challenge` to which to bind the presentation, if any,
+    /// * A `csprng`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`ProofOfLinkedCredentials`],
+    /// otherwise a [`CredentialError`], which is [`CredentialError::BadAttribute`]
+    /// if a link refers to anything but a hidden scalar attribute, or if the
+    /// linked attributes are not all equal.
+    pub fn prove<C>(
+        credentials: &[(&SystemParameters, &IssuerParameters, &AnonymousCredential)],
+        links: &[Link],
+        keypair: Option<&SymmetricKeypair>,
+        challenge: Option<&Challenge>,
+        csprng: &mut C,
+    ) -> Result<ProofOfLinkedCredentials, CredentialError>
+    where
+        C: RngCore + CryptoRng,
+    {
+        if credentials.is_empty() || credentials.len() > u8::max_value() as usize {
+            return Err(CredentialError::MissingData);
+        }
+
+        if let Some(challenge) = challenge {
+            if challenge.context.len() > u16::max_value() as usize {
+                return Err(CredentialError::WrongNumberOfBytes);
+            }
+        }
+
+        let mut presentations: Vec<CredentialCommitments> = Vec::with_capacity(credentials.len());
+        let mut nonces: Vec<(Scalar, RistrettoPoint)> = Vec::with_capacity(credentials.len());
+
+        for (system_parameters, issuer_parameters, credential) in credentials.iter() {
+            let (presentation, z, Z) = CredentialCommitments::commit(system_parameters, issuer_parameters,
+                                                                  credential, keypair, csprng)?;
+            presentations.push(presentation);
+            nonces.push((z, Z));
+        }
+
+        let layouts: Vec<&[EncryptedAttribute]> = presentations.iter().map(|p| &p.encrypted_attributes[..]).collect();
+
+        check_links(links, &layouts)?;
+
+        // Fetch the hidden scalar attribute at (a, i), which check_links()
+        // has ensured exists.
+        let secret_scalar = |a: usize, i: usize| -> Scalar {
+            match credentials[a].2.attributes[i] {
+                Attribute::SecretScalar(m) => m,
+                _ => Scalar::zero(),
+            }
+        };
+
+        for link in links.iter() {
+            let (a, i) = link[0];
+
+            if link.iter().any(|(b, j)| secret_scalar(*b, *j) != secret_scalar(a, i)) {
+                return Err(CredentialError::BadAttribute);
+            }
+        }
+
+        // Create a transcript, bound to the verifier's challenge if any, and a prover.
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+
+        if let Some(challenge) = challenge {
+            challenge.commit(&mut transcript);
+        }
+
+        let mut prover = Prover::new(b"2019/1416 linked presentation proof", &mut transcript);
+
+        // Allocate a single variable for each set of linked attributes.
+        let shared: Vec<ProverScalarVar> = links.iter().map(|link| {
+            prover.allocate_scalar(b"m", secret_scalar(link[0].0, link[0].1))
+        }).collect();
+
+        for (a, (system_parameters, issuer_parameters, credential)) in credentials.iter().enumerate() {
+            let presentation = &presentations[a];
+            let (z_, Z_) = nonces[a];
+            let z_0_: Scalar = (-credential.amac.t * z_).reduce();
+
+            let z   = prover.allocate_scalar(b"z", z_);
+            let z_0 = prover.allocate_scalar(b"z_0", z_0_);
+            let t   = prover.allocate_scalar(b"t", credential.amac.t);
+
+            let mut H_s = ProverHiddenScalars(Vec::new());
+
+            for (i, attribute) in presentation.encrypted_attributes.iter().enumerate() {
+                if let EncryptedAttribute::SecretScalar = attribute {
+                    match links.iter().position(|link| link.contains(&(a, i))) {
+                        Some(l) => H_s.push((i, shared[l])),
+                        None    => H_s.push((i, prover.allocate_scalar(b"m", secret_scalar(a, i)))),
+                    }
+                }
+            }
+
+            let mut a_a0_a1: Option<(ProverScalarVar, ProverScalarVar, ProverScalarVar)> = None;
+
+            if !presentation.proofs_of_encryption.is_empty() {
+                let secret = &keypair.ok_or(CredentialError::NoSymmetricKey)?.secret;
+
+                a_a0_a1 = Some((prover.allocate_scalar(b"a",  secret.a),
+                                prover.allocate_scalar(b"a0", secret.a0),
+                                prover.allocate_scalar(b"a1", secret.a1)));
+            }
+
+            let (Z, _)     = prover.allocate_point(b"Z", Z_);
+            let (I, _)     = prover.allocate_point(b"I", issuer_parameters.I);
+            let (C_x_1, _) = prover.allocate_point(b"C_x_1", presentation.C_x_1);
+            let (C_x_0, _) = prover.allocate_point(b"C_x_0", presentation.C_x_0);
+            let (G_x_0, _) = prover.allocate_point(b"G_x_0", system_parameters.G_x_0);
+            let (G_x_1, _) = prover.allocate_point(b"G_x_1", system_parameters.G_x_1);
+
+            let mut C_y: Vec<(usize, ProverPointVar)> = Vec::new();
+            let mut G_y: Vec<ProverPointVar> = Vec::new();
+
+            for (i, commitment) in presentation.C_y.iter().enumerate() {
+                match presentation.encrypted_attributes[i] {
+                    EncryptedAttribute::SecretPoint => continue,
+                    _ => C_y.push((i, prover.allocate_point(b"C_y", *commitment).0)),
+                };
+            }
+
+            for basepoint in system_parameters.G_y.iter() {
+                G_y.push(prover.allocate_point(b"G_y", *basepoint).0);
+            }
+
+            let mut G_m = ProverHiddenScalarBasepoints(Vec::with_capacity(H_s.0.len()));
+
+            for (i, _) in H_s.0.iter() {
+                G_m.push((*i, prover.allocate_point(b"G_m", system_parameters.G_m[*i]).0));
+            }
+
+            let mut pk_G_a: Option<(ProverPointVar, ProverPointVar, ProverPointVar, ProverPointVar)> = None;
+            let mut E: Vec<(usize, ProverPointVar, ProverPointVar)> = Vec::new();
+
+            if let Some((_, proof_of_encryption)) = presentation.proofs_of_encryption.first() {
+                pk_G_a = Some((prover.allocate_point(b"pk",    proof_of_encryption.public_key.pk).0,
+                               prover.allocate_point(b"G_a",   system_parameters.G_a).0,
+                               prover.allocate_point(b"G_a_0", system_parameters.G_a0).0,
+                               prover.allocate_point(b"G_a_1", system_parameters.G_a1).0));
+            }
+
+            for (i, proof_of_encryption) in presentation.proofs_of_encryption.iter() {
+                let ciphertext = &proof_of_encryption.ciphertext;
+
+                E.push((*i as usize,
+                        prover.allocate_point(b"C_y-E2", presentation.C_y[*i as usize] - ciphertext.E2).0,
+                        prover.allocate_point(b"-E1", -ciphertext.E1).0));
+            }
+
+            // The constraints are those of a ProofOfValidCredential.
+            prover.constrain(Z, vec![(z, I)]);
+            prover.constrain(C_x_1, vec![(t, C_x_0), (z_0, G_x_0), (z, G_x_1)]);
+
+            for (i, C_y_i) in C_y.iter() {
+                match presentation.encrypted_attributes[*i] {
+                    EncryptedAttribute::SecretScalar => prover.constrain(*C_y_i, vec![(z, G_y[*i]), (H_s[*i], G_m[*i])]),
+                    _                                => prover.constrain(*C_y_i, vec![(z, G_y[*i])]),
+                }
+            }
+
+            if let (Some((a, a0, a1)), Some((pk, G_a, G_a_0, G_a_1))) = (a_a0_a1, pk_G_a) {
+                prover.constrain(pk, vec![(a, G_a), (a0, G_a_0), (a1, G_a_1)]);
+
+                for (i, C_y_i_minus_E2, minus_E1) in E.iter() {
+                    prover.constrain(*C_y_i_minus_E2, vec![(z, G_y[*i]), (a, *minus_E1)]);
+                }
+            }
+        }
+
+        let proof = prover.prove_compact();
+
+        Ok(ProofOfLinkedCredentials { proof, presentations, links: links.to_vec(), challenge: challenge.cloned() })
+    }
+
+    /// Verify this [`ProofOfLinkedCredentials`].
+    ///
+    /// # Inputs
+    ///
+    /// * The `issuers` of the presented credentials, in the same order as
+    ///   they were given to [`ProofOfLinkedCredentials::prove`],
+    /// * The verifier's `challenge`, if the presentation must be bound to one.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`],
+    /// which is [`CredentialError::MissingData`] if a `challenge` was given
+    /// but the presentation is not bound to any challenge.
+    pub fn verify(
+        &self,
+        issuers: &[&Issuer],
+        challenge: Option<&Challenge>,
+    ) -> Result<(), CredentialError>
+    {
+        if issuers.len() != self.presentations.len() {
+            return Err(CredentialError::MissingData);
+        }
+
+        if let Some(challenge) = challenge {
+            match &self.challenge {
+                Some(c) if c == challenge => (),
+                Some(_) => return Err(CredentialError::VerificationFailure),
+                None    => return Err(CredentialError::MissingData),
+            }
+        }
+
+        for (presentation, issuer) in self.presentations.iter().zip(issuers.iter()) {
+            presentation.check_structure(issuer)?;
+        }
+
+        let layouts: Vec<&[EncryptedAttribute]> = self.presentations.iter().map(|p| &p.encrypted_attributes[..]).collect();
+
+        check_links(&self.links, &layouts)?;
+
+        // Create a transcript, bound to the presentation's challenge if any, and a verifier.
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+
+        if let Some(challenge) = &self.challenge {
+            challenge.commit(&mut transcript);
+        }
+
+        let mut verifier = Verifier::new(b"2019/1416 linked presentation proof", &mut transcript);
+
+        let shared: Vec<VerifierScalarVar> = self.links.iter().map(|_| verifier.allocate_scalar(b"m")).collect();
+
+        for (a, (presentation, issuer)) in self.presentations.iter().zip(issuers.iter()).enumerate() {
+            let z   = verifier.allocate_scalar(b"z");
+            let z_0 = verifier.allocate_scalar(b"z_0");
+            let t   = verifier.allocate_scalar(b"t");
+
+            let mut H_s = VerifierHiddenScalars(Vec::new());
+
+            for (i, attribute) in presentation.encrypted_attributes.iter().enumerate() {
+                if let EncryptedAttribute::SecretScalar = attribute {
+                    match self.links.iter().position(|link| link.contains(&(a, i))) {
+                        Some(l) => H_s.push((i, shared[l])),
+                        None    => H_s.push((i, verifier.allocate_scalar(b"m"))),
+                    }
+                }
+            }
+
+            let mut a_a0_a1: Option<(VerifierScalarVar, VerifierScalarVar, VerifierScalarVar)> = None;
+
+            if !presentation.proofs_of_encryption.is_empty() {
+                a_a0_a1 = Some((verifier.allocate_scalar(b"a"),
+                                verifier.allocate_scalar(b"a0"),
+                                verifier.allocate_scalar(b"a1")));
+            }
+
+            let Z     = verifier.allocate_point(b"Z", presentation.compute_Z(issuer).compress())?;
+            let I     = verifier.allocate_point(b"I", issuer.issuer_parameters.I.compress())?;
+            let C_x_1 = verifier.allocate_point(b"C_x_1", presentation.C_x_1.compress())?;
+            let C_x_0 = verifier.allocate_point(b"C_x_0", presentation.C_x_0.compress())?;
+            let G_x_0 = verifier.allocate_point(b"G_x_0", issuer.system_parameters.G_x_0.compress())?;
+            let G_x_1 = verifier.allocate_point(b"G_x_1", issuer.system_parameters.G_x_1.compress())?;
+
+            let mut C_y: Vec<(usize, VerifierPointVar)> = Vec::new();
+            let mut G_y: Vec<VerifierPointVar> = Vec::new();
+
+            for (i, commitment) in presentation.C_y.iter().enumerate() {
+                match presentation.encrypted_attributes[i] {
+                    EncryptedAttribute::SecretPoint => continue,
+                    _ => C_y.push((i, verifier.allocate_point(b"C_y", commitment.compress())?)),
+                };
+            }
+
+            for basepoint in issuer.system_parameters.G_y.iter() {
+                G_y.push(verifier.allocate_point(b"G_y", basepoint.compress())?);
+            }
+
+            let mut G_m = VerifierHiddenScalarBasepoints(Vec::with_capacity(H_s.0.len()));
+
+            for (i, _) in H_s.0.iter() {
+                G_m.push((*i, verifier.allocate_point(b"G_m", issuer.system_parameters.G_m[*i].compress())?));
+            }
+
+            let mut pk_G_a: Option<(VerifierPointVar, VerifierPointVar, VerifierPointVar, VerifierPointVar)> = None;
+            let mut E: Vec<(usize, VerifierPointVar, VerifierPointVar)> = Vec::new();
+
+            if let Some((_, proof_of_encryption)) = presentation.proofs_of_encryption.first() {
+                pk_G_a = Some((verifier.allocate_point(b"pk",    proof_of_encryption.public_key.pk.compress())?,
+                               verifier.allocate_point(b"G_a",   issuer.system_parameters.G_a.compress())?,
+                               verifier.allocate_point(b"G_a_0", issuer.system_parameters.G_a0.compress())?,
+                               verifier.allocate_point(b"G_a_1", issuer.system_parameters.G_a1.compress())?));
+            }
+
+            for (i, proof_of_encryption) in presentation.proofs_of_encryption.iter() {
+                let ciphertext = &proof_of_encryption.ciphertext;
+
+                E.push((*i as usize,
+                        verifier.allocate_point(b"C_y-E2", (presentation.C_y[*i as usize] - ciphertext.E2).compress())?,
+                        verifier.allocate_point(b"-E1", (-ciphertext.E1).compress())?));
+            }
+
+            verifier.constrain(Z, vec![(z, I)]);
+            verifier.constrain(C_x_1, vec![(t, C_x_0), (z_0, G_x_0), (z, G_x_1)]);
+
+            for (i, C_y_i) in C_y.iter() {
+                match presentation.encrypted_attributes[*i] {
+                    EncryptedAttribute::SecretScalar => verifier.constrain(*C_y_i, vec![(z, G_y[*i]), (H_s[*i], G_m[*i])]),
+                    _                                => verifier.constrain(*C_y_i, vec![(z, G_y[*i])]),
+                }
+            }
+
+            if let (Some((a, a0, a1)), Some((pk, G_a, G_a_0, G_a_1))) = (a_a0_a1, pk_G_a) {
+                verifier.constrain(pk, vec![(a, G_a), (a0, G_a_0), (a1, G_a_1)]);
+
+                for (i, C_y_i_minus_E2, minus_E1) in E.iter() {
+                    verifier.constrain(*C_y_i_minus_E2, vec![(z, G_y[*i]), (a, *minus_E1)]);
+                }
+            }
+        }
+
+        verifier.verify_compact(&self.proof).or_else(|_| Err(CredentialError::VerificationFailure))?;
+
+        for (presentation, issuer) in self.presentations.iter().zip(issuers.iter()) {
+            for (_, proof_of_encryption) in presentation.proofs_of_encryption.iter() {
+                proof_of_encryption.verify(&issuer.system_parameters)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl ProofOfLinkedCredentials {
+    /// Deserialise a [`ProofOfLinkedCredentials`] from bytes.
+    ///
+    /// The format is a version byte, the number of credentials followed by
+    /// the issuer's [`KeyId`], commitments, and proofs of encryption for each
+    /// of them (as in a [`ProofOfValidCredential`]), the number of links
+    /// followed by each link as its length and its pairs of a one-byte
+    /// credential index and a two-byte attribute index, a byte which is one if
+    /// there is a challenge and otherwise zero, the challenge if there is one
+    /// (as in a [`ProofOfValidCredential`]), and finally the proof itself.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ProofOfLinkedCredentials, CredentialError> {
+        if bytes.len() < 2 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+        if bytes[0] != PROOF_FORMAT_VERSION {
+            return Err(CredentialError::UnsupportedVersion);
+        }
+
+        let number_of_credentials: usize = bytes[1] as usize;
+        let mut index: usize = 2;
+        let mut presentations: Vec<CredentialCommitments> = Vec::with_capacity(number_of_credentials);
+
+        for _ in 0..number_of_credentials {
+            let (presentation, length) = CredentialCommitments::from_bytes(&bytes[index..])?;
+
+            presentations.push(presentation);
+            index += length;
+        }
+
+        if bytes.len() < index + 1 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let number_of_links: usize = bytes[index] as usize; index += 1;
+        let mut links: Vec<Link> = Vec::with_capacity(number_of_links);
+        let mut u16_bytes = [0u8; 2];
+
+        for _ in 0..number_of_links {
+            if bytes.len() < index + 1 {
+                return Err(CredentialError::WrongNumberOfBytes);
+            }
+
+            let length: usize = bytes[index] as usize; index += 1;
+
+            if bytes.len() < index + 3 * length {
+                return Err(CredentialError::WrongNumberOfBytes);
+            }
+
+            let mut link: Link = Vec::with_capacity(length);
+
+            for _ in 0..length {
+                let a = bytes[index] as usize;
+                u16_bytes.copy_from_slice(&bytes[index+1..index+3]); index += 3;
+                link.push((a, u16::from_le_bytes(u16_bytes) as usize));
+            }
+            links.push(link);
+        }
+
+        if bytes.len() < index + 1 {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let has_challenge = bytes[index]; index += 1;
+
+        let challenge: Option<Challenge> = match has_challenge {
+            0 => None,
+            1 => {
+                let (challenge, length) = Challenge::from_bytes(&bytes[index..])?;
+
+                index += length;
+                Some(challenge)
+            },
+            _ => return Err(CredentialError::BadAttribute),
+        };
+
+        // There are responses for z, z_0, and t for each credential, for a,
+        // a0, and a1 for each credential with hidden group element attributes,
+        // for each hidden scalar attribute which is not linked, and for each link.
+        let number_of_symmetric_keys = presentations.iter().filter(|p| p.number_of_hidden_points() > 0).count();
+        let number_of_hidden_scalars: usize = presentations.iter().map(|p| p.number_of_hidden_scalars()).sum();
+        let number_of_linked_scalars: usize = links.iter().map(|link| link.len()).sum();
+
+        if number_of_linked_scalars > number_of_hidden_scalars {
+            return Err(CredentialError::BadAttribute);
+        }
+
+        let number_of_responses = 3 * (number_of_credentials + number_of_symmetric_keys) + number_of_hidden_scalars -
+            number_of_linked_scalars + number_of_links;
+
+        if bytes.len() - index != 32 * (1 + number_of_responses) {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let proof = compact_proof_from_bytes(&bytes[index..])?;
+
+        Ok(ProofOfLinkedCredentials { proof, presentations, links, challenge })
+    }
+
+    /// Serialise this [`ProofOfLinkedCredentials`] to a byte array.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::new();
+
+        v.push(PROOF_FORMAT_VERSION);
+        v.push(self.presentations.len() as u8);
+
+        for presentation in self.presentations.iter() {
+            presentation.to_bytes(&mut v);
+        }
+
+        v.push(self.links.len() as u8);
+
+        for link in self.links.iter() {
+            v.push(link.len() as u8);
+
+            for (a, i) in link.iter() {
+                v.push(*a as u8);
+                v.extend((*i as u16).to_le_bytes().iter());
+            }
+        }
+
+        match &self.challenge {
+            None => v.push(0),
+            Some(challenge) => {
+                v.push(1);
+                challenge.to_bytes(&mut v);
+            },
+        }
+
+        compact_proof_to_bytes(&self.proof, &mut v);
+        v
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(ProofOfLinkedCredentials, "A valid byte sequence representing a ProofOfLinkedCredentials");
+
+/// Determine which hidden scalar attributes of a refreshed credential are
+/// linked to those of the old one, along with the public update
+/// \\( \delta_i \\) to each.
+///
+/// Every hidden scalar attribute of the new credential must be a hidden scalar
+/// attribute of the old credential, and vice versa, and updates may only be
+/// made to hidden scalar attributes.  The attribute from which the old
+/// credential's [`SerialNumber`] is derived, if any, is not linked, so that
+/// the new credential may be given a fresh one.
+fn refreshed_scalars(
+    presentation: &ProofOfValidCredential,
+    blinded_attributes: &Vec<BlindedAttribute>,
+    updates: &[(usize, Scalar)],
+) -> Result<Vec<(usize, Scalar)>, CredentialError>
+{
+    let mut linked: Vec<(usize, Scalar)> = Vec::new();
+    let n = blinded_attributes.len().max(presentation.commitments.encrypted_attributes.len());
+    let serial_number_index = presentation.serial_number.map(|(index, _)| index as usize);
+
+    for i in 0..n {
+        match (blinded_attributes.get(i), presentation.commitments.encrypted_attributes.get(i)) {
+            (Some(BlindedAttribute::SecretScalar(_)), Some(EncryptedAttribute::SecretScalar))
+                if Some(i) == serial_number_index => continue,
+            (Some(BlindedAttribute::SecretScalar(_)), Some(EncryptedAttribute::SecretScalar)) => {
+                let delta: Scalar = updates.iter().filter(|(j, _)| *j == i).map(|(_, d)| d).sum();
+
+                linked.push((i, delta));
+            },
+            (Some(BlindedAttribute::SecretScalar(_)), _) |
+            (_, Some(EncryptedAttribute::SecretScalar)) => return Err(CredentialError::BadAttribute),
+            _ => continue,
+        }
+    }
+
+    for (j, _) in updates.iter() {
+        if !linked.iter().any(|(i, _)| i == j) {
+            return Err(CredentialError::BadAttribute);
+        }
+    }
+
+    Ok(linked)
+}
+
+/// A non-interactive zero-knowledge proof demonstrating that the hidden scalar
+/// attributes of a [`CredentialRequest`] for a new credential are those
+/// committed to in a [`ProofOfValidCredential`] for an old credential, each
+/// plus some public update \\( \delta_i \\), e.g. one for a counter.
+///
+/// For each hidden scalar attribute, the user proves knowledge of \\( z \\),
+/// \\( m_i' \\), and \\( r_i \\) such that
+/// \\( C_y_i + G_m_i * \delta_i = G_y_i * z + G_m_i * m_i' \\) and that
+/// \\( (E_i1, E_i2) = (G * r_i, G_m_i * m_i' + D * r_i) \\).  Since the
+/// commitment \\( C_y_i \\) is binding, \\( m_i' = m_i + \delta_i \\), where
+/// \\( m_i \\) is the attribute on the old credential.
+pub struct ProofOfRefresh(CompactProof);
+
+impl ProofOfRefresh {
+    /// Create a [`ProofOfRefresh`].
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    /// * The `presentation` of the old credential and the nonce `z` used for
+    ///   its commitments,
+    /// * The user's ElGamal `public_key`,
+    /// * The `blinded_attributes` of the request for the new credential,
+    /// * The `hidden_scalars` of the request, as `(index, m_i', r_i)` tuples,
+    ///   where `r_i` is the nonce used for the encryption of the `i`th
+    ///   attribute, including any fresh serial number attribute, and
+    /// * The `updates` to the hidden scalar attributes, as `(index, delta_i)`
+    ///   tuples.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`ProofOfRefresh`], otherwise a
+    /// [`CredentialError`], which is [`CredentialError::BadAttribute`] if the
+    /// hidden scalar attributes of the new credential are not those of the old
+    /// credential with the `updates` applied.
+    pub(crate) fn prove(
+        system_parameters: &SystemParameters,
+        presentation: &ProofOfValidCredential,
+        z: &Scalar,
+        public_key: &ElGamalPublicKey,
+        blinded_attributes: &Vec<BlindedAttribute>,
+        hidden_scalars: &Vec<(usize, Scalar, Scalar)>,
+        updates: &[(usize, Scalar)],
+    ) -> Result<ProofOfRefresh, CredentialError>
+    {
+        let linked = refreshed_scalars(presentation, blinded_attributes, updates)?;
+        let hidden_scalars: Vec<&(usize, Scalar, Scalar)> = hidden_scalars.iter()
+            .filter(|(j, _, _)| linked.iter().any(|(i, _)| i == j))
+            .collect();
+
+        if linked.len() != hidden_scalars.len() {
+            return Err(CredentialError::BadAttribute);
+        }
+
+        // Check that each new attribute really is the old one plus its update.
+        for ((i, delta_i), (j, m_i, _)) in linked.iter().zip(hidden_scalars.iter()) {
+            let C_y_i = presentation.commitments.C_y.get(*i).ok_or(CredentialError::MissingData)?;
+
+            if i != j || C_y_i + system_parameters.G_m[*i] * delta_i !=
+                system_parameters.G_y[*i] * z + system_parameters.G_m[*i] * m_i
+            {
+                return Err(CredentialError::BadAttribute);
+            }
+        }
+
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+        let mut prover = Prover::new(b"2019/1416 refresh proof", &mut transcript);
+
+        let z      = prover.allocate_scalar(b"z", *z);
+        let (G, _) = prover.allocate_point(b"G", system_parameters.G);
+        let (D, _) = prover.allocate_point(b"D", public_key.D);
+
+        for ((i, delta_i), (_, m_i, r_i)) in linked.iter().zip(hidden_scalars.iter()) {
+            let E_i = match blinded_attributes[*i] {
+                BlindedAttribute::SecretScalar(E_i) => E_i,
+                _ => return Err(CredentialError::BadAttribute),
+            };
+
+            // XXX fix the zkp crate to take Strings
+            let m        = prover.allocate_scalar(b"m", *m_i);
+            let r        = prover.allocate_scalar(b"r", *r_i);
+            let (G_y, _) = prover.allocate_point(b"G_y", system_parameters.G_y[*i]);
+            let (G_m, _) = prover.allocate_point(b"G_m", system_parameters.G_m[*i]);
+            let (C_y, _) = prover.allocate_point(b"C_y'", presentation.commitments.C_y[*i] + system_parameters.G_m[*i] * delta_i);
+            let (E_1, _) = prover.allocate_point(b"E_1", E_i.C1);
+            let (E_2, _) = prover.allocate_point(b"E_2", E_i.C2);
+
+            // Constraint #1: The updated commitment opens to the new attribute.
+            //                C_y_i + G_m_i * delta_i = G_y_i * z + G_m_i * m_i'
+            prover.constrain(C_y, vec![(z, G_y), (m, G_m)]);
+
+            // Constraint #2: The encryption nonce is known.
+            //                E_i1 = G * r_i
+            prover.constrain(E_1, vec![(r, G)]);
+
+            // Constraint #3: The ciphertext encrypts G_m_i * m_i' to D.
+            //                E_i2 = G_m_i * m_i' + D * r_i
+            prover.constrain(E_2, vec![(m, G_m), (r, D)]);
+        }
+
+        Ok(ProofOfRefresh(prover.prove_compact()))
+    }
+
+    /// Verify a [`ProofOfRefresh`].
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    /// * The verified `presentation` of the old credential,
+    /// * The user's ElGamal `public_key`,
+    /// * The `blinded_attributes` of the request for the new credential, and
+    /// * The `updates` to the hidden scalar attributes, as `(index, delta_i)`
+    ///   tuples, which are chosen by the verifier.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`].
+    pub fn verify(
+        &self,
+        system_parameters: &SystemParameters,
+        presentation: &ProofOfValidCredential,
+        public_key: &ElGamalPublicKey,
+        blinded_attributes: &Vec<BlindedAttribute>,
+        updates: &[(usize, Scalar)],
+    ) -> Result<(), CredentialError>
+    {
+        if blinded_attributes.len() > system_parameters.NUMBER_OF_ATTRIBUTES as usize {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+        if presentation.commitments.C_y.len() != system_parameters.NUMBER_OF_ATTRIBUTES as usize {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+
+        let linked = refreshed_scalars(presentation, blinded_attributes, updates)?;
+
+        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
+        let mut verifier = Verifier::new(b"2019/1416 refresh proof", &mut transcript);
+
+        let z = verifier.allocate_scalar(b"z");
+        let G = verifier.allocate_point(b"G", system_parameters.G.compress())?;
+        let D = verifier.allocate_point(b"D", public_key.D.compress())?;
+
+        for (i, delta_i) in linked.iter() {
+            let E_i = match blinded_attributes[*i] {
+                BlindedAttribute::SecretScalar(E_i) => E_i,
+                _ => return Err(CredentialError::BadAttribute),
+            };
+            let C_y_i = presentation.commitments.C_y[*i] + system_parameters.G_m[*i] * delta_i;
+
+            // XXX fix the zkp crate to take Strings
+            let m   = verifier.allocate_scalar(b"m");
+            let r   = verifier.allocate_scalar(b"r");
+            let G_y = verifier.allocate_point(b"G_y", system_parameters.G_y[*i].compress())?;
+            let G_m = verifier.allocate_point(b"G_m", system_parameters.G_m[*i].compress())?;
+            let C_y = verifier.allocate_point(b"C_y'", C_y_i.compress())?;
+            let E_1 = verifier.allocate_point(b"E_1", E_i.C1.compress())?;
+            let E_2 = verifier.allocate_point(b"E_2", E_i.C2.compress())?;
+
+            // Constraint #1: The updated commitment opens to the new attribute.
+            //                C_y_i + G_m_i * delta_i = G_y_i * z + G_m_i * m_i'
+            verifier.constrain(C_y, vec![(z, G_y), (m, G_m)]);
+
+            // Constraint #2: The encryption nonce is known.
+            //                E_i1 = G * r_i
+            verifier.constrain(E_1, vec![(r, G)]);
+
+            // Constraint #3: The ciphertext encrypts G_m_i * m_i' to D.
+            //                E_i2 = G_m_i * m_i' + D * r_i
+            verifier.constrain(E_2, vec![(m, G_m), (r, D)]);
+        }
+
+        verifier.verify_compact(&self.0).or_else(|_| Err(CredentialError::VerificationFailure))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use core::convert::TryFrom;
+
+    use curve25519_dalek::traits::IsIdentity;
+
+    use rand::thread_rng;
+
+    use crate::credential::CredentialRequest;
+    use crate::encoding::decode_from_group;
+    use crate::encoding::number_of_chunks;
+
+    #[test]
+    fn issuance_proof() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 5).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let plaintext = Plaintext::try_from(&[1u8; 30][..]).unwrap();
+
+        let mut attributes = Vec::new();
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
+        attributes.push(Attribute::SecretPoint(plaintext));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential { amac: response.amac, attributes };
+        let verification = response.proof.verify(&system_parameters, &issuer.issuer_parameters, &credential);
+
+        assert!(verification.is_ok());
+    }
+
+    /// An all-zero plaintext does not encode to the identity element, and so
+    /// its issuance proof verifies.
+    #[test]
+    fn issuance_proof_zero_plaintext() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 5).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let plaintext = Plaintext::try_from(&[0u8; 30][..]).unwrap();
+
+        assert!(!plaintext.M1.is_identity());
+
+        let mut attributes = Vec::new();
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
+        attributes.push(Attribute::SecretPoint(plaintext));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential { amac: response.amac, attributes };
+        let verification = response.proof.verify(&system_parameters, &issuer.issuer_parameters, &credential);
+
+        assert!(verification.is_ok());
+    }
+
+    #[test]
+    fn issuance_proof_zero_scalar() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+
+        let mut attributes = Vec::new();
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::zero()));
+        attributes.push(Attribute::SecretScalar(Scalar::zero()));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential { amac: response.amac, attributes };
+        let verification = response.proof.verify(&system_parameters, &issuer.issuer_parameters, &credential);
+
+        assert!(verification.is_ok());
+    }
+
+    #[test]
+    fn blinded_issuance_proof() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 5).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let plaintext = Plaintext::try_from(&[1u8; 30][..]).unwrap();
+
+        let mut attributes = Vec::new();
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
+        attributes.push(Attribute::SecretPoint(plaintext));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+
+        let keypair = ElGamalKeypair::generate(&system_parameters, &mut rng);
+        let request = CredentialRequest::new(&system_parameters, &attributes, &keypair, &mut rng).unwrap();
+        let response = issuer.issue_blinded(&request, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_blinded_issuance_response(&system_parameters,
+                                                                             &issuer.issuer_parameters,
+                                                                             &request, &response,
+                                                                             &keypair, attributes).unwrap();
+
+        assert!(credential.amac.verify(&system_parameters, &issuer.amacs_key, &credential.attributes));
+    }
+
+    #[test]
+    fn blinded_issuance_proof_wrong_issuer_parameters() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 2).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let other_issuer = Issuer::new(&system_parameters, &mut rng);
+
+        let mut attributes = Vec::new();
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+
+        let keypair = ElGamalKeypair::generate(&system_parameters, &mut rng);
+        let request = CredentialRequest::new(&system_parameters, &attributes, &keypair, &mut rng).unwrap();
+        let response = issuer.issue_blinded(&request, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_blinded_issuance_response(&system_parameters,
+                                                                             &other_issuer.issuer_parameters,
+                                                                             &request, &response,
+                                                                             &keypair, attributes);
+
+        assert!(credential.is_err());
+    }
+
+    #[test]
+    fn encryption_proof() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 5).unwrap();
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let z = Scalar::random(&mut rng);
+        let message1: &[u8] = b"This is a tsunami alert test";
+        let plaintext = Plaintext::try_from(message1).unwrap();
+
+        let proof = ProofOfEncryption::prove(&system_parameters, &plaintext, 1u16, &keypair, &z, &mut rng);
+        let decryption = keypair.decrypt(&proof.ciphertext).unwrap();
+
+        assert!(decryption.M1 == plaintext.M1);
+        assert!(decryption.M2 == plaintext.M2);
+        assert!(decryption.m3 == plaintext.m3);
+
+        let message2 = Vec::<u8>::try_from(&decryption).unwrap();
+
+        assert_eq!(message1, &message2[..]);
+
+        let verification = proof.verify(&system_parameters);
+
+        assert!(verification.is_ok());
+    }
+
+    #[test]
+    fn credential_proof_8_attributes() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 8).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretPoint(plaintext));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let proof = ProofOfValidCredential::prove(&system_parameters, &issuer.issuer_parameters, &credential, Some(&keypair), &mut rng);
+
+        assert!(proof.is_ok());
+
+        let verification = proof.unwrap().verify(&issuer);
+
+        assert!(verification.is_ok());
+    }
+
+    #[test]
+    fn credential_proof_1_attribute() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 1).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretPoint(plaintext));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let proof = ProofOfValidCredential::prove(&system_parameters, &issuer.issuer_parameters, &credential, Some(&keypair), &mut rng);
+
+        assert!(proof.is_ok());
+
+        let verification = proof.unwrap().verify(&issuer);
+
+        assert!(verification.is_ok());
+    }
+
+    /// A proof of encryption made with a nonce other than the presentation's
+    /// verifies by itself, but its ciphertext is of a different group element
+    /// than the one committed to, and so the presentation must not verify.
+    #[test]
+    fn credential_proof_encryption_with_other_nonce() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretPoint(plaintext.clone()));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let (mut proof, z) = ProofOfValidCredential::prove_with_nonce(&system_parameters, &issuer.issuer_parameters,
+                                                                      &credential, Some(&keypair),
+                                                                      &PresentationOptions::default(), &mut rng).unwrap();
+
+        assert!(proof.verify(&issuer).is_ok());
+
+        // Encrypt M1 + G_y_0 * (z - z') instead, which has the same commitment under z'.
+        let z_prime = Scalar::random(&mut rng);
+        let shifted = Plaintext {
+            M1: plaintext.M1 + system_parameters.G_y[0] * (z - z_prime),
+            M2: plaintext.M2,
+            m3: plaintext.m3,
+        };
+        let proof_of_encryption = ProofOfEncryption::prove(&system_parameters, &shifted, 0, &keypair, &z_prime, &mut rng);
+
+        assert!(proof_of_encryption.verify(&system_parameters).is_ok());
+        assert_eq!(proof_of_encryption.C_y_1, proof.commitments.C_y[0]);
+
+        proof.commitments.proofs_of_encryption[0].1 = proof_of_encryption;
+
+        assert!(proof.check_structure(&issuer).is_ok());
+        assert_eq!(proof.verify(&issuer).err(), Some(CredentialError::VerificationFailure));
+        assert_eq!(ProofOfValidCredential::verify_batch(&[proof], &issuer),
+                   Err(CredentialError::BatchVerificationFailure{ index: 0 }));
+    }
+
+    #[test]
+    fn credential_proof_fewer_attributes() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 4).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let proof = ProofOfValidCredential::prove(&system_parameters, &issuer.issuer_parameters, &credential, None, &mut rng);
+        let verification = proof.unwrap().verify(&issuer);
+
+        assert!(verification.is_ok());
+    }
+
+    #[test]
+    fn credential_proof_wrong_issuer() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let other_issuer = Issuer::new(&system_parameters, &mut rng);
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::SecretPoint(plaintext));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let proof = ProofOfValidCredential::prove(&system_parameters, &issuer.issuer_parameters, &credential, Some(&keypair), &mut rng);
+        let verification = proof.unwrap().verify(&other_issuer);
+
+        assert!(verification.is_err());
+    }
+
+    #[test]
+    fn credential_proof_blinded_issuance() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::SecretPoint(plaintext));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+
+        let elgamal_keypair = ElGamalKeypair::generate(&system_parameters, &mut rng);
+        let request = CredentialRequest::new(&system_parameters, &attributes, &elgamal_keypair, &mut rng).unwrap();
+        let response = issuer.issue_blinded(&request, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_blinded_issuance_response(&system_parameters,
+                                                                             &issuer.issuer_parameters,
+                                                                             &request, &response,
+                                                                             &elgamal_keypair, attributes).unwrap();
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let proof = credential.show(&system_parameters, &issuer.issuer_parameters, Some(&keypair), &mut rng);
+        let verification = proof.unwrap().verify(&issuer);
+
+        assert!(verification.is_ok());
+    }
+
+    #[test]
+    fn issuance_proof_serialize_deserialize() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 2).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+
+        let mut attributes = Vec::new();
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let deserialized = ProofOfIssuance::from_bytes(&response.proof.to_bytes()).unwrap();
+        let credential = AnonymousCredential { amac: response.amac, attributes };
+
+        assert!(deserialized.verify(&system_parameters, &issuer.issuer_parameters, &credential).is_ok());
+    }
+
+    #[test]
+    fn credential_proof_serialize_deserialize() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 4).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretPoint(plaintext));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let proof = credential.show(&system_parameters, &issuer.issuer_parameters, Some(&keypair), &mut rng).unwrap();
+        let bytes = proof.to_bytes();
+        let deserialized = ProofOfValidCredential::from_bytes(&bytes).unwrap();
+
+        assert_eq!(deserialized.to_bytes(), bytes);
+        assert!(deserialized.verify(&issuer).is_ok());
+    }
+
+    #[test]
+    fn credential_proof_deserialize_malformed() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 2).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretPoint(plaintext));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let proof = credential.show(&system_parameters, &issuer.issuer_parameters, Some(&keypair), &mut rng).unwrap();
+        let mut bytes = proof.to_bytes();
+
+        assert!(ProofOfValidCredential::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(ProofOfEncryption::from_bytes(&proof.commitments.proofs_of_encryption[0].1.to_bytes()[1..]).is_err());
+
+        bytes[0] = PROOF_FORMAT_VERSION + 1;
+
+        assert!(ProofOfValidCredential::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn credential_proof_byte_string_attributes() {
+        let mut rng = thread_rng();
+        let email = b"a.very.long.email.address.for.testing@example.com";
+        let claims = b"{\"role\":\"admin\",\"expires\":\"never\",\"padding\":\"0000000000\"}";
+        let secret_attributes = Attribute::from_secret_bytes(email).unwrap();
+        let public_attributes = Attribute::from_public_bytes(claims).unwrap();
+
+        assert!(secret_attributes.len() > 1);
+        assert!(public_attributes.len() > 1);
+
+        let number_of_attributes = secret_attributes.len() + public_attributes.len();
+        let system_parameters = SystemParameters::generate(&mut rng, number_of_attributes as u8).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+
+        let mut attributes = Vec::new();
+
+        attributes.extend(secret_attributes);
+        attributes.extend(public_attributes);
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+
+        let (secret_chunks, public_chunks) = credential.attributes.split_at(number_of_attributes - number_of_chunks(claims.len()));
+
+        assert_eq!(Attribute::to_bytes_from_chunks(secret_chunks).unwrap(), &email[..]);
+        assert_eq!(Attribute::to_bytes_from_chunks(public_chunks).unwrap(), &claims[..]);
+
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let proof = credential.show(&system_parameters, &issuer.issuer_parameters, Some(&keypair), &mut rng).unwrap();
+
+        assert_eq!(proof.commitments.proofs_of_encryption.len(), secret_chunks.len());
+        assert!(proof.verify(&issuer).is_ok());
+    }
+
+    #[test]
+    fn credential_proof_batch_verification() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let other_issuer = Issuer::new(&system_parameters, &mut rng);
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+
+        let mut proofs = Vec::new();
+
+        for j in 0..6 {
+            let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
+            let mut attributes = Vec::new();
+
+            // Use two different layouts of attributes within the same batch.
+            if j % 2 == 0 {
+                attributes.push(Attribute::SecretPoint(plaintext));
+                attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+            } else {
+                attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+                attributes.push(Attribute::SecretPoint(plaintext));
+                attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
+            }
+
+            // The fourth credential is from the wrong issuer.
+            let signer = if j == 3 { &other_issuer } else { &issuer };
+            let response = signer.issue(&attributes, &mut rng).unwrap();
+            let credential = AnonymousCredential::from_issuance_response(&system_parameters, &signer.issuer_parameters,
+                                                                         &response, attributes).unwrap();
+
+            proofs.push(credential.show(&system_parameters, &signer.issuer_parameters, Some(&keypair), &mut rng).unwrap());
+        }
+
+        assert_eq!(ProofOfValidCredential::verify_batch(&proofs, &issuer),
+                   Err(CredentialError::BatchVerificationFailure{ index: 3 }));
+
+        proofs.remove(3);
+
+        assert!(ProofOfValidCredential::verify_batch(&proofs, &issuer).is_ok());
+    }
+
+    #[test]
+    fn linked_credentials_proof() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let other_system_parameters = SystemParameters::generate(&mut rng, 2).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let other_issuer = Issuer::new(&other_system_parameters, &mut rng);
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let user_id = Scalar::random(&mut rng);
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretPoint(Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap()));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::SecretScalar(user_id));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let mut other_attributes = Vec::new();
+
+        other_attributes.push(Attribute::SecretScalar(user_id));
+        other_attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+
+        let other_response = other_issuer.issue(&other_attributes, &mut rng).unwrap();
+        let other_credential = AnonymousCredential::from_issuance_response(&other_system_parameters,
+                                                                           &other_issuer.issuer_parameters,
+                                                                           &other_response, other_attributes).unwrap();
+        let credentials = [
+            (&system_parameters, &issuer.issuer_parameters, &credential),
+            (&other_system_parameters, &other_issuer.issuer_parameters, &other_credential),
+        ];
+        let links = vec![vec![(0, 2), (1, 0)]];
+        let proof = ProofOfLinkedCredentials::prove(&credentials, &links, Some(&keypair), None, &mut rng).unwrap();
+
+        assert!(proof.verify(&[&issuer, &other_issuer], None).is_ok());
+        assert_eq!(proof.verify(&[&other_issuer, &issuer], None).err(), Some(CredentialError::UnknownKey));
+        assert!(proof.verify(&[&issuer], None).is_err());
+
+        let deserialized = ProofOfLinkedCredentials::from_bytes(&proof.to_bytes()).unwrap();
+
+        assert!(deserialized.verify(&[&issuer, &other_issuer], None).is_ok());
+
+        // A presentation bound to a verifier's challenge cannot be replayed
+        // to another, nor can an unbound one be accepted where one is required.
+        let challenge = Challenge::generate(b"example.com login", &mut rng);
+        let other_challenge = Challenge::generate(b"example.com login", &mut rng);
+        let bound = ProofOfLinkedCredentials::prove(&credentials, &links, Some(&keypair),
+                                                    Some(&challenge), &mut rng).unwrap();
+        let deserialized = ProofOfLinkedCredentials::from_bytes(&bound.to_bytes()).unwrap();
+
+        assert!(deserialized.verify(&[&issuer, &other_issuer], Some(&challenge)).is_ok());
+        assert_eq!(bound.verify(&[&issuer, &other_issuer], Some(&other_challenge)).err(),
+                   Some(CredentialError::VerificationFailure));
+        assert_eq!(proof.verify(&[&issuer, &other_issuer], Some(&challenge)).err(),
+                   Some(CredentialError::MissingData));
 
-        // Choose a nonce for the commitments.
-        let z_:   Scalar = Scalar::random(csprng);
-        let z_0_: Scalar = (-credential.amac.t * z_).reduce();
+        let mut rebound = ProofOfLinkedCredentials::from_bytes(&bound.to_bytes()).unwrap();
 
-        // Commit to the credential attributes, and store the hidden scalar attributes in H_s.
-        let mut C_y_: Vec<RistrettoPoint> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
-        let mut H_s_: Vec<(usize, RistrettoPoint, Scalar)> = Vec::new();
+        rebound.challenge = Some(other_challenge.clone());
 
-        for (i, attribute) in credential.attributes.iter().enumerate() {
-            match attribute {
-                Attribute::PublicPoint(_)  =>   C_y_.push(system_parameters.G_y[i] * z_),
-                Attribute::SecretPoint(p)  =>   C_y_.push(system_parameters.G_y[i] * z_ + p.M1),
-                Attribute::PublicScalar(_) =>   C_y_.push(system_parameters.G_y[i] * z_),
-                Attribute::SecretScalar(m) => { C_y_.push(system_parameters.G_y[i] * z_ + system_parameters.G_m[i] * *m);
-                                                H_s_.push((i, system_parameters.G_m[i], *m)); },
-            };
-        }
-        let C_x_0_: RistrettoPoint = (system_parameters.G_x_0 * z_) +  credential.amac.U;
-        let C_x_1_: RistrettoPoint = (system_parameters.G_x_1 * z_) + (credential.amac.U * credential.amac.t);
-        let C_V_:   RistrettoPoint = (system_parameters.G_V   * z_) +  credential.amac.V;
-        let Z_:     RistrettoPoint =  issuer_parameters.I     * z_;
+        assert!(rebound.verify(&[&issuer, &other_issuer], Some(&other_challenge)).is_err());
 
-        // Create a transcript and prover.
-        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
-        let mut prover = Prover::new(b"2019/1416 presentation proof", &mut transcript);
+        // Attributes which differ cannot be linked, and a proof cannot be
+        // made to claim a different link.
+        let unequal = vec![vec![(0, 2), (1, 1)]];
 
-        // Feed the domain separators for the Camenisch-Stadler secrets into the protocol transcript.
-        let z   = prover.allocate_scalar(b"z", z_);
-        let z_0 = prover.allocate_scalar(b"z_0", z_0_);
-        let t   = prover.allocate_scalar(b"t", credential.amac.t);
+        assert_eq!(ProofOfLinkedCredentials::prove(&credentials, &unequal, Some(&keypair), None, &mut rng).err(),
+                   Some(CredentialError::BadAttribute));
 
-        let mut H_s = ProverHiddenScalars(Vec::with_capacity(H_s_.len()));
-        let mut hidden_scalar_indices: Vec<u16> = Vec::new();
-        // XXX assert number of attributes is less than 2^16-1
+        let mut relinked = ProofOfLinkedCredentials::from_bytes(&proof.to_bytes()).unwrap();
 
-        for (i, basepoint, scalar) in H_s_.iter() {
-            // XXX Fix zkp crate to take Strings
-            //H_s.push(prover.allocate_scalar(format!(b"H_s_{}", i), scalar));
-            H_s.push((*i, prover.allocate_scalar(b"m", *scalar)));
-            hidden_scalar_indices.push(*i as u16);
-        }
+        relinked.links = unequal;
 
-        // Feed in the domain separators and values for the publics into the transcript.
-        let (Z, _)     = prover.allocate_point(b"Z", Z_);
-        let (I, _)     = prover.allocate_point(b"I", issuer_parameters.I);
-        let (C_x_1, _) = prover.allocate_point(b"C_x_1", C_x_1_);
-        let (C_x_0, _) = prover.allocate_point(b"C_x_0", C_x_0_);
-        let (G_x_0, _) = prover.allocate_point(b"G_x_0", system_parameters.G_x_0);
-        let (G_x_1, _) = prover.allocate_point(b"G_x_1", system_parameters.G_x_1);
+        assert!(relinked.verify(&[&issuer, &other_issuer], None).is_err());
+    }
 
-        let mut C_y: Vec<ProverPointVar> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
-        let mut G_y: Vec<ProverPointVar> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+    #[test]
+    fn credential_proof_predicates() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+        let now: u64 = 1600000000;
 
-        // We only prove knowledge of commitment openings for hidden scalar
-        // attributes and all revealed attributes; for hidden group element
-        // attributes we use proofs of encryption.
-        for (i, commitment) in C_y_.iter().enumerate() {
-            match credential.attributes[i] {
-                Attribute::SecretPoint { .. } => continue,
-                _ => {
-                    // XXX Fix zkp crate to take Strings
-                    //let (C_y_i, _) = prover.allocate_point(format!(b"C_y_{}", i), commitment);
-                    let (C_y_i, _) = prover.allocate_point(b"C_y", *commitment);
+        let mut attributes = Vec::new();
 
-                    C_y.push(C_y_i);
-                },
-            };
-        }
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::SecretScalar(Scalar::from(21u64)));
+        attributes.push(Attribute::SecretScalar(Scalar::from(now + 3600)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let predicates = vec![
+            Predicate::AtLeast { index: 1, bound: 18 },
+            Predicate::LessThan { index: 1, bound: 22 },
+            Predicate::AtLeast { index: 2, bound: now + 1 },
+        ];
+        let options = PresentationOptions { predicates: predicates.clone(), ..Default::default() };
+        let proof = credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                                 None, &options, &mut rng).unwrap();
+
+        assert!(proof.verify(&issuer).is_ok());
+        assert!(proof.verify_with_predicates(&issuer, &predicates).is_ok());
+        assert_eq!(proof.verify_with_predicates(&issuer, &[Predicate::AtLeast { index: 1, bound: 21 }]),
+                   Err(CredentialError::UnsatisfiedPredicate));
+        assert_eq!(proof.predicates(), predicates);
+
+        let deserialized = ProofOfValidCredential::from_bytes(&proof.to_bytes()).unwrap();
+
+        assert!(deserialized.verify_with_predicates(&issuer, &predicates).is_ok());
+    }
 
-        for (i, basepoint) in system_parameters.G_y.iter().enumerate() {
-            // XXX Fix zkp crate to take Strings
-            // let (G_y_i, _) = prover.allocate_point(format!(b"G_y_{}", i), basepoint);
-            let (G_y_i, _) = prover.allocate_point(b"G_y", *basepoint);
+    #[test]
+    fn credential_proof_pseudonyms() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
 
-            G_y.push(G_y_i);
-        }
+        let mut attributes = Vec::new();
 
-        let mut G_m = ProverHiddenScalarBasepoints(Vec::with_capacity(H_s_.len()));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
 
-        for (i, basepoint, scalar) in H_s_.iter() {
-            // XXX Fix zkp crate to take Strings
-            // let (G_m_i, _) = prover.allocate_point(format!(b"G_m_{}", i), basepoint);
-            let (G_m_i, _) = prover.allocate_point(b"G_m", *basepoint);
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let scope = Scope { index: 1, label: b"example.com".to_vec() };
+        let other_scope = Scope { index: 1, label: b"example.org".to_vec() };
+        let mut show = |scope: &Scope| {
+            let options = PresentationOptions { scope: Some(scope.clone()), ..Default::default() };
 
-            G_m.push((*i, G_m_i));
-        }
+            credential.show_with_options(&system_parameters, &issuer.issuer_parameters, None, &options, &mut rng).unwrap()
+        };
+        let first = show(&scope);
+        let second = show(&scope);
+        let other = show(&other_scope);
 
-        // Constraint #1: Prove knowledge of the nonce, z, and the correctness of the AMAC with Z.
-        //                Z = I * z
-        prover.constrain(Z, vec![(z, I)]);
+        let nym = first.verify_in_scope(&issuer, &scope).unwrap();
 
-        // Constraint #2: Prove correctness of t and U.
-        //                C_x_1 = C_x_0 * t          + G_x_0 * z_0 + G_x_1 * z
-        //    G_x_1 * z + U * t = G_x_0 * zt + U * t + G_x_0 * -tz + G_x_1 * z
-        //    G_x_1 * z + U * t =              U * t +               G_x_1 * z
-        prover.constrain(C_x_1, vec![(t, C_x_0), (z_0, G_x_0), (z, G_x_1)]);
+        assert_eq!(second.verify_in_scope(&issuer, &scope).unwrap(), nym);
+        assert_ne!(other.verify_in_scope(&issuer, &other_scope).unwrap(), nym);
+        assert!(other.verify_in_scope(&issuer, &scope).is_err());
 
-        // Constraint #3: Prove correctness/validation of attributes.
-        //        C_y_i = { G_y_i * z + G_m_i * m_i          if i is a hidden scalar attribute
-        //                { G_y_i * z                        if i is a revealed attribute
-        for (i, C_y_i) in C_y.iter().enumerate() {
-            match credential.attributes[i] {
-                Attribute::SecretPoint(_)  => continue,
-                Attribute::SecretScalar(_) => prover.constrain(*C_y_i, vec![(z, G_y[i]), (H_s[i], G_m[i])]),
-                _                          => prover.constrain(*C_y_i, vec![(z, G_y[i])]),
-            }
-        }
-        // Notes:
-        //
-        // 1. Prover recalculates Z', so it is not sent.
-        // 2. C_V, the commitment to the actual AMAC (recall that the t and U
-        //    values in the AMAC are nonces), is sent, but V is kept private to
-        //    provide anonymity, so we do not prove anything about it.
-        // 3; That z_0 actually equals -tz (mod \ell) is never proven, but this
-        //    should not matter as we prove knowledge of t and z, and constraint
-        //    #2 would never pass verification if either were other than the
-        //    values used to compute z_0.
-        let proof = prover.prove_compact();
+        // A pseudonym derived from another hidden scalar attribute would be a
+        // second identity within the same scope, and so is rejected.
+        let sybil = show(&Scope { index: 2, label: b"example.com".to_vec() });
 
-        // Construct proofs of correct encryptions for the hidden group attributes.
-        let mut proofs_of_encryption: Vec<(u16, ProofOfEncryption)> = Vec::new();
+        assert!(sybil.verify(&issuer).is_ok());
+        assert_eq!(sybil.verify_in_scope(&issuer, &scope).err(), Some(CredentialError::VerificationFailure));
 
-        // Rebuild the attributes for our credential to send to the verifier.
-        let mut encrypted_attributes: Vec<EncryptedAttribute> = Vec::with_capacity(system_parameters.NUMBER_OF_ATTRIBUTES as usize);
+        let deserialized = ProofOfValidCredential::from_bytes(&first.to_bytes()).unwrap();
 
-        // XXX don't we also need DLEQ between the plaintext here and that in the commitments above?
-        for (i, attribute) in credential.attributes.iter().enumerate() {
-            match attribute {
-                Attribute::PublicScalar(x) => encrypted_attributes.push(EncryptedAttribute::PublicScalar(*x)),
-                Attribute::SecretScalar(_) => encrypted_attributes.push(EncryptedAttribute::SecretScalar),
-                Attribute::PublicPoint(x)  => encrypted_attributes.push(EncryptedAttribute::PublicPoint(*x)),
-                Attribute::SecretPoint(pt) => {
-                    // The .unwrap() here can never panic because we check above that the key isn't
-                    // None if we have encrypted group element attributes.
-                    proofs_of_encryption.push((i as u16, ProofOfEncryption::prove(&system_parameters, &pt, i as u16, &keypair.unwrap(), &z_)));
-                    encrypted_attributes.push(EncryptedAttribute::SecretPoint);
-                },
-            }
-        }
+        assert_eq!(deserialized.verify_in_scope(&issuer, &scope).unwrap(), nym);
+        assert_eq!(Pseudonym::from_bytes(&nym.to_bytes()).unwrap(), nym);
 
-        Ok(ProofOfValidCredential {
-            proof: proof,
-            proofs_of_encryption: proofs_of_encryption,
-            encrypted_attributes: encrypted_attributes,
-            hidden_scalar_indices: hidden_scalar_indices,
-            C_x_0: C_x_0_,
-            C_x_1: C_x_1_,
-            C_V: C_V_,
-            C_y: C_y_,
-        })
-    }
+        // Presentations with and without pseudonyms, in any scopes, may be verified together.
+        let unscoped = credential.show(&system_parameters, &issuer.issuer_parameters, None, &mut thread_rng()).unwrap();
 
-    /// DOCDOC
-    pub fn verify(
-        &self,
-        issuer: &Issuer,
-        credential: &AnonymousCredential,
-    ) -> Result<(), CredentialError>
-    {
-        let NUMBER_OF_ATTRIBUTES = issuer.system_parameters.NUMBER_OF_ATTRIBUTES as usize;
+        assert_eq!(unscoped.verify_in_scope(&issuer, &scope).err(), Some(CredentialError::MissingData));
 
-        // Recompute the prover's Z value.
-        //
-        // Let \mathcal{H} denote the set of hidden attributes, both those which are group elements
-        // and those which are scalars.
-        //
-        // Let M_i be a revealed group element attribute, if so, and otherwise if a revealed scalar
-        // attribute, m_i, then let M_i be G_m_i * m_i.
-        //
-        // Z = C_V - (W + C_x0 * x0 + C_x1 * x1 +
-        //            \sigma_{i \in \mathcal{H}}{C_y_i * y_i} +
-        //            \sigma_{i \notin \mathcal{H}}{(C_y_i + M_i) * y_i})
-        let mut Z_ = self.C_V - (issuer.amacs_key.W + self.C_x_0 * issuer.amacs_key.x_0 + self.C_x_1 * issuer.amacs_key.x_1);
+        let mut proofs = vec![first, second, other, unscoped];
 
-        for (i, attribute) in self.encrypted_attributes.iter().enumerate() {
-            match attribute {
-                EncryptedAttribute::PublicScalar(m_i) => Z_ -= (self.C_y[i] + (issuer.system_parameters.G_m[i] * m_i)) * issuer.amacs_key.y[i],
-                EncryptedAttribute::SecretScalar      => Z_ -=  self.C_y[i]                                            * issuer.amacs_key.y[i],
-                EncryptedAttribute::PublicPoint(M_i)  => Z_ -= (self.C_y[i] + M_i)                                     * issuer.amacs_key.y[i],
-                EncryptedAttribute::SecretPoint       => Z_ -=  self.C_y[i]                                            * issuer.amacs_key.y[i],
-            }
-        }
+        assert!(ProofOfValidCredential::verify_batch(&proofs, &issuer).is_ok());
 
-        // Create a transcript and verifier.
-        let mut transcript = Transcript::new(b"2019/1416 anonymous credential");
-        let mut verifier = Verifier::new(b"2019/1416 presentation proof", &mut transcript);
+        // A pseudonym cannot be swapped for another.
+        let (_, P) = proofs[2].pseudonym.clone().unwrap();
 
-        // Feed the domain separators for the Camenisch-Stadler secrets into the protocol transcript.
-        let z   = verifier.allocate_scalar(b"z");
-        let z_0 = verifier.allocate_scalar(b"z_0");
-        let t   = verifier.allocate_scalar(b"t");
+        proofs[0].pseudonym.as_mut().unwrap().1 = P;
 
-        let mut H_s = VerifierHiddenScalars(Vec::new());
+        assert!(proofs[0].verify(&issuer).is_err());
+        assert_eq!(ProofOfValidCredential::verify_batch(&proofs, &issuer),
+                   Err(CredentialError::BatchVerificationFailure{ index: 0 }));
+    }
 
-        // XXX fixme this struct won't work here
-        for i in self.hidden_scalar_indices.iter() {
-            // XXX Fix zkp crate to take Strings
-            //H_s.push(*i, verifier.allocate_scalar(format!(b"H_s_{}", i)));
-            H_s.push((*i as usize, verifier.allocate_scalar(b"m")));
-        }
+    #[test]
+    fn credential_proof_challenges_and_serial_numbers() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
 
-        // Feed in the domain separators and values for the publics into the transcript.
-        let Z     = verifier.allocate_point(b"Z", Z_.compress())?;
-        let I     = verifier.allocate_point(b"I", issuer.issuer_parameters.I.compress())?;
-        let C_x_1 = verifier.allocate_point(b"C_x_1", self.C_x_1.compress())?;
-        let C_x_0 = verifier.allocate_point(b"C_x_0", self.C_x_0.compress())?;
-        let G_x_0 = verifier.allocate_point(b"G_x_0", issuer.system_parameters.G_x_0.compress())?;
-        let G_x_1 = verifier.allocate_point(b"G_x_1", issuer.system_parameters.G_x_1.compress())?;
+        let mut attributes = Vec::new();
 
-        let mut C_y: Vec<VerifierPointVar> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
-        let mut G_y: Vec<VerifierPointVar> = Vec::with_capacity(NUMBER_OF_ATTRIBUTES);
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
 
-        // We only prove knowledge of commitment openings for hidden scalar
-        // attributes and all revealed attributes; for hidden group element
-        // attributes we use proofs of encryption.
-        for (i, commitment) in self.C_y.iter().enumerate() {
-            match credential.attributes[i] {
-                Attribute::SecretPoint { .. } => continue,
-                _ => {
-                    // XXX Fix zkp crate to take Strings
-                    // C_y.push(verifier.allocate_point(format!(b"C_y_{}", i), commitment.compress())?);
-                    C_y.push(verifier.allocate_point(b"C_y", commitment.compress())?);
-                },
-            };
-        }
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let challenge = Challenge::generate(b"example.com login", &mut rng);
+        let other_challenge = Challenge::generate(b"example.com login", &mut rng);
+        let options = PresentationOptions {
+            serial_number: Some(1),
+            challenge: Some(challenge.clone()),
+            ..Default::default()
+        };
+        let first = credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                                 None, &options, &mut rng).unwrap();
 
-        for (i, basepoint) in issuer.system_parameters.G_y.iter().enumerate() {
-            // XXX Fix zkp crate to take Strings
-            // G_y.push(verifier.allocate_point(format!(b"G_y_{}", i), basepoint.compress())?);
-            G_y.push(verifier.allocate_point(b"G_y", basepoint.compress())?);
-        }
+        assert!(first.verify_with_challenge(&issuer, &challenge).is_ok());
+        assert_eq!(first.verify_with_challenge(&issuer, &other_challenge).err(),
+                   Some(CredentialError::VerificationFailure));
 
-        let mut G_m = VerifierHiddenScalarBasepoints(Vec::with_capacity(H_s.0.len()));
+        let deserialized = ProofOfValidCredential::from_bytes(&first.to_bytes()).unwrap();
 
-        for (i, _) in H_s.0.iter() {
-            // XXX Fix zkp crate to take Strings
-            // G_m.push(verifier.allocate_point(format!(b"G_m_{}", i), issuer.system_parameters.G_m[i].compress())?);
-            G_m.push((*i, verifier.allocate_point(b"G_m", issuer.system_parameters.G_m[*i].compress())?));
-        }
+        assert!(deserialized.verify_with_challenge(&issuer, &challenge).is_ok());
+        assert_eq!(deserialized.serial_number(), first.serial_number());
 
-        // Constraint #1: Prove knowledge of the nonce, z, and the correctness of the AMAC with Z.
-        //                Z = I * z
-        verifier.constrain(Z, vec![(z, I)]);
+        // Every presentation of the credential has the same serial number.
+        let options = PresentationOptions { serial_number: Some(1), ..Default::default() };
+        let second = credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                                  None, &options, &mut rng).unwrap();
 
-        // Constraint #2: Prove correctness of t and U.
-        //                C_x_1 = C_x_0 * t          + G_x_0 * z_0 + G_x_1 * z
-        //    G_x_1 * z + U * t = G_x_0 * zt + U * t + G_x_0 * -tz + G_x_1 * z
-        //    G_x_1 * z + U * t =              U * t +               G_x_1 * z
-        verifier.constrain(C_x_1, vec![(t, C_x_0), (z_0, G_x_0), (z, G_x_1)]);
+        assert!(second.serial_number().is_some());
+        assert_eq!(second.serial_number(), first.serial_number());
+        assert_eq!(second.verify_with_challenge(&issuer, &challenge).err(), Some(CredentialError::MissingData));
 
-        // Constraint #3: Prove correctness/validation of attributes.
-        //        C_y_i = { G_y_i * z + G_m_i * m_i          if i is a hidden scalar attribute
-        //                { G_y_i * z                        if i is a revealed attribute
-        for (i, C_y_i) in C_y.iter().enumerate() {
-            match credential.attributes[i] {
-                Attribute::SecretPoint(_)  => continue,
-                Attribute::SecretScalar(_) => verifier.constrain(*C_y_i, vec![(z, G_y[i]), (H_s[i], G_m[i])]),
-                _                          => verifier.constrain(*C_y_i, vec![(z, G_y[i])]),
-            }
-        }
+        let S = first.verify_with_serial_number(&issuer, 1).unwrap();
 
-        verifier.verify_compact(&self.proof).or_else(|_| return Err(CredentialError::VerificationFailure));
+        assert_eq!(second.verify_with_serial_number(&issuer, 1).unwrap(), S);
+        assert_eq!(SerialNumber::from_bytes(&S.to_bytes()).unwrap(), S);
 
-        // Check the proofs of correct encryptions and fail if any cannot be verified.
-        for (i, proof_of_encryption) in self.proofs_of_encryption.iter() {
-            proof_of_encryption.verify(&issuer.system_parameters)?;
-        }
+        // A serial number derived from another hidden scalar attribute is
+        // valid, but differs, and so is refused where index 1 is expected.
+        let options = PresentationOptions { serial_number: Some(2), ..Default::default() };
+        let third = credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                                 None, &options, &mut rng).unwrap();
 
-        Ok(())
-    }
-}
+        assert!(third.verify(&issuer).is_ok());
+        assert_ne!(third.serial_number().unwrap().1, S);
+        assert_eq!(third.verify_with_serial_number(&issuer, 1).err(), Some(CredentialError::VerificationFailure));
 
-#[cfg(test)]
-mod test {
-    use super::*;
+        // Serial numbers may only be derived from hidden scalar attributes.
+        let options = PresentationOptions { serial_number: Some(0), ..Default::default() };
 
-    use curve25519_dalek::traits::IsIdentity;
+        assert!(credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                             None, &options, &mut rng).is_err());
 
-    use rand::thread_rng;
+        let unbound = credential.show(&system_parameters, &issuer.issuer_parameters, None, &mut rng).unwrap();
+
+        assert_eq!(unbound.verify_with_serial_number(&issuer, 1).err(), Some(CredentialError::MissingData));
+
+        let mut proofs = vec![first, second, unbound];
+
+        assert!(ProofOfValidCredential::verify_batch(&proofs, &issuer).is_ok());
+
+        // A presentation cannot be rebound to another challenge.
+        proofs[0].challenge = Some(other_challenge.clone());
+
+        assert!(proofs[0].verify_with_challenge(&issuer, &other_challenge).is_err());
+        assert_eq!(ProofOfValidCredential::verify_batch(&proofs, &issuer),
+                   Err(CredentialError::BatchVerificationFailure{ index: 0 }));
+    }
 
     #[test]
-    fn issuance_proof() {
+    fn credential_proof_rate_limit_is_bounded() {
         let mut rng = thread_rng();
-        let system_parameters = SystemParameters::generate(&mut rng, 5).unwrap();
+        let system_parameters = SystemParameters::generate(&mut rng, 2).unwrap();
         let issuer = Issuer::new(&system_parameters, &mut rng);
-        let plaintext: Plaintext = (&[1u8; 30]).into();
 
         let mut attributes = Vec::new();
+
         attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
         attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
-        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
-        attributes.push(Attribute::SecretPoint(plaintext));
-        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
 
-        let credential = issuer.issue(attributes, &mut rng).unwrap();
-        let proof = ProofOfIssuance::prove(&issuer, &credential);
-        let verification = proof.verify(&system_parameters, &issuer.issuer_parameters, &credential);
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let rate_limit = RateLimit { index: 0, label: b"example.com".to_vec(), epoch: 7, limit: MAX_RATE_LIMIT + 1 };
+        let options = PresentationOptions { rate_limit: Some((rate_limit, 0)), ..Default::default() };
 
-        assert!(verification.is_ok());
+        assert_eq!(credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                                None, &options, &mut rng).err(),
+                   Some(CredentialError::UnsatisfiedPredicate));
+
+        // A limit above the maximum is refused before any of its branches are checked.
+        let rate_limit = RateLimit { index: 0, label: b"example.com".to_vec(), epoch: 7, limit: 3 };
+        let options = PresentationOptions { rate_limit: Some((rate_limit, 0)), ..Default::default() };
+        let mut proof = credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                                     None, &options, &mut rng).unwrap();
+
+        assert!(proof.verify(&issuer).is_ok());
+
+        let rate_limit_proof = proof.rate_limit.as_mut().unwrap();
+        let response = rate_limit_proof.responses[0];
+
+        rate_limit_proof.rate_limit.limit = MAX_RATE_LIMIT + 1;
+        rate_limit_proof.responses.resize(MAX_RATE_LIMIT as usize + 1, response);
+
+        assert_eq!(proof.verify(&issuer).err(), Some(CredentialError::VerificationFailure));
+        assert_eq!(ProofOfValidCredential::verify_batch(&[proof], &issuer),
+                   Err(CredentialError::BatchVerificationFailure{ index: 0 }));
     }
 
-    /// An issuance proof with a plaintext equal to the identity element will fail.
     #[test]
-    #[should_panic(expected = "assertion failed: verification.is_ok()")]
-    fn issuance_proof_identity_plaintext() {
+    fn credential_proof_escrow() {
         let mut rng = thread_rng();
-        let system_parameters = SystemParameters::generate(&mut rng, 5).unwrap();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
         let issuer = Issuer::new(&system_parameters, &mut rng);
-        let plaintext: Plaintext = (&[0u8; 30]).into();
-
-        assert!(plaintext.M1.is_identity());
+        let plaintext = Plaintext::try_from(&b"abuse@example.com"[..]).unwrap();
+        let m = Scalar::random(&mut rng);
 
         let mut attributes = Vec::new();
-        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+
         attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
-        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
+        attributes.push(Attribute::SecretScalar(m));
         attributes.push(Attribute::SecretPoint(plaintext));
-        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
 
-        let credential = issuer.issue(attributes, &mut rng).unwrap();
-        let proof = ProofOfIssuance::prove(&issuer, &credential);
-        let verification = proof.verify(&system_parameters, &issuer.issuer_parameters, &credential);
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let auditor = ElGamalKeypair::generate(&system_parameters, &mut rng);
+        let other_auditor = ElGamalKeypair::generate(&system_parameters, &mut rng);
+        let escrow = Escrow { public_key: auditor.public, indices: vec![2, 1] };
+        let options = PresentationOptions { escrow: Some(escrow.clone()), ..Default::default() };
+        let proof = credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                                 Some(&keypair), &options, &mut rng).unwrap();
 
-        assert!(verification.is_ok());
-    }
+        assert!(proof.verify(&issuer).is_ok());
 
-    #[test]
-    fn encryption_proof() {
-        let mut rng = thread_rng();
-        let system_parameters = SystemParameters::generate(&mut rng, 5).unwrap();
-        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
-        let z = Scalar::random(&mut rng);
-        let message1: &[u8; 30] = b"This is a tsunami alert test..";
-        let plaintext: Plaintext = message1.into();
+        let deserialized = ProofOfValidCredential::from_bytes(&proof.to_bytes()).unwrap();
+        let ciphertexts = deserialized.verify_escrowed(&issuer, &escrow).unwrap();
 
-        let proof = ProofOfEncryption::prove(&system_parameters, &plaintext, 1u16, &keypair, &z);
-        let decryption = keypair.decrypt(&proof.ciphertext).unwrap();
+        // Only the auditor can recover the hidden attributes.
+        let (data, _) = decode_from_group(&auditor.decrypt(&ciphertexts[0])).unwrap();
 
-        assert!(decryption.M1 == plaintext.M1);
-        assert!(decryption.M2 == plaintext.M2);
-        assert!(decryption.m3 == plaintext.m3);
+        assert_eq!(&data[..], &b"abuse@example.com"[..]);
+        assert_eq!(auditor.decrypt(&ciphertexts[1]), system_parameters.G_m[1] * m);
+        assert_ne!(other_auditor.decrypt(&ciphertexts[1]), system_parameters.G_m[1] * m);
 
-        let message2: [u8; 30] = (&decryption).into();
+        let other_escrow = Escrow { public_key: other_auditor.public, indices: vec![2, 1] };
 
-        assert!(message1 == &message2);
+        assert_eq!(proof.verify_escrowed(&issuer, &other_escrow).err(), Some(CredentialError::VerificationFailure));
 
-        let verification = proof.verify(&system_parameters);
+        let unescrowed = credential.show(&system_parameters, &issuer.issuer_parameters, Some(&keypair), &mut rng).unwrap();
 
-        assert!(verification.is_ok());
+        assert_eq!(unescrowed.verify_escrowed(&issuer, &escrow).err(), Some(CredentialError::MissingData));
+
+        // Ciphertexts cannot be swapped for encryptions of other attributes.
+        let mut tampered = ProofOfValidCredential::from_bytes(&proof.to_bytes()).unwrap();
+
+        tampered.escrow.as_mut().unwrap().ciphertexts.swap(0, 1);
+
+        assert!(tampered.verify(&issuer).is_err());
+        assert!(ProofOfValidCredential::verify_batch(&[proof, tampered], &issuer).is_err());
+
+        // Public attributes cannot be escrowed.
+        let escrow = Escrow { public_key: auditor.public, indices: vec![0] };
+        let options = PresentationOptions { escrow: Some(escrow), ..Default::default() };
+
+        assert_eq!(credential.show_with_options(&system_parameters, &issuer.issuer_parameters,
+                                                Some(&keypair), &options, &mut rng).err(),
+                   Some(CredentialError::BadAttribute));
     }
 
     #[test]
-    fn credential_proof_8_attributes() {
+    fn credential_proof_unsatisfied_predicates() {
         let mut rng = thread_rng();
-        let system_parameters = SystemParameters::generate(&mut rng, 8).unwrap();
+        let system_parameters = SystemParameters::generate(&mut rng, 2).unwrap();
         let issuer = Issuer::new(&system_parameters, &mut rng);
-        let plaintext: Plaintext = b"This is a tsunami alert test..".into();
 
         let mut attributes = Vec::new();
 
-        attributes.push(Attribute::SecretPoint(plaintext));
-        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
-        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
-        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
-        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
-        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
-        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
-        attributes.push(Attribute::PublicPoint(RistrettoPoint::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::from(17u64)));
+        attributes.push(Attribute::SecretScalar(Scalar::from(17u64)));
 
-        let credential = issuer.issue(attributes, &mut rng).unwrap();
-        let (keypair, master_secret) = SymmetricKeypair::generate(&system_parameters, &mut rng);
-        let proof = ProofOfValidCredential::prove(&system_parameters, &issuer.issuer_parameters, &credential, Some(&keypair), &mut rng);
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+        let show = |predicate: Predicate, rng: &mut _| {
+            let options = PresentationOptions { predicates: vec![predicate], ..Default::default() };
 
-        assert!(proof.is_ok());
+            credential.show_with_options(&system_parameters, &issuer.issuer_parameters, None, &options, rng)
+        };
 
-        let verification = proof.unwrap().verify(&issuer, &credential);
+        assert_eq!(show(Predicate::AtLeast { index: 1, bound: 18 }, &mut rng).err(),
+                   Some(CredentialError::UnsatisfiedPredicate));
+        assert_eq!(show(Predicate::LessThan { index: 1, bound: 17 }, &mut rng).err(),
+                   Some(CredentialError::UnsatisfiedPredicate));
+        assert_eq!(show(Predicate::AtLeast { index: 0, bound: 1 }, &mut rng).err(),
+                   Some(CredentialError::BadAttribute));
 
-        assert!(verification.is_ok());
+        // A range proof must not verify against a different predicate.
+        let mut proof = show(Predicate::AtLeast { index: 1, bound: 17 }, &mut rng).unwrap();
+
+        assert!(proof.verify(&issuer).is_ok());
+
+        proof.range_proofs[0].predicate = Predicate::AtLeast { index: 1, bound: 18 };
+
+        assert!(proof.verify(&issuer).is_err());
+        assert!(ProofOfValidCredential::verify_batch(&[proof], &issuer).is_err());
     }
 
     #[test]
-    fn credential_proof_1_attribute() {
+    fn encryption_proof_batch_verification() {
         let mut rng = thread_rng();
-        let system_parameters = SystemParameters::generate(&mut rng, 1).unwrap();
-        let issuer = Issuer::new(&system_parameters, &mut rng);
-        let plaintext: Plaintext = b"This is a tsunami alert test..".into();
+        let system_parameters = SystemParameters::generate(&mut rng, 5).unwrap();
+        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let other_parameters = SystemParameters::generate(&mut rng, 5).unwrap();
+        let (other_keypair, _) = SymmetricKeypair::generate(&other_parameters, &mut rng);
 
-        let mut attributes = Vec::new();
+        let mut proofs = Vec::new();
 
-        attributes.push(Attribute::SecretPoint(plaintext));
+        for i in 0..5u16 {
+            let plaintext = Plaintext::try_from(&[i as u8; 30][..]).unwrap();
+            let z = Scalar::random(&mut rng);
 
-        let credential = issuer.issue(attributes, &mut rng).unwrap();
-        let (keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
-        let proof = ProofOfValidCredential::prove(&system_parameters, &issuer.issuer_parameters, &credential, Some(&keypair), &mut rng);
+            // The third proof is made w.r.t. other system parameters.
+            if i == 2 {
+                proofs.push(ProofOfEncryption::prove(&other_parameters, &plaintext, i, &other_keypair, &z, &mut rng));
+            } else {
+                proofs.push(ProofOfEncryption::prove(&system_parameters, &plaintext, i, &keypair, &z, &mut rng));
+            }
+        }
 
-        assert!(proof.is_ok());
+        assert_eq!(ProofOfEncryption::verify_batch(&proofs, &system_parameters),
+                   Err(CredentialError::BatchVerificationFailure{ index: 2 }));
 
-        let verification = proof.unwrap().verify(&issuer, &credential);
+        proofs.remove(2);
 
-        assert!(verification.is_ok());
+        assert!(ProofOfEncryption::verify_batch(&proofs, &system_parameters).is_ok());
     }
 
+    #[test]
+    fn plaintext_equality_proof() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let (_, master_secret) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let old_keypair = SymmetricKeypair::derive_for_epoch(&master_secret, 1, &system_parameters);
+        let new_keypair = SymmetricKeypair::derive_for_epoch(&master_secret, 2, &system_parameters);
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
+        let other_plaintext = Plaintext::try_from(&b"This is not a tsunami alert..."[..]).unwrap();
+        let old_ciphertext = old_keypair.encrypt(&plaintext);
+
+        let (new_ciphertext, proof) = ProofOfPlaintextEquality::reencrypt(&system_parameters, &old_keypair, &new_keypair,
+                                                                          &old_ciphertext, &mut rng).unwrap();
+
+        assert_ne!(old_ciphertext, new_ciphertext);
+        assert_eq!(new_keypair.decrypt(&new_ciphertext).unwrap(), plaintext);
+        assert!(proof.verify(&system_parameters, &old_keypair.public, &old_ciphertext,
+                             &new_keypair.public, &new_ciphertext).is_ok());
+
+        let deserialized = ProofOfPlaintextEquality::from_bytes(&proof.to_bytes()).unwrap();
+
+        assert!(deserialized.verify(&system_parameters, &old_keypair.public, &old_ciphertext,
+                                    &new_keypair.public, &new_ciphertext).is_ok());
+
+        // The proof does not verify for an encryption of another plaintext.
+        let other_ciphertext = new_keypair.encrypt(&other_plaintext);
+
+        assert!(proof.verify(&system_parameters, &old_keypair.public, &old_ciphertext,
+                             &new_keypair.public, &other_ciphertext).is_err());
+        assert!(proof.verify(&system_parameters, &new_keypair.public, &old_ciphertext,
+                             &old_keypair.public, &new_ciphertext).is_err());
+
+        // Ciphertexts which do not decrypt under the old keypair cannot be re-encrypted.
+        assert_eq!(ProofOfPlaintextEquality::reencrypt(&system_parameters, &old_keypair, &new_keypair,
+                                                       &other_ciphertext, &mut rng).err(),
+                   Some(CredentialError::UndecryptableAttribute));
+
+        // Encryptions of a plaintext under two unrelated keypairs can be shown to be equal.
+        let (first_keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let (second_keypair, _) = SymmetricKeypair::generate(&system_parameters, &mut rng);
+        let (first, second, proof) = ProofOfPlaintextEquality::prove(&system_parameters, &plaintext, &first_keypair,
+                                                                     &second_keypair, &mut rng).unwrap();
+
+        assert_eq!(first, first_keypair.encrypt(&plaintext));
+        assert!(proof.verify(&system_parameters, &first_keypair.public, &first, &second_keypair.public, &second).is_ok());
+        assert!(proof.verify(&system_parameters, &first_keypair.public, &first, &second_keypair.public,
+                             &second_keypair.encrypt(&other_plaintext)).is_err());
+    }
 }
@@ -26,6 +26,9 @@ use serde::de::Visitor;
 use rand_core::CryptoRng;
 use rand_core::RngCore;
 
+use sha2::Digest;
+use sha2::Sha512;
+
 use crate::amacs::SecretKey;
 use crate::errors::CredentialError;
 
@@ -39,6 +42,9 @@ pub(crate) fn sizeof_system_parameters(number_of_attributes: u8) -> usize {
     32 * (5 + (2 * number_of_attributes as usize) + 4) + 1
 }
 
+/// The size of serialised [`IssuerParameters`], in bytes.
+pub(crate) const SIZEOF_ISSUER_PARAMETERS: usize = 64;
+
 /// The `SystemParameters` define the system-wide context in which the anonymous
 /// credentials scheme and its proofs are constructed within.
 ///
@@ -74,19 +80,6 @@ pub struct SystemParameters {
     pub G_a1:      RistrettoPoint,
 }
 
-macro_rules! try_deserialise {
-    ($name:expr, $bytes:expr) => {
-        match CompressedRistretto($bytes).decompress() {
-            Some(x)  => x,
-            None     => {
-                #[cfg(feature = "std")]
-                println!("Could not decode {:?} from bytes: {:?}", $name, $bytes);
-                return Err(CredentialError::PointDecompressionError);
-            },
-        }
-    }
-}
-
 impl SystemParameters {
     pub fn from_bytes(bytes: &[u8]) -> Result<SystemParameters, CredentialError> {
         let mut index: usize = 0;
@@ -288,38 +281,112 @@ impl SystemParameters {
         let G_a0 = G_a0.unwrap();
         let G_a1 = G_a1.unwrap();
 
-        // Safety check: all generators should be generators (i.e. not the
-        // identity element) and be unique.  While the chances of this happening
-        // with a CSPRNG are miniscule, we might have been handed a bad RNG.
+        let system_parameters = SystemParameters { NUMBER_OF_ATTRIBUTES, G, G_w, G_w_prime, G_x_0, G_x_1, G_y, G_m, G_V, G_a, G_a0, G_a1 };
+
+        // Safety check: while the chances of a bad generator are miniscule with
+        // a CSPRNG, we might have been handed a bad RNG.
+        system_parameters.check_generators()?;
+
+        Ok(system_parameters)
+    }
+
+    /// Check that all generators are generators (i.e. not the identity
+    /// element) and are unique.
+    fn check_generators(&self) -> Result<(), CredentialError> {
         let mut generators: Vec<CompressedRistretto> = Vec::new();
 
         generators.push(RistrettoPoint::identity().compress());
         generators.push(RISTRETTO_BASEPOINT_COMPRESSED);
-        generators.push(G_w.compress());
-        generators.push(G_w_prime.compress());
-        generators.push(G_x_0.compress());
-        generators.push(G_x_1.compress());
-        generators.push(G_V.compress());
-        generators.push(G_a.compress());
-        generators.push(G_a0.compress());
-        generators.push(G_a1.compress());
-
-        for i in 0..NUMBER_OF_ATTRIBUTES as usize {
-            generators.push(G_y[i].compress());
-            generators.push(G_m[i].compress());
+        generators.push(self.G_w.compress());
+        generators.push(self.G_w_prime.compress());
+        generators.push(self.G_x_0.compress());
+        generators.push(self.G_x_1.compress());
+        generators.push(self.G_V.compress());
+        generators.push(self.G_a.compress());
+        generators.push(self.G_a0.compress());
+        generators.push(self.G_a1.compress());
+
+        for G_y_i in self.G_y.iter() {
+            generators.push(G_y_i.compress());
+        }
+        for G_m_i in self.G_m.iter() {
+            generators.push(G_m_i.compress());
         }
 
         while generators.len() >= 2 {
             let x = generators.pop().unwrap();
 
-            for i in 0 .. generators.len()-1 {
+            for i in 0..generators.len() {
                 if x == generators[i] {
                     return Err(CredentialError::NoSystemParameters);
                 }
             }
         }
 
-        Ok(SystemParameters { NUMBER_OF_ATTRIBUTES, G, G_w, G_w_prime, G_x_0, G_x_1, G_y, G_m, G_V, G_a, G_a0, G_a1 })
+        Ok(())
+    }
+
+    /// Deterministically derive the [`SystemParameters`] from a public `label`.
+    ///
+    /// Every generator other than the ristretto255 basepoint, \( G \), is
+    /// derived as `RistrettoPoint::hash_from_bytes::<Sha512>` over the `label`,
+    /// the name of the generator, and its index (for \( G_y \) and \( G_m \)),
+    /// so that no one knows any discrete-log relations between them.  Anyone
+    /// may audit a set of parameters by recomputing them from the same `label`
+    /// and `number_of_attributes` and checking that they are equal.
+    ///
+    /// # Inputs
+    ///
+    /// * A `label` unique to this deployment, e.g. `b"example.com credentials v1"`,
+    /// * The `number_of_attributes` in the message space.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the [`SystemParameters`], otherwise a
+    /// [`CredentialError`].
+    pub fn from_domain_separator(
+        label: &[u8],
+        number_of_attributes: u8,
+    ) -> Result<SystemParameters, CredentialError>
+    {
+        // Hash the length-prefixed label, the name of the generator, and its index.
+        let derive = |name: &[u8], index: u8| -> RistrettoPoint {
+            let mut input: Vec<u8> = Vec::with_capacity(8 + label.len() + name.len() + 1);
+
+            input.extend((label.len() as u64).to_le_bytes().iter());
+            input.extend(label.iter());
+            input.extend(name.iter());
+            input.push(index);
+
+            RistrettoPoint::hash_from_bytes::<Sha512>(&input)
+        };
+
+        // The number of elements in G_y must always be at least three in order
+        // to support encrypted group element attributes.
+        let mut number_of_G_y: u8 = number_of_attributes;
+
+        if number_of_G_y < 3 {
+            number_of_G_y = 3;
+        }
+
+        let system_parameters = SystemParameters {
+            NUMBER_OF_ATTRIBUTES: number_of_attributes,
+            G:         RISTRETTO_BASEPOINT_POINT,
+            G_w:       derive(b"G_w", 0),
+            G_w_prime: derive(b"G_w_prime", 0),
+            G_x_0:     derive(b"G_x_0", 0),
+            G_x_1:     derive(b"G_x_1", 0),
+            G_y:       (0..number_of_G_y).map(|i| derive(b"G_y", i)).collect(),
+            G_m:       (0..number_of_attributes).map(|i| derive(b"G_m", i)).collect(),
+            G_V:       derive(b"G_V", 0),
+            G_a:       derive(b"G_a", 0),
+            G_a0:      derive(b"G_a0", 0),
+            G_a1:      derive(b"G_a1", 0),
+        };
+
+        system_parameters.check_generators()?;
+
+        Ok(system_parameters)
     }
 
     /// Generate new system parameters using the
@@ -333,6 +400,13 @@ impl SystemParameters {
     }
 }
 
+/// The size of a [`KeyId`], in bytes.
+pub const SIZEOF_KEY_ID: usize = 8;
+
+/// A short identifier for an issuer's key, derived from its [`IssuerParameters`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct KeyId(pub [u8; SIZEOF_KEY_ID]);
+
 /// DOCDOC
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct IssuerParameters {
@@ -358,14 +432,44 @@ impl IssuerParameters {
         IssuerParameters { C_W, I }
     }
 
-    /// DOCDOC
+    /// Deserialise some [`IssuerParameters`] from the compressed points
+    /// \( (C_W, I) \).
     pub fn from_bytes(bytes: &[u8]) -> Result<IssuerParameters, CredentialError> {
-        unimplemented!();
+        if bytes.len() != SIZEOF_ISSUER_PARAMETERS {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut chunk = [0u8; 32];
+
+        chunk.copy_from_slice(&bytes[..32]);
+        let C_W: RistrettoPoint = try_deserialise!("C_W", chunk);
+
+        chunk.copy_from_slice(&bytes[32..64]);
+        let I: RistrettoPoint = try_deserialise!("I", chunk);
+
+        Ok(IssuerParameters { C_W, I })
     }
 
-    /// DOCDOC
+    /// Serialise these [`IssuerParameters`] to a byte array.
     pub fn to_bytes(&self) -> Vec<u8> {
-        unimplemented!();
+        let mut v: Vec<u8> = Vec::with_capacity(SIZEOF_ISSUER_PARAMETERS);
+
+        v.extend(self.C_W.compress().to_bytes().iter());
+        v.extend(self.I.compress().to_bytes().iter());
+        v
+    }
+
+    /// Compute the [`KeyId`] of these [`IssuerParameters`], which is a
+    /// truncated SHA-512 hash of their serialisation.
+    pub fn key_id(&self) -> KeyId {
+        let mut hash = Sha512::new();
+        let mut key_id = [0u8; SIZEOF_KEY_ID];
+
+        hash.input(b"2019/1416 issuer key id");
+        hash.input(&self.to_bytes());
+        key_id.copy_from_slice(&hash.result()[..SIZEOF_KEY_ID]);
+
+        KeyId(key_id)
     }
 }
 
@@ -395,6 +499,17 @@ mod test {
         SystemParameters::hash_and_pray(&mut rng, 2).unwrap();
     }
 
+    #[test]
+    fn from_domain_separator_is_deterministic() {
+        let a = SystemParameters::from_domain_separator(b"aeonflux test parameters", 4).unwrap();
+        let b = SystemParameters::from_domain_separator(b"aeonflux test parameters", 4).unwrap();
+        let c = SystemParameters::from_domain_separator(b"aeonflux other parameters", 4).unwrap();
+
+        assert!(a == b);
+        assert!(a != c);
+        assert!(a == SystemParameters::from_bytes(&a.to_bytes()).unwrap());
+    }
+
     #[test]
     fn issuer_parameters_generate() {
         let mut rng = thread_rng();
@@ -404,4 +519,29 @@ mod test {
 
         assert!(issuer_params.C_W != RistrettoPoint::identity());
     }
+
+    #[test]
+    fn issuer_parameters_serialize_deserialize() {
+        let mut rng = thread_rng();
+        let system_parameters: SystemParameters = SystemParameters::hash_and_pray(&mut rng, 2).unwrap();
+        let sk: SecretKey = SecretKey::generate(&mut rng, &system_parameters);
+        let issuer_params: IssuerParameters = IssuerParameters::generate(&system_parameters, &sk);
+
+        let serialized = issuer_params.to_bytes();
+        let deserialized = IssuerParameters::from_bytes(&serialized).unwrap();
+
+        assert!(issuer_params == deserialized);
+        assert!(IssuerParameters::from_bytes(&serialized[..63]).is_err());
+    }
+
+    #[test]
+    fn issuer_parameters_key_id() {
+        let mut rng = thread_rng();
+        let system_parameters: SystemParameters = SystemParameters::hash_and_pray(&mut rng, 2).unwrap();
+        let issuer_params = IssuerParameters::generate(&system_parameters, &SecretKey::generate(&mut rng, &system_parameters));
+        let other_params = IssuerParameters::generate(&system_parameters, &SecretKey::generate(&mut rng, &system_parameters));
+
+        assert_eq!(issuer_params.key_id(), issuer_params.clone().key_id());
+        assert_ne!(issuer_params.key_id(), other_params.key_id());
+    }
 }
@@ -0,0 +1,616 @@
+// -*- mode: rust; -*-
+//
+// This file is part of aeonflux.
+// Copyright (c) 2020 The Brave Authors
+// See LICENSE for licensing information.
+//
+// Authors:
+// - isis agora lovecruft <isis@patternsinthevoid.net>
+
+//! Profile keys, and credentials certifying them.
+//!
+//! A user encrypts their profile, e.g. their name and avatar, with a 32-byte
+//! [`ProfileKey`], which they share with the groups they are a member of as a
+//! [`ProfileKeyCiphertext`] under each group's key.  The issuer, which stores
+//! the user's encrypted profile, also stores a [`ProfileKeyCommitment`] to its
+//! profile key, against which a [`ProfileKeyIssuer`] issues profile key
+//! credentials without ever learning the key itself.
+//!
+//! A profile key credential has [`NUMBER_OF_PROFILE_KEY_CREDENTIAL_ATTRIBUTES`]
+//! attributes: the user's [`Uid`], which is revealed to the issuer, followed by
+//! the two halves of their profile key, which are not.  All three are hidden
+//! group element attributes upon presentation, so presenting the credential
+//! with a group's keypair encrypts them under the group's key, proving to the
+//! group's server that a member's encrypted profile key is the one which the
+//! issuer certified for their UID, with [`ProfileKeyCiphertext::verify`].
+//!
+//! Nothing in a presentation distinguishes a profile key credential from any
+//! other credential with three hidden group element attributes, so they are
+//! issued under a key of their own, which a [`ProfileKeyIssuer`] never uses
+//! to issue anything else.
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::vec::Vec;
+#[cfg(all(not(feature = "alloc"), feature = "std"))]
+use std::vec::Vec;
+
+use core::convert::TryFrom;
+
+use curve25519_dalek::ristretto::CompressedRistretto;
+use curve25519_dalek::ristretto::RistrettoPoint;
+use curve25519_dalek::scalar::Scalar;
+
+use rand_core::CryptoRng;
+use rand_core::RngCore;
+
+use serde::{self, Serialize, Deserialize, Serializer, Deserializer};
+use serde::de::Visitor;
+
+use sha2::Sha512;
+
+use subtle::Choice;
+use subtle::ConstantTimeEq;
+
+use zeroize::Zeroize;
+
+use crate::amacs::Attribute;
+use crate::amacs::BlindedAttribute;
+use crate::credential::CredentialRequest;
+use crate::elgamal::Ciphertext as ElGamalCiphertext;
+use crate::elgamal::Keypair as ElGamalKeypair;
+use crate::encoding::decode_32_bytes_from_group;
+use crate::encoding::SIZEOF_32_BYTES;
+use crate::errors::CredentialError;
+use crate::groups::Group;
+use crate::groups::Uid;
+use crate::issuer::BlindedIssuanceResponse;
+use crate::issuer::Issuer;
+use crate::nizk::Challenge;
+use crate::nizk::ProofOfCredentialRequest;
+use crate::nizk::ProofOfProfileKeyCommitment;
+use crate::nizk::ProofOfValidCredential;
+use crate::parameters::IssuerParameters;
+use crate::parameters::SystemParameters;
+use crate::symmetric::Ciphertext;
+use crate::symmetric::Plaintext;
+use crate::symmetric::SIZEOF_CIPHERTEXT;
+
+/// The size of a [`ProfileKey`], in bytes.
+pub const SIZEOF_PROFILE_KEY: usize = SIZEOF_32_BYTES;
+
+/// The number of attributes of a profile key credential.
+pub const NUMBER_OF_PROFILE_KEY_CREDENTIAL_ATTRIBUTES: usize = 3;
+
+/// The index of the [`Uid`] attribute of a profile key credential.
+const UID_INDEX: u16 = 0;
+
+/// A key with which a user's profile is encrypted.
+#[derive(Clone, Zeroize)]
+pub struct ProfileKey([u8; SIZEOF_PROFILE_KEY]);
+
+/// Overwrite the profile key with zeroes when it drops out of scope.
+impl Drop for ProfileKey {
+    fn drop(&mut self) {
+        self.zeroize();
+    }
+}
+
+impl ConstantTimeEq for ProfileKey {
+    fn ct_eq(&self, other: &ProfileKey) -> Choice {
+        self.0[..].ct_eq(&other.0[..])
+    }
+}
+
+impl PartialEq for ProfileKey {
+    fn eq(&self, other: &ProfileKey) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for ProfileKey {}
+
+impl ProfileKey {
+    /// Generate a new, random [`ProfileKey`].
+    pub fn generate<R>(csprng: &mut R) -> ProfileKey
+    where
+        R: RngCore + CryptoRng,
+    {
+        let mut key = [0u8; SIZEOF_PROFILE_KEY];
+
+        csprng.fill_bytes(&mut key);
+
+        ProfileKey(key)
+    }
+
+    /// Deserialise a [`ProfileKey`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ProfileKey, CredentialError> {
+        if bytes.len() != SIZEOF_PROFILE_KEY {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut key = [0u8; SIZEOF_PROFILE_KEY];
+
+        key.copy_from_slice(bytes);
+
+        Ok(ProfileKey(key))
+    }
+
+    /// Serialise this [`ProfileKey`] to a byte array.
+    pub fn to_bytes(&self) -> [u8; SIZEOF_PROFILE_KEY] {
+        self.0
+    }
+
+    /// Split this [`ProfileKey`] into two [`Plaintext`]s, one for each half,
+    /// whose encodings are those of
+    /// [`crate::encoding::encode_32_bytes_to_group`].
+    pub(crate) fn plaintexts(&self) -> Result<[Plaintext; 2], CredentialError> {
+        Ok([Plaintext::try_from(&self.0[..SIZEOF_PROFILE_KEY / 2])?,
+            Plaintext::try_from(&self.0[SIZEOF_PROFILE_KEY / 2..])?])
+    }
+
+    /// Reassemble a [`ProfileKey`] from the [`Plaintext`]s of its halves.
+    pub(crate) fn from_plaintexts(plaintexts: &[Plaintext; 2]) -> Result<ProfileKey, CredentialError> {
+        Ok(ProfileKey(decode_32_bytes_from_group(&[plaintexts[0].M1, plaintexts[1].M1])?))
+    }
+
+    /// The attributes of a profile key credential on this [`ProfileKey`] for
+    /// the user with the given `uid`.
+    pub fn attributes(&self, uid: &Uid) -> Result<Vec<Attribute>, CredentialError> {
+        let [first, second] = self.plaintexts()?;
+        let mut attributes = Vec::with_capacity(NUMBER_OF_PROFILE_KEY_CREDENTIAL_ATTRIBUTES);
+
+        attributes.push(Attribute::SecretPoint(Plaintext::try_from(&uid[..])?));
+        attributes.push(Attribute::SecretPoint(first));
+        attributes.push(Attribute::SecretPoint(second));
+
+        Ok(attributes)
+    }
+}
+
+/// A commitment to a user's [`ProfileKey`], which the issuer stores alongside
+/// their encrypted profile.
+///
+/// Each half of the profile key, encoded as \\( M_j \\), is committed to as
+/// \\( (J_j1, J_j2) = (G * s_j, M_j + H * s_j) \\), where \\( s_j \\) is derived
+/// from the profile key and the user's [`Uid`], such that the commitment is
+/// deterministic but hides the profile key.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ProfileKeyCommitment {
+    pub(crate) J: [(RistrettoPoint, RistrettoPoint); 2],
+}
+
+/// The size of a serialised [`ProfileKeyCommitment`], in bytes.
+pub const SIZEOF_PROFILE_KEY_COMMITMENT: usize = 4 * 32;
+
+impl ProfileKeyCommitment {
+    /// The generator \\( H \\) for the profile key halves' nonces.
+    pub(crate) fn generator() -> RistrettoPoint {
+        RistrettoPoint::hash_from_bytes::<Sha512>(b"2019/1416 profile key commitment")
+    }
+
+    /// Derive the nonces \\( s_j \\) of the commitment to a `profile_key`.
+    fn nonces(profile_key: &ProfileKey, uid: &Uid) -> [Scalar; 2] {
+        let mut nonces = [Scalar::zero(); 2];
+
+        for (j, nonce) in nonces.iter_mut().enumerate() {
+            let mut input: Vec<u8> = Vec::with_capacity(39 + uid.len() + 1 + SIZEOF_PROFILE_KEY);
+
+            input.extend(b"2019/1416 profile key commitment nonce".iter());
+            input.extend(uid.iter());
+            input.push(j as u8);
+            input.extend(profile_key.0.iter());
+
+            *nonce = Scalar::hash_from_bytes::<Sha512>(&input);
+            input.zeroize();
+        }
+        nonces
+    }
+
+    /// Commit to the `profile_key` of the user with the given `uid`.
+    pub fn new(
+        system_parameters: &SystemParameters,
+        profile_key: &ProfileKey,
+        uid: &Uid,
+    ) -> Result<ProfileKeyCommitment, CredentialError>
+    {
+        let plaintexts = profile_key.plaintexts()?;
+        let s = ProfileKeyCommitment::nonces(profile_key, uid);
+        let H = ProfileKeyCommitment::generator();
+
+        Ok(ProfileKeyCommitment {
+            J: [(system_parameters.G * s[0], plaintexts[0].M1 + H * s[0]),
+                (system_parameters.G * s[1], plaintexts[1].M1 + H * s[1])],
+        })
+    }
+
+    /// Deserialise a [`ProfileKeyCommitment`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ProfileKeyCommitment, CredentialError> {
+        if bytes.len() != SIZEOF_PROFILE_KEY_COMMITMENT {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut points = [RistrettoPoint::default(); 4];
+        let mut chunk = [0u8; 32];
+
+        for (i, point) in points.iter_mut().enumerate() {
+            chunk.copy_from_slice(&bytes[i * 32..(i + 1) * 32]);
+            *point = try_deserialise!("J", chunk);
+        }
+
+        Ok(ProfileKeyCommitment { J: [(points[0], points[1]), (points[2], points[3])] })
+    }
+
+    /// Serialise this [`ProfileKeyCommitment`] to a byte array.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::with_capacity(SIZEOF_PROFILE_KEY_COMMITMENT);
+
+        for (J_1, J_2) in self.J.iter() {
+            v.extend(J_1.compress().to_bytes().iter());
+            v.extend(J_2.compress().to_bytes().iter());
+        }
+        v
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(ProfileKeyCommitment, "A valid byte sequence representing a ProfileKeyCommitment");
+
+/// A request for a new profile key credential, in which the halves of the
+/// profile key are ElGamal encrypted to a key chosen by the user and proven to
+/// be those committed to by a [`ProfileKeyCommitment`].
+pub struct ProfileKeyCredentialRequest {
+    pub(crate) request: CredentialRequest,
+    pub(crate) commitment: ProfileKeyCommitment,
+    pub(crate) proof: ProofOfProfileKeyCommitment,
+}
+
+impl ProfileKeyCredentialRequest {
+    /// Create a request for a profile key credential.
+    ///
+    /// # Inputs
+    ///
+    /// * The [`SystemParameters`] for this anonymous credential instantiation,
+    /// * The user's `profile_key` and `uid`,
+    /// * An ElGamal `keypair`, which must be kept to unblind the response, and
+    /// * A `csprng`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`ProfileKeyCredentialRequest`],
+    /// otherwise a [`CredentialError`].  Once the issuer has responded, the
+    /// user obtains their credential with
+    /// [`crate::credential::AnonymousCredential::from_blinded_issuance_response`],
+    /// passing it [`ProfileKeyCredentialRequest::request`] and
+    /// [`ProfileKey::attributes`].
+    pub fn new<C>(
+        system_parameters: &SystemParameters,
+        profile_key: &ProfileKey,
+        uid: &Uid,
+        keypair: &ElGamalKeypair,
+        csprng: &mut C,
+    ) -> Result<ProfileKeyCredentialRequest, CredentialError>
+    where
+        C: CryptoRng + RngCore,
+    {
+        if system_parameters.NUMBER_OF_ATTRIBUTES as usize != NUMBER_OF_PROFILE_KEY_CREDENTIAL_ATTRIBUTES {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+
+        let commitment = ProfileKeyCommitment::new(system_parameters, profile_key, uid)?;
+        let plaintexts = profile_key.plaintexts()?;
+        let s = ProfileKeyCommitment::nonces(profile_key, uid);
+        let r = [Scalar::random(csprng), Scalar::random(csprng)];
+        let ciphertexts = [keypair.public.encrypt_with_nonce(system_parameters, &plaintexts[0].M1, &r[0]),
+                           keypair.public.encrypt_with_nonce(system_parameters, &plaintexts[1].M1, &r[1])];
+
+        let mut attributes: Vec<BlindedAttribute> = Vec::with_capacity(NUMBER_OF_PROFILE_KEY_CREDENTIAL_ATTRIBUTES);
+
+        attributes.push(BlindedAttribute::SecretPoint(Plaintext::try_from(&uid[..])?));
+        attributes.push(BlindedAttribute::EncryptedPoint(ciphertexts[0]));
+        attributes.push(BlindedAttribute::EncryptedPoint(ciphertexts[1]));
+
+        let request = CredentialRequest {
+            public_key: keypair.public,
+            proof: ProofOfCredentialRequest::prove(system_parameters, keypair, &attributes, &Vec::new()),
+            attributes: attributes,
+        };
+        let proof = ProofOfProfileKeyCommitment::prove(system_parameters, &keypair.public, &commitment,
+                                                       &ciphertexts, &[(s[0], r[0]), (s[1], r[1])]);
+
+        Ok(ProfileKeyCredentialRequest { request, commitment, proof })
+    }
+
+    /// The blinded [`CredentialRequest`] underlying this request.
+    pub fn request(&self) -> &CredentialRequest {
+        &self.request
+    }
+
+    /// The [`ProfileKeyCommitment`] to the requested profile key.
+    pub fn commitment(&self) -> &ProfileKeyCommitment {
+        &self.commitment
+    }
+
+    /// Verify that this request is for a profile key credential for the
+    /// user with the given `uid`, on the profile key committed to by the
+    /// `commitment`.
+    pub(crate) fn verify(
+        &self,
+        system_parameters: &SystemParameters,
+        uid: &Uid,
+        commitment: &ProfileKeyCommitment,
+    ) -> Result<(), CredentialError>
+    {
+        let attributes = &self.request.attributes;
+
+        if system_parameters.NUMBER_OF_ATTRIBUTES as usize != NUMBER_OF_PROFILE_KEY_CREDENTIAL_ATTRIBUTES ||
+            attributes.len() != NUMBER_OF_PROFILE_KEY_CREDENTIAL_ATTRIBUTES {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+        match &attributes[UID_INDEX as usize] {
+            BlindedAttribute::SecretPoint(p) if *p == Plaintext::try_from(&uid[..])? => (),
+            _ => return Err(CredentialError::BadAttribute),
+        }
+
+        let ciphertexts: [ElGamalCiphertext; 2] = match (&attributes[1], &attributes[2]) {
+            (BlindedAttribute::EncryptedPoint(E_0), BlindedAttribute::EncryptedPoint(E_1)) => [*E_0, *E_1],
+            _ => return Err(CredentialError::BadAttribute),
+        };
+
+        if self.commitment != *commitment {
+            return Err(CredentialError::VerificationFailure);
+        }
+
+        self.request.proof.verify(system_parameters, &self.request.public_key, attributes)?;
+        self.proof.verify(system_parameters, &self.request.public_key, commitment, &ciphertexts)
+    }
+}
+
+/// An issuer of profile key credentials, and of nothing else.
+///
+/// Its key must not also be used by an [`Issuer`], since anyone could then
+/// request a credential with another user's UID and a profile key of their
+/// choosing as bare hidden group element attributes, and pass it off as that
+/// user's profile key credential.
+pub struct ProfileKeyIssuer(Issuer);
+
+impl ProfileKeyIssuer {
+    /// Create a new [`ProfileKeyIssuer`] with a fresh key.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`ProfileKeyIssuer`], otherwise a
+    /// [`CredentialError::WrongNumberOfAttributes`] if the `system_parameters`
+    /// are not for [`NUMBER_OF_PROFILE_KEY_CREDENTIAL_ATTRIBUTES`] attributes.
+    pub fn new<C>(
+        system_parameters: &SystemParameters,
+        csprng: &mut C,
+    ) -> Result<ProfileKeyIssuer, CredentialError>
+    where
+        C: CryptoRng + RngCore,
+    {
+        if system_parameters.NUMBER_OF_ATTRIBUTES as usize != NUMBER_OF_PROFILE_KEY_CREDENTIAL_ATTRIBUTES {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+
+        Ok(ProfileKeyIssuer(Issuer::new(system_parameters, csprng)))
+    }
+
+    /// The [`SystemParameters`] of this issuer.
+    pub fn system_parameters(&self) -> &SystemParameters {
+        &self.0.system_parameters
+    }
+
+    /// The [`IssuerParameters`] under which profile key credentials are issued.
+    pub fn issuer_parameters(&self) -> &IssuerParameters {
+        &self.0.issuer_parameters
+    }
+
+    /// Issue a new profile key credential, certifying the profile key which
+    /// the user has committed to, without learning it.
+    ///
+    /// # Inputs
+    ///
+    /// * The user's `request`,
+    /// * The user's `uid`, which the caller must have authenticated,
+    /// * The [`ProfileKeyCommitment`] which the caller has stored for the
+    ///   user's current profile, and
+    /// * A `csprng`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`BlindedIssuanceResponse`], which the
+    /// user may unblind to obtain their
+    /// [`crate::credential::AnonymousCredential`], otherwise a
+    /// [`CredentialError`].
+    pub fn issue<C>(
+        &self,
+        request: &ProfileKeyCredentialRequest,
+        uid: &Uid,
+        commitment: &ProfileKeyCommitment,
+        csprng: &mut C,
+    ) -> Result<BlindedIssuanceResponse, CredentialError>
+    where
+        C: CryptoRng + RngCore,
+    {
+        request.verify(&self.0.system_parameters, uid, commitment)?;
+
+        self.0.tag_blinded(&request.request, csprng)
+    }
+
+    /// Deserialise a [`ProfileKeyIssuer`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ProfileKeyIssuer, CredentialError> {
+        let issuer = Issuer::from_bytes(bytes)?;
+
+        if issuer.system_parameters.NUMBER_OF_ATTRIBUTES as usize != NUMBER_OF_PROFILE_KEY_CREDENTIAL_ATTRIBUTES {
+            return Err(CredentialError::WrongNumberOfAttributes);
+        }
+
+        Ok(ProfileKeyIssuer(issuer))
+    }
+
+    /// Serialise this [`ProfileKeyIssuer`] to a byte array.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(ProfileKeyIssuer, "A valid byte sequence representing a ProfileKeyIssuer");
+
+/// A [`ProfileKey`] encrypted under a group's key, as the encryptions of its
+/// two halves.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ProfileKeyCiphertext(pub(crate) [Ciphertext; 2]);
+
+impl ConstantTimeEq for ProfileKeyCiphertext {
+    fn ct_eq(&self, other: &ProfileKeyCiphertext) -> Choice {
+        self.0[0].ct_eq(&other.0[0]) & self.0[1].ct_eq(&other.0[1])
+    }
+}
+
+impl ProfileKeyCiphertext {
+    /// Deserialise a [`ProfileKeyCiphertext`] from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ProfileKeyCiphertext, CredentialError> {
+        if bytes.len() != 2 * SIZEOF_CIPHERTEXT {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        Ok(ProfileKeyCiphertext([Ciphertext::from_bytes(&bytes[..SIZEOF_CIPHERTEXT])?,
+                                 Ciphertext::from_bytes(&bytes[SIZEOF_CIPHERTEXT..])?]))
+    }
+
+    /// Serialise this [`ProfileKeyCiphertext`] to a byte array.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::with_capacity(2 * SIZEOF_CIPHERTEXT);
+
+        v.extend(self.0[0].to_bytes().iter());
+        v.extend(self.0[1].to_bytes().iter());
+        v
+    }
+
+    /// Verify a presentation of a profile key credential, made with the
+    /// `group`'s keypair and bound to the server's `challenge`, and that this
+    /// is the encryption of the profile key it certifies, for a member of the
+    /// `group`.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is empty, otherwise a [`CredentialError`],
+    /// which is [`CredentialError::NotAGroupMember`] if the credential is
+    /// valid but its UID is not a member of the `group`.
+    pub fn verify(
+        &self,
+        issuer: &ProfileKeyIssuer,
+        group: &Group,
+        proof: &ProofOfValidCredential,
+        challenge: &Challenge,
+    ) -> Result<(), CredentialError>
+    {
+        let mut matches = Choice::from(1u8);
+
+        for j in 0..2 {
+            let (public_key, ciphertext) = proof.encrypted_attribute(UID_INDEX + 1 + j as u16)
+                .ok_or(CredentialError::MissingData)?;
+
+            if public_key.pk != group.public_params().public_key.pk {
+                return Err(CredentialError::VerificationFailure);
+            }
+            matches &= ciphertext.ct_eq(&self.0[j]);
+        }
+
+        group.verify_membership(&issuer.0, proof, UID_INDEX, challenge)?;
+
+        match bool::from(matches) {
+            true  => Ok(()),
+            false => Err(CredentialError::VerificationFailure),
+        }
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(ProfileKeyCiphertext, "A valid byte sequence representing a ProfileKeyCiphertext");
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rand::thread_rng;
+
+    use crate::credential::AnonymousCredential;
+    use crate::groups::GroupSecretParams;
+    use crate::groups::SIZEOF_UID;
+    use crate::nizk::PresentationOptions;
+
+    #[test]
+    fn profile_key_credential() {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let issuer = ProfileKeyIssuer::from_bytes(&ProfileKeyIssuer::new(&system_parameters, &mut rng)
+                                                  .unwrap().to_bytes()).unwrap();
+        let generic_issuer = Issuer::new(&system_parameters, &mut rng);
+        let uid: Uid = [7u8; SIZEOF_UID];
+        let profile_key = ProfileKey::generate(&mut rng);
+        let other_profile_key = ProfileKey::generate(&mut rng);
+
+        // The server stores the commitment to the user's current profile key.
+        let commitment = ProfileKeyCommitment::new(&system_parameters, &profile_key, &uid).unwrap();
+
+        assert_eq!(ProfileKeyCommitment::from_bytes(&commitment.to_bytes()).unwrap(), commitment);
+
+        let keypair = ElGamalKeypair::generate(&system_parameters, &mut rng);
+        let request = ProfileKeyCredentialRequest::new(&system_parameters, &profile_key, &uid,
+                                                       &keypair, &mut rng).unwrap();
+
+        // The bare request cannot be issued without its proof of commitment.
+        assert_eq!(generic_issuer.issue_blinded(request.request(), &mut rng).err(), Some(CredentialError::BadAttribute));
+
+        // Nor can it be issued against another profile key or user.
+        let other_commitment = ProfileKeyCommitment::new(&system_parameters, &other_profile_key, &uid).unwrap();
+
+        assert!(issuer.issue(&request, &uid, &other_commitment, &mut rng).is_err());
+        assert!(issuer.issue(&request, &[8u8; SIZEOF_UID], &commitment, &mut rng).is_err());
+
+        let response = issuer.issue(&request, &uid, &commitment, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_blinded_issuance_response(&system_parameters,
+                                                                             issuer.issuer_parameters(),
+                                                                             request.request(), &response, &keypair,
+                                                                             profile_key.attributes(&uid).unwrap()).unwrap();
+
+        // The user shares their profile key with a group they are a member of.
+        let group_secret_params = GroupSecretParams::generate(&system_parameters, &mut rng);
+        let mut group = Group::new(group_secret_params.public_params());
+
+        group.add_member(&group_secret_params.encrypt_uid(&uid).unwrap());
+
+        let ciphertext = group_secret_params.encrypt_profile_key(&profile_key).unwrap();
+        let challenge = Challenge::generate(b"example.com", &mut rng);
+        let options = PresentationOptions { challenge: Some(challenge.clone()), ..Default::default() };
+        let proof = credential.show_with_options(&system_parameters, issuer.issuer_parameters(),
+                                                 Some(group_secret_params.keypair()), &options, &mut rng).unwrap();
+        let deserialized = ProfileKeyCiphertext::from_bytes(&ciphertext.to_bytes()).unwrap();
+
+        assert!(deserialized.verify(&issuer, &group, &proof, &challenge).is_ok());
+        assert!(group_secret_params.decrypt_profile_key(&ciphertext).unwrap() == profile_key);
+
+        // Another profile key cannot be passed off as the certified one.
+        let other_ciphertext = group_secret_params.encrypt_profile_key(&other_profile_key).unwrap();
+
+        assert_eq!(other_ciphertext.verify(&issuer, &group, &proof, &challenge).err(), Some(CredentialError::VerificationFailure));
+
+        // Nor can a credential from another issuer, on which anyone's UID may be paired with any profile key.
+        let attributes = other_profile_key.attributes(&uid).unwrap();
+        let response = generic_issuer.issue(&attributes, &mut rng).unwrap();
+        let forged = AnonymousCredential::from_issuance_response(&system_parameters, &generic_issuer.issuer_parameters,
+                                                                 &response, attributes).unwrap();
+        let forged_proof = forged.show_with_options(&system_parameters, &generic_issuer.issuer_parameters,
+                                                    Some(group_secret_params.keypair()), &options, &mut rng).unwrap();
+
+        assert!(other_ciphertext.verify(&issuer, &group, &forged_proof, &challenge).is_err());
+
+        // Nor can the profile key of someone who is not a member.
+        let other_group_secret_params = GroupSecretParams::generate(&system_parameters, &mut rng);
+        let other_group = Group::new(other_group_secret_params.public_params());
+        let proof = credential.show_with_options(&system_parameters, issuer.issuer_parameters(),
+                                                 Some(other_group_secret_params.keypair()), &options, &mut rng).unwrap();
+        let ciphertext = other_group_secret_params.encrypt_profile_key(&profile_key).unwrap();
+
+        assert_eq!(ciphertext.verify(&issuer, &other_group, &proof, &challenge).err(), Some(CredentialError::NotAGroupMember));
+    }
+}
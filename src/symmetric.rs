@@ -22,6 +22,14 @@
 //! * is correct under adversarially chosen keys, meaning that it is hard to
 //!   find a key and a message that cause decryption to fail.
 
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::vec::Vec;
+#[cfg(all(not(feature = "alloc"), feature = "std"))]
+use std::vec::Vec;
+
+use core::convert::TryFrom;
+
+use curve25519_dalek::ristretto::CompressedRistretto;
 use curve25519_dalek::ristretto::RistrettoPoint;
 use curve25519_dalek::scalar::Scalar;
 use curve25519_dalek::traits::Identity;
@@ -29,6 +37,10 @@ use curve25519_dalek::traits::Identity;
 use rand_core::CryptoRng;
 use rand_core::RngCore;
 
+use serde::{self, Serialize, Deserialize, Serializer, Deserializer};
+use serde::de::Visitor;
+
+use sha2::Digest;
 use sha2::Sha512;
 
 use subtle::Choice;
@@ -39,6 +51,7 @@ use zeroize::Zeroize;
 use crate::encoding::decode_from_group;
 use crate::encoding::encode_to_group;
 use crate::errors::CredentialError;
+use crate::expiry::Epoch;
 use crate::parameters::SystemParameters;
 
 /// A secret key, used for hidden group element attributes during credential
@@ -79,6 +92,24 @@ pub type MasterSecret = [u8; 64];
 
 // XXX impl Drop for MasterSecret
 
+/// Derive the [`MasterSecret`] for some `epoch` from a long-term
+/// `master_secret`.
+///
+/// Successive epochs' secrets are independent of one another, so rotating to
+/// the keypair for a new epoch, with [`Keypair::derive_for_epoch`], changes
+/// every ciphertext, while only the long-term master secret must be stored.
+pub fn master_secret_for_epoch(master_secret: &MasterSecret, epoch: Epoch) -> MasterSecret {
+    let mut hash = Sha512::new();
+    let mut epoch_secret: MasterSecret = [0u8; 64];
+
+    hash.input(b"2019/1416 symmetric key rotation");
+    hash.input(&master_secret[..]);
+    hash.input(&epoch.to_le_bytes());
+    epoch_secret.copy_from_slice(&hash.result()[..]);
+
+    epoch_secret
+}
+
 /// A plaintext encodes up to thrity bytes of information into a group element.
 #[derive(Clone, Debug)]
 pub struct Plaintext {
@@ -109,20 +140,65 @@ impl Drop for Plaintext {
     }
 }
 
-impl From<&[u8; 30]> for Plaintext {
-    fn from(source: &[u8; 30]) -> Plaintext {
-        let (M1, _) = encode_to_group(source);
+/// Create a [`Plaintext`] from up to 30 bytes of data.
+impl TryFrom<&[u8]> for Plaintext {
+    type Error = CredentialError;
+
+    fn try_from(source: &[u8]) -> Result<Plaintext, CredentialError> {
+        let (M1, _) = encode_to_group(source)?;
         let M2: RistrettoPoint = RistrettoPoint::hash_from_bytes::<Sha512>(source);
         let m3: Scalar = Scalar::hash_from_bytes::<Sha512>(source);
 
-        Plaintext { M1, M2, m3 }
+        Ok(Plaintext { M1, M2, m3 })
     }
 }
 
+/// Recover the exact data which a [`Plaintext`] was created from.
+//
 // XXX TODO return attempt counter
-impl From<&Plaintext> for [u8; 30] {
-    fn from(source: &Plaintext) -> [u8; 30] {
-        decode_from_group(&source.M1).0
+impl TryFrom<&Plaintext> for Vec<u8> {
+    type Error = CredentialError;
+
+    fn try_from(source: &Plaintext) -> Result<Vec<u8>, CredentialError> {
+        Ok(decode_from_group(&source.M1)?.0)
+    }
+}
+
+/// The size of a serialised [`Plaintext`], in bytes.
+pub(crate) const SIZEOF_PLAINTEXT: usize = 96;
+
+impl Plaintext {
+    /// Deserialise a [`Plaintext`] from its compressed points \(( (M_1, M_2) \))
+    /// and its scalar \(( m_3 \)).
+    pub(crate) fn from_bytes(bytes: &[u8]) -> Result<Plaintext, CredentialError> {
+        if bytes.len() != SIZEOF_PLAINTEXT {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut chunk = [0u8; 32];
+
+        chunk.copy_from_slice(&bytes[..32]);
+        let M1: RistrettoPoint = try_deserialise!("M1", chunk);
+
+        chunk.copy_from_slice(&bytes[32..64]);
+        let M2: RistrettoPoint = try_deserialise!("M2", chunk);
+
+        chunk.copy_from_slice(&bytes[64..96]);
+        let m3: Scalar = try_deserialise_scalar!("m3", chunk);
+
+        chunk.zeroize();
+
+        Ok(Plaintext { M1, M2, m3 })
+    }
+
+    /// Serialise this [`Plaintext`] to a byte array.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::with_capacity(SIZEOF_PLAINTEXT);
+
+        v.extend(self.M1.compress().to_bytes().iter());
+        v.extend(self.M2.compress().to_bytes().iter());
+        v.extend(self.m3.as_bytes().iter());
+        v
     }
 }
 
@@ -173,6 +249,36 @@ impl Keypair {
         }
     }
 
+    /// Derive the successor [`Keypair`] for some `epoch` from a long-term
+    /// master secret.
+    ///
+    /// Existing ciphertexts may be moved to the new keypair, along with a
+    /// proof that they still encrypt the same plaintexts, with
+    /// [`crate::nizk::ProofOfPlaintextEquality::reencrypt`].
+    ///
+    /// # Inputs
+    ///
+    /// * A [`MasterSecret`],
+    /// * The [`Epoch`] to derive a keypair for, and
+    /// * some [`SystemParameters`].
+    ///
+    /// # Returns
+    ///
+    /// The `Keypair` for the `epoch`.
+    pub fn derive_for_epoch(
+        master_secret: &MasterSecret,
+        epoch: Epoch,
+        system_parameters: &SystemParameters,
+    ) -> Keypair
+    {
+        let mut epoch_secret = master_secret_for_epoch(master_secret, epoch);
+        let keypair = Keypair::derive(&epoch_secret, system_parameters);
+
+        epoch_secret.zeroize();
+
+        keypair
+    }
+
     /// Generate a new keypair.
     ///
     /// # Inputs
@@ -219,7 +325,10 @@ impl Keypair {
     ) -> Result<Plaintext, CredentialError>
     {
         let M1_prime = ciphertext.E2 - (ciphertext.E1 * self.secret.a);
-        let (m_prime, _) = decode_from_group(&M1_prime);
+        let (m_prime, _) = match decode_from_group(&M1_prime) {
+            Ok(x)  => x,
+            Err(_) => return Err(CredentialError::UndecryptableAttribute),
+        };
         let m3_prime = Scalar::hash_from_bytes::<Sha512>(&m_prime);
 
         let M2_prime = RistrettoPoint::hash_from_bytes::<Sha512>(&m_prime);
@@ -233,11 +342,181 @@ impl Keypair {
 }
 
 /// DOCDOC
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub struct Ciphertext {
     pub E1: RistrettoPoint,
     pub E2: RistrettoPoint,
 }
 
+/// The size of a serialised [`Ciphertext`], in bytes.
+pub(crate) const SIZEOF_CIPHERTEXT: usize = 64;
+
+impl Ciphertext {
+    /// Deserialise a [`Ciphertext`] from its two compressed points.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Ciphertext, CredentialError> {
+        if bytes.len() != SIZEOF_CIPHERTEXT {
+            return Err(CredentialError::WrongNumberOfBytes);
+        }
+
+        let mut chunk = [0u8; 32];
+
+        chunk.copy_from_slice(&bytes[0..32]);
+        let E1: RistrettoPoint = try_deserialise!("E1", chunk);
+
+        chunk.copy_from_slice(&bytes[32..64]);
+        let E2: RistrettoPoint = try_deserialise!("E2", chunk);
+
+        Ok(Ciphertext { E1, E2 })
+    }
+
+    /// Serialise this [`Ciphertext`] as its two compressed points.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut v: Vec<u8> = Vec::with_capacity(SIZEOF_CIPHERTEXT);
+
+        v.extend(self.E1.compress().to_bytes().iter());
+        v.extend(self.E2.compress().to_bytes().iter());
+        v
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(Ciphertext, "A valid byte sequence representing a symmetric::Ciphertext");
+
+impl ConstantTimeEq for Ciphertext {
+    fn ct_eq(&self, other: &Ciphertext) -> Choice {
+        self.E1.compress().ct_eq(&other.E1.compress()) &
+        self.E2.compress().ct_eq(&other.E2.compress())
+    }
+}
+
+/// A stable, hashable form of a [`Ciphertext`], for use as a lookup key.
+///
+/// Since ciphertexts are unique, that is, every plaintext has exactly one
+/// ciphertext under a given key, two [`CiphertextIndex`]s are equal if and
+/// only if their ciphertexts encrypt the same plaintext under the same key,
+/// allowing e.g. a server to store a list of a group's encrypted members and
+/// to look up a member without learning who they are.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct CiphertextIndex([u8; SIZEOF_CIPHERTEXT]);
+
+impl CiphertextIndex {
+    /// Deserialise a [`CiphertextIndex`], checking that it is the index of a
+    /// valid [`Ciphertext`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<CiphertextIndex, CredentialError> {
+        let ciphertext = Ciphertext::from_bytes(bytes)?;
+
+        Ok(CiphertextIndex::from(&ciphertext))
+    }
+
+    /// Serialise this [`CiphertextIndex`] as the two compressed points of its
+    /// [`Ciphertext`].
+    pub fn to_bytes(&self) -> [u8; SIZEOF_CIPHERTEXT] {
+        self.0
+    }
+}
+
+impl From<&Ciphertext> for CiphertextIndex {
+    fn from(source: &Ciphertext) -> CiphertextIndex {
+        let mut index = [0u8; SIZEOF_CIPHERTEXT];
+
+        index[..32].copy_from_slice(source.E1.compress().as_bytes());
+        index[32..].copy_from_slice(source.E2.compress().as_bytes());
+
+        CiphertextIndex(index)
+    }
+}
+
+impl TryFrom<&CiphertextIndex> for Ciphertext {
+    type Error = CredentialError;
+
+    fn try_from(source: &CiphertextIndex) -> Result<Ciphertext, CredentialError> {
+        Ciphertext::from_bytes(&source.0[..])
+    }
+}
+
+impl ConstantTimeEq for CiphertextIndex {
+    fn ct_eq(&self, other: &CiphertextIndex) -> Choice {
+        self.0[..].ct_eq(&other.0[..])
+    }
+}
+
+impl_serde_with_to_bytes_and_from_bytes!(CiphertextIndex, "A valid byte sequence representing a symmetric::CiphertextIndex");
+
+/// A set of stored [`Ciphertext`]s, such as the encrypted members of a group,
+/// which can be checked for membership of a presented ciphertext in constant
+/// time.
+///
+/// Membership checks compare the presented ciphertext against every stored
+/// one, and so take time linear in, but otherwise independent of, the size of
+/// the set.  Insertion and removal are not constant time, as they are
+/// expected to be performed by whoever manages the set.
+#[derive(Clone, Debug, Default)]
+pub struct CiphertextSet {
+    indices: Vec<CiphertextIndex>,
+}
+
+impl CiphertextSet {
+    /// Create a new, empty [`CiphertextSet`].
+    pub fn new() -> CiphertextSet {
+        CiphertextSet { indices: Vec::new() }
+    }
+
+    /// The number of ciphertexts in this set.
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Whether this set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Add a `ciphertext` to this set.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the `ciphertext` was not already in this set, otherwise `false`.
+    pub fn insert(&mut self, ciphertext: &Ciphertext) -> bool {
+        let index = CiphertextIndex::from(ciphertext);
+
+        if self.indices.contains(&index) {
+            return false;
+        }
+        self.indices.push(index);
+        true
+    }
+
+    /// Remove a `ciphertext` from this set.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the `ciphertext` was in this set, otherwise `false`.
+    pub fn remove(&mut self, ciphertext: &Ciphertext) -> bool {
+        let index = CiphertextIndex::from(ciphertext);
+
+        match self.indices.iter().position(|x| *x == index) {
+            Some(i) => { self.indices.swap_remove(i); true },
+            None    => false,
+        }
+    }
+
+    /// Check, in constant time, whether a `ciphertext` is in this set.
+    pub fn contains(&self, ciphertext: &Ciphertext) -> Choice {
+        let index = CiphertextIndex::from(ciphertext);
+        let mut found = Choice::from(0u8);
+
+        for stored in self.indices.iter() {
+            found |= stored.ct_eq(&index);
+        }
+        found
+    }
+
+    /// An iterator over the [`CiphertextIndex`]s in this set, in no
+    /// particular order.
+    pub fn iter(&self) -> impl Iterator<Item = &CiphertextIndex> {
+        self.indices.iter()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -250,11 +529,75 @@ mod test {
         let system_parameters = SystemParameters::hash_and_pray(&mut csprng, 2).unwrap();
         let (keypair, master_secret) = Keypair::generate(&system_parameters, &mut csprng);
         let message = [0u8; 30];
-        let plaintext: Plaintext = (&message).into();
+        let plaintext = Plaintext::try_from(&message[..]).unwrap();
         let ciphertext = keypair.encrypt(&plaintext);
         let decrypted = keypair.decrypt(&ciphertext);
 
         assert!(decrypted.is_ok());
         assert_eq!(plaintext, decrypted.unwrap());
     }
+
+    #[test]
+    fn ciphertext_serialize_deserialize() {
+        let mut csprng = thread_rng();
+        let system_parameters = SystemParameters::hash_and_pray(&mut csprng, 2).unwrap();
+        let (keypair, _) = Keypair::generate(&system_parameters, &mut csprng);
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
+        let ciphertext = keypair.encrypt(&plaintext);
+        let deserialized = Ciphertext::from_bytes(&ciphertext.to_bytes()).unwrap();
+
+        assert_eq!(ciphertext, deserialized);
+        assert!(Ciphertext::from_bytes(&ciphertext.to_bytes()[..63]).is_err());
+    }
+
+    #[test]
+    fn ciphertext_index_and_set() {
+        let mut csprng = thread_rng();
+        let system_parameters = SystemParameters::hash_and_pray(&mut csprng, 2).unwrap();
+        let (keypair, _) = Keypair::generate(&system_parameters, &mut csprng);
+        let (other_keypair, _) = Keypair::generate(&system_parameters, &mut csprng);
+        let alice = Plaintext::try_from(&b"alice"[..]).unwrap();
+        let bob = Plaintext::try_from(&b"bob"[..]).unwrap();
+
+        // Encryptions of the same plaintext under the same key have the same index.
+        let index = CiphertextIndex::from(&keypair.encrypt(&alice));
+
+        assert_eq!(index, CiphertextIndex::from(&keypair.encrypt(&alice)));
+        assert_ne!(index, CiphertextIndex::from(&keypair.encrypt(&bob)));
+        assert_ne!(index, CiphertextIndex::from(&other_keypair.encrypt(&alice)));
+        assert_eq!(CiphertextIndex::from_bytes(&index.to_bytes()).unwrap(), index);
+        assert_eq!(Ciphertext::try_from(&index).unwrap(), keypair.encrypt(&alice));
+
+        let mut members = CiphertextSet::new();
+
+        assert!(members.insert(&keypair.encrypt(&alice)));
+        assert!(!members.insert(&keypair.encrypt(&alice)));
+        assert!(members.insert(&keypair.encrypt(&bob)));
+        assert_eq!(members.len(), 2);
+
+        assert!(bool::from(members.contains(&keypair.encrypt(&alice))));
+        assert!(!bool::from(members.contains(&other_keypair.encrypt(&alice))));
+
+        assert!(members.remove(&keypair.encrypt(&alice)));
+        assert!(!members.remove(&keypair.encrypt(&alice)));
+        assert!(!bool::from(members.contains(&keypair.encrypt(&alice))));
+        assert!(bool::from(members.contains(&keypair.encrypt(&bob))));
+    }
+
+    #[test]
+    fn derive_for_epoch() {
+        let mut csprng = thread_rng();
+        let system_parameters = SystemParameters::hash_and_pray(&mut csprng, 2).unwrap();
+        let (keypair, master_secret) = Keypair::generate(&system_parameters, &mut csprng);
+        let first = Keypair::derive_for_epoch(&master_secret, 1, &system_parameters);
+        let second = Keypair::derive_for_epoch(&master_secret, 2, &system_parameters);
+        let plaintext = Plaintext::try_from(&b"This is a tsunami alert test.."[..]).unwrap();
+
+        assert_eq!(first.public.pk, Keypair::derive_for_epoch(&master_secret, 1, &system_parameters).public.pk);
+        assert_ne!(first.public.pk, second.public.pk);
+        assert_ne!(first.public.pk, keypair.public.pk);
+        assert_ne!(first.encrypt(&plaintext), second.encrypt(&plaintext));
+        assert!(second.decrypt(&first.encrypt(&plaintext)).is_err());
+        assert_eq!(first.decrypt(&first.encrypt(&plaintext)).unwrap(), plaintext);
+    }
 }
@@ -0,0 +1,442 @@
+// -*- mode: rust; -*-
+//
+// This file is part of aeonflux.
+// Copyright (c) 2020 The Brave Authors
+// See LICENSE for licensing information.
+//
+// Authors:
+// - isis agora lovecruft <isis@patternsinthevoid.net>
+
+//! Verifiers which remember the nullifiers of the presentations they accept.
+//!
+//! A credential presented with a [`SerialNumber`] may be shown only once: the
+//! [`Verifier`] records the serial number of every presentation it accepts in
+//! a [`NullifierStore`], and rejects any later presentation with the same one.
+//! Likewise, a credential presented with a rate-limiting [`Tag`] may be shown
+//! only as many times per epoch as its [`RateLimit`] allows.
+
+#[cfg(all(not(feature = "alloc"), feature = "std"))]
+use std::vec::Vec;
+
+#[cfg(all(not(feature = "std"), feature = "alloc"))]
+use alloc::collections::BTreeSet;
+#[cfg(all(not(feature = "alloc"), feature = "std"))]
+use std::collections::BTreeSet;
+
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::fs::OpenOptions;
+#[cfg(feature = "std")]
+use std::io::Read;
+#[cfg(feature = "std")]
+use std::io::Write;
+#[cfg(feature = "std")]
+use std::path::Path;
+
+use crate::errors::CredentialError;
+use crate::issuer::Issuer;
+use crate::nizk::Challenge;
+use crate::nizk::ProofOfValidCredential;
+use crate::nizk::RateLimit;
+use crate::nizk::SerialNumber;
+use crate::nizk::Tag;
+
+/// A nullifier, such as the bytes of a [`SerialNumber`], which may be spent
+/// at most once.
+pub type Nullifier = [u8; 32];
+
+/// A set of the nullifiers which a verifier has seen.
+pub trait NullifierStore {
+    /// Determine whether the `nullifier` has been seen.
+    fn contains(&self, nullifier: &Nullifier) -> Result<bool, CredentialError>;
+
+    /// Record the `nullifier`, unless it has already been seen.
+    ///
+    /// Checking and recording must happen atomically, such that of several
+    /// insertions of the same nullifier exactly one succeeds.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is `true` if the `nullifier` had not been
+    /// seen before, and `false` if it had, otherwise a [`CredentialError`],
+    /// which is [`CredentialError::NullifierStorage`] if the store could not
+    /// be written.
+    fn insert(&mut self, nullifier: &Nullifier) -> Result<bool, CredentialError>;
+}
+
+impl NullifierStore for BTreeSet<Nullifier> {
+    fn contains(&self, nullifier: &Nullifier) -> Result<bool, CredentialError> {
+        Ok(BTreeSet::contains(self, nullifier))
+    }
+
+    fn insert(&mut self, nullifier: &Nullifier) -> Result<bool, CredentialError> {
+        Ok(BTreeSet::insert(self, *nullifier))
+    }
+}
+
+/// A [`NullifierStore`] which persists nullifiers to a file, so that they
+/// survive restarts of the verifier.
+///
+/// The file is a sequence of 32-byte nullifiers, to which new ones are
+/// appended and synced before they are reported as recorded.  The nullifiers
+/// are also kept in memory for lookups.  Only one `FileNullifierStore` should
+/// be open on a file at a time.
+///
+/// A nullifier which was only partially written, e.g. because the verifier
+/// crashed, was never reported as recorded, and so is discarded.
+#[cfg(feature = "std")]
+pub struct FileNullifierStore {
+    file: File,
+    len: u64,
+    nullifiers: BTreeSet<Nullifier>,
+}
+
+#[cfg(feature = "std")]
+impl FileNullifierStore {
+    /// Open the [`FileNullifierStore`] at `path`, creating it if need be.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is a [`FileNullifierStore`] holding every
+    /// nullifier previously recorded in the file, otherwise a
+    /// [`CredentialError::NullifierStorage`] if it could not be opened, or a
+    /// trailing partial nullifier could not be truncated from it.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FileNullifierStore, CredentialError> {
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)
+            .or(Err(CredentialError::NullifierStorage))?;
+        let mut bytes: Vec<u8> = Vec::new();
+
+        file.read_to_end(&mut bytes).or(Err(CredentialError::NullifierStorage))?;
+
+        let len = (bytes.len() - bytes.len() % 32) as u64;
+
+        if bytes.len() as u64 != len {
+            file.set_len(len).or(Err(CredentialError::NullifierStorage))?;
+            file.sync_data().or(Err(CredentialError::NullifierStorage))?;
+        }
+
+        let mut nullifiers: BTreeSet<Nullifier> = BTreeSet::new();
+
+        for chunk in bytes.chunks_exact(32) {
+            let mut nullifier = [0u8; 32];
+
+            nullifier.copy_from_slice(chunk);
+            nullifiers.insert(nullifier);
+        }
+
+        Ok(FileNullifierStore { file, len, nullifiers })
+    }
+}
+
+#[cfg(feature = "std")]
+impl NullifierStore for FileNullifierStore {
+    fn contains(&self, nullifier: &Nullifier) -> Result<bool, CredentialError> {
+        Ok(self.nullifiers.contains(nullifier))
+    }
+
+    fn insert(&mut self, nullifier: &Nullifier) -> Result<bool, CredentialError> {
+        if self.nullifiers.contains(nullifier) {
+            return Ok(false);
+        }
+
+        // Discard anything written by a failed insertion, such that the next
+        // nullifier is still appended at a multiple of 32 bytes.
+        if self.file.write_all(nullifier).and_then(|_| self.file.sync_data()).is_err() {
+            let _ = self.file.set_len(self.len);
+
+            return Err(CredentialError::NullifierStorage);
+        }
+        self.len += 32;
+        self.nullifiers.insert(*nullifier);
+
+        Ok(true)
+    }
+}
+
+/// A verifier which accepts each credential presented with a [`SerialNumber`]
+/// at most once.
+pub struct Verifier<S: NullifierStore> {
+    issuer: Issuer,
+    store: S,
+    serial_number_index: u16,
+}
+
+impl<S: NullifierStore> Verifier<S> {
+    /// Create a [`Verifier`] for credentials from the `issuer`, which records
+    /// the serial numbers of accepted presentations in the `store`.
+    ///
+    /// Serial numbers must be derived from the hidden scalar attribute at
+    /// `serial_number_index`, since a credential with several hidden scalars
+    /// could otherwise be shown once with each of them.
+    pub fn new(issuer: Issuer, store: S, serial_number_index: u16) -> Verifier<S> {
+        Verifier { issuer, store, serial_number_index }
+    }
+
+    /// The [`Issuer`] whose credentials this verifier accepts.
+    pub fn issuer(&self) -> &Issuer {
+        &self.issuer
+    }
+
+    /// The [`NullifierStore`] of this verifier.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Verify a [`ProofOfValidCredential`] and record its [`SerialNumber`].
+    ///
+    /// The serial number is only recorded once the `proof` has verified, such
+    /// that invalid presentations cannot spend other users' credentials.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the recorded [`SerialNumber`], otherwise
+    /// a [`CredentialError`], which is [`CredentialError::MissingData`] if the
+    /// presentation has no serial number and
+    /// [`CredentialError::SpentNullifier`] if it has been seen before.  A
+    /// serial number derived from any attribute other than the verifier's is
+    /// a [`CredentialError::VerificationFailure`].
+    pub fn verify(
+        &mut self,
+        proof: &ProofOfValidCredential,
+    ) -> Result<SerialNumber, CredentialError>
+    {
+        let serial_number = proof.verify_with_serial_number(&self.issuer, self.serial_number_index)?;
+
+        self.spend(serial_number)
+    }
+
+    /// As [`Verifier::verify`], but additionally checking that the `proof`
+    /// is bound to the verifier's `challenge`.
+    pub fn verify_with_challenge(
+        &mut self,
+        proof: &ProofOfValidCredential,
+        challenge: &Challenge,
+    ) -> Result<SerialNumber, CredentialError>
+    {
+        let serial_number = match proof.serial_number() {
+            Some((i, serial_number)) if i == self.serial_number_index => serial_number,
+            Some(_) => return Err(CredentialError::VerificationFailure),
+            None    => return Err(CredentialError::MissingData),
+        };
+
+        proof.verify_with_challenge(&self.issuer, challenge)?;
+        self.spend(serial_number)
+    }
+
+    /// Verify a [`ProofOfValidCredential`] within the `rate_limit` and record
+    /// its [`Tag`].
+    ///
+    /// Tags from past epochs can never be presented again, and so may be
+    /// pruned from the store by the caller.
+    ///
+    /// # Returns
+    ///
+    /// A `Result` whose `Ok` value is the recorded [`Tag`], otherwise a
+    /// [`CredentialError`], which is [`CredentialError::SpentNullifier`] if the
+    /// credential has been presented too many times within the epoch.
+    pub fn verify_rate_limited(
+        &mut self,
+        proof: &ProofOfValidCredential,
+        rate_limit: &RateLimit,
+    ) -> Result<Tag, CredentialError>
+    {
+        let tag = proof.verify_rate_limited(&self.issuer, rate_limit)?;
+
+        match self.store.insert(&tag.to_bytes())? {
+            true  => Ok(tag),
+            false => Err(CredentialError::SpentNullifier),
+        }
+    }
+
+    /// Record the `serial_number` of a verified presentation.
+    fn spend(
+        &mut self,
+        serial_number: SerialNumber,
+    ) -> Result<SerialNumber, CredentialError>
+    {
+        match self.store.insert(&serial_number.to_bytes())? {
+            true  => Ok(serial_number),
+            false => Err(CredentialError::SpentNullifier),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use curve25519_dalek::scalar::Scalar;
+
+    use rand::thread_rng;
+    use rand::RngCore;
+
+    use crate::amacs::Attribute;
+    use crate::credential::AnonymousCredential;
+    use crate::nizk::PresentationOptions;
+    use crate::parameters::SystemParameters;
+
+    fn one_show_credential() -> (Issuer, AnonymousCredential) {
+        let mut rng = thread_rng();
+        let system_parameters = SystemParameters::generate(&mut rng, 3).unwrap();
+        let issuer = Issuer::new(&system_parameters, &mut rng);
+
+        let mut attributes = Vec::new();
+
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::PublicScalar(Scalar::random(&mut rng)));
+        attributes.push(Attribute::SecretScalar(Scalar::random(&mut rng)));
+
+        let response = issuer.issue(&attributes, &mut rng).unwrap();
+        let credential = AnonymousCredential::from_issuance_response(&system_parameters, &issuer.issuer_parameters,
+                                                                     &response, attributes).unwrap();
+
+        (issuer, credential)
+    }
+
+    #[test]
+    fn verifier_rejects_spent_serial_numbers() {
+        let mut rng = thread_rng();
+        let (issuer, credential) = one_show_credential();
+        let system_parameters = issuer.system_parameters.clone();
+        let issuer_parameters = issuer.issuer_parameters.clone();
+        let mut verifier = Verifier::new(issuer, BTreeSet::new(), 0);
+
+        let options = PresentationOptions { serial_number: Some(0), ..Default::default() };
+        let first = credential.show_with_options(&system_parameters, &issuer_parameters, None, &options, &mut rng).unwrap();
+        let second = credential.show_with_options(&system_parameters, &issuer_parameters, None, &options, &mut rng).unwrap();
+        let unserialed = credential.show(&system_parameters, &issuer_parameters, None, &mut rng).unwrap();
+
+        assert_eq!(verifier.verify(&unserialed).err(), Some(CredentialError::MissingData));
+        assert!(verifier.verify(&first).is_ok());
+        assert_eq!(verifier.verify(&second).err(), Some(CredentialError::SpentNullifier));
+        assert_eq!(verifier.store().len(), 1);
+
+        // Presentations bound to a challenge are checked against it first.
+        let challenge = Challenge::generate(b"example.com", &mut rng);
+        let options = PresentationOptions { serial_number: Some(0), challenge: Some(challenge.clone()), ..Default::default() };
+        let third = credential.show_with_options(&system_parameters, &issuer_parameters, None, &options, &mut rng).unwrap();
+
+        assert_eq!(verifier.verify_with_challenge(&third, &Challenge::generate(b"example.com", &mut rng)).err(),
+                   Some(CredentialError::VerificationFailure));
+        assert_eq!(verifier.verify_with_challenge(&third, &challenge).err(),
+                   Some(CredentialError::SpentNullifier));
+
+        // A serial number derived from another hidden scalar is refused, and not spent.
+        let options = PresentationOptions { serial_number: Some(2), challenge: Some(challenge.clone()), ..Default::default() };
+        let fourth = credential.show_with_options(&system_parameters, &issuer_parameters, None, &options, &mut rng).unwrap();
+
+        assert!(fourth.verify_with_challenge(verifier.issuer(), &challenge).is_ok());
+        assert_eq!(verifier.verify(&fourth).err(), Some(CredentialError::VerificationFailure));
+        assert_eq!(verifier.verify_with_challenge(&fourth, &challenge).err(),
+                   Some(CredentialError::VerificationFailure));
+        assert_eq!(verifier.store().len(), 1);
+    }
+
+    #[test]
+    fn verifier_rate_limits_presentations() {
+        let mut rng = thread_rng();
+        let (issuer, credential) = one_show_credential();
+        let system_parameters = issuer.system_parameters.clone();
+        let issuer_parameters = issuer.issuer_parameters.clone();
+        let mut verifier = Verifier::new(issuer, BTreeSet::new(), 0);
+        let rate_limit = RateLimit { index: 0, label: b"example.com".to_vec(), epoch: 7, limit: 3 };
+
+        let mut show = |rate_limit: &RateLimit, j: u16| {
+            let options = PresentationOptions { rate_limit: Some((rate_limit.clone(), j)), ..Default::default() };
+
+            credential.show_with_options(&system_parameters, &issuer_parameters, None, &options, &mut rng)
+        };
+
+        // The credential may be shown once for each counter below the limit.
+        let mut tags = Vec::new();
+
+        for j in 0..3 {
+            let proof = show(&rate_limit, j).unwrap();
+            let deserialized = ProofOfValidCredential::from_bytes(&proof.to_bytes()).unwrap();
+
+            tags.push(verifier.verify_rate_limited(&deserialized, &rate_limit).unwrap());
+        }
+        assert!(tags[0] != tags[1] && tags[1] != tags[2] && tags[0] != tags[2]);
+
+        // Any further presentation within the epoch repeats a tag.
+        assert_eq!(show(&rate_limit, 3).err(), Some(CredentialError::UnsatisfiedPredicate));
+        assert_eq!(verifier.verify_rate_limited(&show(&rate_limit, 1).unwrap(), &rate_limit).err(),
+                   Some(CredentialError::SpentNullifier));
+
+        // The limit resets in the next epoch, and presentations must be within the verifier's limit.
+        let next = RateLimit { epoch: 8, ..rate_limit.clone() };
+        let proof = show(&next, 0).unwrap();
+
+        assert_eq!(verifier.verify_rate_limited(&proof, &rate_limit).err(), Some(CredentialError::VerificationFailure));
+        assert!(verifier.verify_rate_limited(&proof, &next).is_ok());
+
+        let unlimited = show(&RateLimit { limit: 4, ..rate_limit.clone() }, 3).unwrap();
+
+        assert_eq!(verifier.verify_rate_limited(&unlimited, &rate_limit).err(), Some(CredentialError::VerificationFailure));
+    }
+
+    #[test]
+    fn file_nullifier_store() {
+        let mut rng = thread_rng();
+        let mut path = std::env::temp_dir();
+
+        path.push(format!("aeonflux-nullifiers-{}", rng.next_u64()));
+
+        let mut nullifier = [0u8; 32];
+
+        rng.fill_bytes(&mut nullifier);
+
+        {
+            let mut store = FileNullifierStore::open(&path).unwrap();
+
+            assert_eq!(store.insert(&nullifier), Ok(true));
+            assert_eq!(store.insert(&nullifier), Ok(false));
+        }
+
+        // The nullifier is remembered once the store is reopened.
+        let mut store = FileNullifierStore::open(&path).unwrap();
+
+        assert_eq!(store.contains(&nullifier), Ok(true));
+        assert_eq!(store.insert(&nullifier), Ok(false));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn file_nullifier_store_truncates_partial_nullifiers() {
+        let mut rng = thread_rng();
+        let mut path = std::env::temp_dir();
+
+        path.push(format!("aeonflux-nullifiers-{}", rng.next_u64()));
+
+        let mut nullifiers = [[0u8; 32]; 2];
+
+        rng.fill_bytes(&mut nullifiers[0]);
+        rng.fill_bytes(&mut nullifiers[1]);
+
+        // A crash part way through recording the second nullifier.
+        let mut bytes = nullifiers[0].to_vec();
+
+        bytes.extend_from_slice(&nullifiers[1][..13]);
+        std::fs::write(&path, &bytes).unwrap();
+
+        {
+            let mut store = FileNullifierStore::open(&path).unwrap();
+
+            assert_eq!(store.contains(&nullifiers[0]), Ok(true));
+            assert_eq!(store.contains(&nullifiers[1]), Ok(false));
+            assert_eq!(store.insert(&nullifiers[1]), Ok(true));
+        }
+
+        // Later nullifiers are appended at whole records.
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), 64);
+
+        let store = FileNullifierStore::open(&path).unwrap();
+
+        assert_eq!(store.contains(&nullifiers[0]), Ok(true));
+        assert_eq!(store.contains(&nullifiers[1]), Ok(true));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}